@@ -0,0 +1,24 @@
+-- Customer names are unique regardless of case
+CREATE TABLE customers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    email TEXT,
+    phone TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+ALTER TABLE orders ADD COLUMN customer_id INTEGER REFERENCES customers(id);
+
+CREATE INDEX IX_orders_customer_id ON orders(customer_id);
+
+-- Backfill customers from the free-text names on existing orders
+INSERT OR IGNORE INTO customers (name, created_at, updated_at)
+SELECT customer_name, MIN(created_at), MAX(updated_at)
+FROM orders
+WHERE customer_id IS NULL
+GROUP BY customer_name;
+
+UPDATE orders
+SET customer_id = (SELECT id FROM customers WHERE customers.name = orders.customer_name)
+WHERE customer_id IS NULL;
@@ -0,0 +1,19 @@
+-- Every order has at least one line
+CREATE TABLE order_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    line_number INTEGER NOT NULL,
+    product_name TEXT NOT NULL,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    unit_price REAL NOT NULL CHECK (unit_price > 0),
+    line_total REAL NOT NULL,
+
+    UNIQUE (order_id, line_number)
+);
+
+CREATE INDEX IX_order_items_product_name ON order_items(product_name);
+
+-- Orders written before line items existed become single-line orders
+INSERT INTO order_items (order_id, line_number, product_name, quantity, unit_price, line_total)
+SELECT id, 1, product_name, quantity, unit_price, total_amount
+FROM orders;
@@ -0,0 +1,18 @@
+-- Order lines take their price from the catalog
+CREATE TABLE products (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    sku TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    list_price REAL NOT NULL CHECK (list_price > 0),
+    active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IX_products_name ON products(name);
+
+-- Lines written before the catalog existed keep NULL here
+ALTER TABLE order_items ADD COLUMN product_id INTEGER REFERENCES products(id);
+ALTER TABLE order_items ADD COLUMN sku TEXT;
+
+CREATE INDEX IX_order_items_product_id ON order_items(product_id);
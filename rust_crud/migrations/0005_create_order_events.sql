@@ -0,0 +1,14 @@
+-- Order audit trail; rows are kept after the order is deleted
+CREATE TABLE order_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    order_id INTEGER NOT NULL,
+    event_type TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    old_values TEXT,
+    new_values TEXT,
+    created_at TEXT NOT NULL,
+
+    CHECK (event_type IN ('created', 'updated', 'status_changed', 'deleted'))
+);
+
+CREATE INDEX IX_order_events_order_id ON order_events(order_id);
@@ -0,0 +1,23 @@
+-- Stock levels, one row per product
+CREATE TABLE inventory (
+    product_id INTEGER PRIMARY KEY REFERENCES products(id) ON DELETE CASCADE,
+    on_hand INTEGER NOT NULL DEFAULT 0,
+    reserved INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL,
+
+    CHECK (on_hand >= 0),
+    CHECK (reserved >= 0)
+);
+
+INSERT INTO inventory (product_id, on_hand, reserved, updated_at)
+SELECT id, 0, 0, updated_at FROM products;
+
+-- Open orders placed before stock was tracked still hold their quantities
+UPDATE inventory
+SET reserved = (
+    SELECT COALESCE(SUM(oi.quantity), 0)
+    FROM order_items oi
+    JOIN orders o ON o.id = oi.order_id
+    WHERE oi.product_id = inventory.product_id
+      AND o.status IN ('Pending', 'Processing')
+);
@@ -0,0 +1,2 @@
+-- Optimistic concurrency: every write bumps the version
+ALTER TABLE orders ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
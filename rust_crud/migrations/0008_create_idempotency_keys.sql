@@ -0,0 +1,12 @@
+-- order_id is not a foreign key so a retry still replays after the order
+-- has been deleted
+CREATE TABLE idempotency_keys (
+    key TEXT PRIMARY KEY,
+    fingerprint TEXT NOT NULL,
+    order_id INTEGER NOT NULL,
+    response TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX IX_idempotency_keys_expires_at ON idempotency_keys(expires_at);
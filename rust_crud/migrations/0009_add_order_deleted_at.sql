@@ -0,0 +1,4 @@
+-- Soft delete: deleted orders keep their row until purged
+ALTER TABLE orders ADD COLUMN deleted_at TEXT;
+
+CREATE INDEX IX_orders_deleted_at ON orders(deleted_at);
@@ -0,0 +1,19 @@
+-- Status reports waiting to be delivered to STATUS_ENDPOINT. Rows are kept
+-- after delivery; reports that run out of attempts stay as 'dead'.
+CREATE TABLE status_outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation TEXT NOT NULL,
+    success INTEGER NOT NULL,
+    details TEXT,
+    order_id INTEGER,
+    reported_at TEXT NOT NULL,
+    state TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    last_error TEXT,
+    delivered_at TEXT,
+
+    CHECK (state IN ('pending', 'delivered', 'dead'))
+);
+
+CREATE INDEX IX_status_outbox_state_next_attempt_at ON status_outbox(state, next_attempt_at);
@@ -0,0 +1,35 @@
+-- Partner subscriptions to order events. event_types is a JSON array of
+-- order_events.event_type values.
+CREATE TABLE webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    event_types TEXT NOT NULL,
+    active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- One row per event per subscribed webhook, queued in the same transaction
+-- as the event. The payload is stored so retries sign the same bytes.
+CREATE TABLE webhook_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook_id INTEGER NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    event_id INTEGER NOT NULL,
+    event_type TEXT NOT NULL,
+    order_id INTEGER NOT NULL,
+    payload TEXT NOT NULL,
+    state TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    response_status INTEGER,
+    last_error TEXT,
+    redelivery_of INTEGER REFERENCES webhook_deliveries(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL,
+    delivered_at TEXT,
+
+    CHECK (state IN ('pending', 'delivered', 'failed'))
+);
+
+CREATE INDEX IX_webhook_deliveries_webhook_id ON webhook_deliveries(webhook_id);
+CREATE INDEX IX_webhook_deliveries_state_next_attempt_at ON webhook_deliveries(state, next_attempt_at);
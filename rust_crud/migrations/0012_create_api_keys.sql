@@ -0,0 +1,12 @@
+-- Client credentials. Only the SHA-256 of each key is stored; prefix is the
+-- start of the key, kept so admins can tell keys apart. scopes is a JSON
+-- array of scope names.
+CREATE TABLE api_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    revoked_at TEXT
+);
@@ -0,0 +1,19 @@
+-- Idempotency keys are chosen by clients, so they are scoped to the
+-- credential that sent them: two clients picking the same key must not
+-- replay each other's orders. SQLite cannot change a primary key in place;
+-- keys in flight cannot be attributed to a credential and are dropped.
+DROP TABLE idempotency_keys;
+
+CREATE TABLE idempotency_keys (
+    principal TEXT NOT NULL,
+    key TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    order_id INTEGER NOT NULL,
+    response TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+
+    PRIMARY KEY (principal, key)
+);
+
+CREATE INDEX IX_idempotency_keys_expires_at ON idempotency_keys(expires_at);
@@ -0,0 +1,3 @@
+-- The status dispatcher purges delivered reports once they are older than
+-- STATUS_RETENTION_DAYS
+CREATE INDEX IX_status_outbox_delivered_at ON status_outbox(delivered_at);
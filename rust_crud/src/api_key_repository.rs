@@ -0,0 +1,95 @@
+use chrono::Utc;
+use sqlx::types::Json;
+use crate::database::DatabasePool;
+use crate::models::{ApiKey, ApiScope};
+use crate::repository::RepositoryError;
+
+const API_KEY_COLUMNS: &str = "id, name, prefix, scopes, created_at, revoked_at";
+
+pub struct ApiKeyRepository {
+    pool: DatabasePool,
+}
+
+impl ApiKeyRepository {
+    pub fn new(pool: DatabasePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        name: &str,
+        prefix: &str,
+        key_hash: &str,
+        scopes: Vec<ApiScope>,
+    ) -> Result<ApiKey, RepositoryError> {
+        let now = Utc::now();
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO api_keys (name, prefix, key_hash, scopes, created_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(name)
+        .bind(prefix)
+        .bind(key_hash)
+        .bind(Json(&scopes))
+        .bind(now.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ApiKey {
+            id: result.last_insert_rowid(),
+            name: name.to_string(),
+            prefix: prefix.to_string(),
+            scopes: Json(scopes),
+            created_at: now,
+            revoked_at: None,
+        })
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<ApiKey>, RepositoryError> {
+        let keys = sqlx::query_as::<_, ApiKey>(
+            &format!("SELECT {} FROM api_keys ORDER BY id", API_KEY_COLUMNS)
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    pub async fn find_by_id(&self, id: i64) -> Result<Option<ApiKey>, RepositoryError> {
+        let key = sqlx::query_as::<_, ApiKey>(
+            &format!("SELECT {} FROM api_keys WHERE id = ?", API_KEY_COLUMNS)
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    /// The unrevoked key with this hash, if any.
+    pub async fn find_active_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, RepositoryError> {
+        let key = sqlx::query_as::<_, ApiKey>(
+            &format!("SELECT {} FROM api_keys WHERE key_hash = ? AND revoked_at IS NULL", API_KEY_COLUMNS)
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    /// Revokes the key. Revoking a key twice keeps the first revocation
+    /// time. Returns `None` when there is no such key.
+    pub async fn revoke(&self, id: i64) -> Result<Option<ApiKey>, RepositoryError> {
+        sqlx::query("UPDATE api_keys SET revoked_at = COALESCE(revoked_at, ?) WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        self.find_by_id(id).await
+    }
+}
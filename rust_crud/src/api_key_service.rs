@@ -0,0 +1,130 @@
+use std::sync::Arc;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use validator::Validate;
+use crate::api_key_repository::ApiKeyRepository;
+use crate::models::{ApiKey, CreateApiKeyRequest, CreatedApiKey};
+use crate::repository::RepositoryError;
+use crate::service::ServiceError;
+use crate::status_reporter::StatusReporter;
+
+/// Every key starts with this, so leaked keys are easy to search for.
+pub const KEY_PREFIX: &str = "ock_";
+/// Random bytes in a key, hex encoded after the prefix.
+const KEY_BYTES: usize = 32;
+/// Characters of the key kept in clear to tell keys apart.
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+pub struct ApiKeyService {
+    repository: Arc<ApiKeyRepository>,
+    status_reporter: Arc<dyn StatusReporter>,
+}
+
+impl ApiKeyService {
+    pub fn new(repository: Arc<ApiKeyRepository>, status_reporter: Arc<dyn StatusReporter>) -> Self {
+        Self {
+            repository,
+            status_reporter,
+        }
+    }
+
+    pub async fn create_api_key(&self, request: CreateApiKeyRequest) -> Result<CreatedApiKey, ServiceError> {
+        // Validate the request
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("create_api_key", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let key = generate_key();
+        let mut scopes = request.scopes;
+        scopes.sort();
+        scopes.dedup();
+
+        match self
+            .repository
+            .create(request.name.trim(), &key[..DISPLAY_PREFIX_LEN], &hash_key(&key), scopes)
+            .await
+        {
+            Ok(api_key) => {
+                self.status_reporter
+                    .report_success("create_api_key", None)
+                    .await;
+                Ok(CreatedApiKey { api_key, key })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to create API key: {}", e);
+                self.status_reporter
+                    .report_failure("create_api_key", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_api_keys(&self) -> Result<Vec<ApiKey>, ServiceError> {
+        match self.repository.find_all().await {
+            Ok(keys) => {
+                self.status_reporter
+                    .report_success("get_api_keys", None)
+                    .await;
+                Ok(keys)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get API keys: {}", e);
+                self.status_reporter
+                    .report_failure("get_api_keys", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn revoke_api_key(&self, id: i64) -> Result<ApiKey, ServiceError> {
+        match self.repository.revoke(id).await {
+            Ok(Some(api_key)) => {
+                self.status_reporter
+                    .report_success("revoke_api_key", None)
+                    .await;
+                Ok(api_key)
+            }
+            Ok(None) => {
+                let error_msg = format!("API key not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("revoke_api_key", &error_msg, None)
+                    .await;
+                Err(ServiceError::ApiKeyNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to revoke API key {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("revoke_api_key", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// Looks up the unrevoked key matching `key`. Runs on every request, so
+    /// unlike the operations above it is not reported.
+    pub async fn authenticate(&self, key: &str) -> Result<Option<ApiKey>, RepositoryError> {
+        if !key.starts_with(KEY_PREFIX) {
+            return Ok(None);
+        }
+        self.repository.find_active_by_hash(&hash_key(key)).await
+    }
+}
+
+fn generate_key() -> String {
+    let mut bytes = [0u8; KEY_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", KEY_PREFIX, hex::encode(bytes))
+}
+
+/// Keys are long and random, so a plain SHA-256 is enough to keep them out
+/// of the database without a slow password hash on every request.
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
@@ -0,0 +1,308 @@
+use std::sync::Arc;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use crate::api_key_service::{ApiKeyService, KEY_PREFIX};
+use crate::errors::ApiError;
+use crate::jwt::JwtVerifier;
+use crate::models::{ApiKey, ApiScope};
+
+/// What a caller may do to orders beyond their scopes. Later roles include
+/// everything the earlier ones may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Reads orders
+    Viewer,
+    /// Creates orders and moves them through fulfilment
+    Clerk,
+    /// Also cancels, deletes and restores orders
+    Manager,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Clerk => "clerk",
+            Role::Manager => "manager",
+        }
+    }
+
+    fn from_claim(value: &str) -> Option<Self> {
+        match value {
+            "viewer" => Some(Role::Viewer),
+            "clerk" => Some(Role::Clerk),
+            "manager" => Some(Role::Manager),
+            _ => None,
+        }
+    }
+
+    fn scopes(&self) -> Vec<ApiScope> {
+        match self {
+            Role::Viewer => vec![ApiScope::OrdersRead],
+            Role::Clerk | Role::Manager => vec![ApiScope::OrdersRead, ApiScope::OrdersWrite],
+        }
+    }
+}
+
+/// The authenticated caller, added to the request by `require_scope`.
+/// `subject` is recorded as the actor on the order audit trail.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// Unique per credential: `api-key:<id>` or `jwt:<sub>`. Key names are
+    /// not unique, so anything scoped to a caller uses this, not `subject`.
+    pub id: String,
+    pub subject: String,
+    pub role: Role,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl Principal {
+    /// API keys act as managers with the `admin` scope, clerks with
+    /// `orders:write` and viewers otherwise.
+    fn from_api_key(api_key: ApiKey) -> Self {
+        let scopes = api_key.scopes.0;
+        let role = if scopes.contains(&ApiScope::Admin) {
+            Role::Manager
+        } else if scopes.contains(&ApiScope::OrdersWrite) {
+            Role::Clerk
+        } else {
+            Role::Viewer
+        };
+
+        Self {
+            id: format!("api-key:{}", api_key.id),
+            subject: format!("api-key:{}", api_key.name),
+            role,
+            scopes,
+        }
+    }
+
+    /// Whether the caller may use routes needing `scope`. `admin` covers
+    /// every scope.
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.iter().any(|granted| *granted == scope || *granted == ApiScope::Admin)
+    }
+
+    /// Fails with 403 unless the caller has at least `role`.
+    pub fn require_role(&self, role: Role, action: &str) -> Result<(), ApiError> {
+        if self.role >= role {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(format!(
+            "Only the {} role may {}; {} is a {}",
+            role.as_str(),
+            action,
+            self.subject,
+            self.role.as_str()
+        )))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Principal {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .ok_or_else(|| ApiError::Unauthorized("Authentication required".to_string()))
+    }
+}
+
+/// Turns bearer credentials into a `Principal`. Credentials with the API key
+/// prefix are looked up as keys; anything else is checked as a JWT when a
+/// signing key is configured.
+pub struct Authenticator {
+    api_keys: Arc<ApiKeyService>,
+    jwt: Option<JwtVerifier>,
+}
+
+impl Authenticator {
+    pub fn new(api_keys: Arc<ApiKeyService>, jwt: Option<JwtVerifier>) -> Self {
+        Self { api_keys, jwt }
+    }
+
+    async fn authenticate(&self, credentials: &str) -> Result<Principal, ApiError> {
+        if credentials.starts_with(KEY_PREFIX) {
+            return match self.api_keys.authenticate(credentials).await {
+                Ok(Some(api_key)) => Ok(Principal::from_api_key(api_key)),
+                Ok(None) => Err(ApiError::Unauthorized("Invalid or revoked API key".to_string())),
+                Err(e) => {
+                    tracing::error!("Failed to look up API key: {}", e);
+                    Err(ApiError::Internal)
+                }
+            };
+        }
+
+        let Some(jwt) = &self.jwt else {
+            return Err(ApiError::Unauthorized("Invalid or revoked API key".to_string()));
+        };
+
+        let claims = jwt.verify(credentials).map_err(|e| {
+            tracing::debug!("Rejected bearer token: {}", e);
+            ApiError::Unauthorized("Invalid or expired token".to_string())
+        })?;
+
+        let role = claims
+            .roles
+            .iter()
+            .filter_map(|role| Role::from_claim(role))
+            .max()
+            .ok_or_else(|| {
+                ApiError::Forbidden(format!("{} has no viewer, clerk or manager role", claims.subject))
+            })?;
+
+        Ok(Principal {
+            id: format!("jwt:{}", claims.subject),
+            subject: claims.subject,
+            role,
+            scopes: role.scopes(),
+        })
+    }
+}
+
+/// State for `require_scope`: the scope a group of routes needs.
+#[derive(Clone)]
+pub struct RequireScope {
+    pub authenticator: Arc<Authenticator>,
+    pub scope: ApiScope,
+}
+
+/// Middleware for a route group. The request must carry an unrevoked API key
+/// or a valid token as `Authorization: Bearer <credentials>` (401 otherwise)
+/// that grants the group's scope (403 otherwise).
+pub async fn require_scope(
+    State(required): State<RequireScope>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let credentials = bearer_token(request.headers())
+        .ok_or_else(|| ApiError::Unauthorized("Missing bearer credentials".to_string()))?;
+
+    let principal = required.authenticator.authenticate(credentials).await?;
+    if !principal.allows(required.scope) {
+        return Err(ApiError::Forbidden(format!(
+            "{} does not have the {} scope",
+            principal.subject,
+            required.scope.as_str()
+        )));
+    }
+
+    tracing::debug!("Authenticated {} for {}", principal.subject, required.scope.as_str());
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    Some(token.trim()).filter(|token| !token.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+    use tower::ServiceExt;
+    use crate::api_key_repository::ApiKeyRepository;
+    use crate::database::test_pool;
+    use crate::memory_repository::InMemoryStatusReporter;
+    use crate::models::CreateApiKeyRequest;
+
+    async fn api_keys() -> Arc<ApiKeyService> {
+        Arc::new(ApiKeyService::new(
+            Arc::new(ApiKeyRepository::new(test_pool().await)),
+            Arc::new(InMemoryStatusReporter::new()),
+        ))
+    }
+
+    /// A route needing `scope` that answers with the caller's role.
+    fn app(authenticator: Arc<Authenticator>, scope: ApiScope) -> Router {
+        Router::new()
+            .route("/", get(|principal: Principal| async move { principal.role.as_str() }))
+            .route_layer(middleware::from_fn_with_state(RequireScope { authenticator, scope }, require_scope))
+    }
+
+    async fn send(app: &Router, credentials: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri("/");
+        if let Some(credentials) = credentials {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", credentials));
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+    }
+
+    async fn create_key(api_keys: &ApiKeyService, scopes: Vec<ApiScope>) -> (i64, String) {
+        let created = api_keys
+            .create_api_key(CreateApiKeyRequest { name: "ci".to_string(), scopes })
+            .await
+            .unwrap();
+        (created.api_key.id, created.key)
+    }
+
+    #[tokio::test]
+    async fn api_keys_grant_only_their_scopes() {
+        let api_keys = api_keys().await;
+        let authenticator = Arc::new(Authenticator::new(api_keys.clone(), None));
+        let reads = app(authenticator.clone(), ApiScope::OrdersRead);
+        let writes = app(authenticator.clone(), ApiScope::OrdersWrite);
+
+        let (_, reader) = create_key(&api_keys, vec![ApiScope::OrdersRead]).await;
+        let (_, admin) = create_key(&api_keys, vec![ApiScope::Admin]).await;
+
+        assert_eq!(send(&reads, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&reads, Some("ock_not-a-real-key")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&reads, Some(&reader)).await, StatusCode::OK);
+        assert_eq!(send(&writes, Some(&reader)).await, StatusCode::FORBIDDEN);
+        assert_eq!(send(&writes, Some(&admin)).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn revoked_api_keys_are_refused() {
+        let api_keys = api_keys().await;
+        let reads = app(Arc::new(Authenticator::new(api_keys.clone(), None)), ApiScope::OrdersRead);
+
+        let (id, key) = create_key(&api_keys, vec![ApiScope::OrdersRead]).await;
+        assert_eq!(send(&reads, Some(&key)).await, StatusCode::OK);
+
+        api_keys.revoke_api_key(id).await.unwrap();
+        assert_eq!(send(&reads, Some(&key)).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn api_key_scopes_decide_the_role() {
+        let principal = |scopes: Vec<ApiScope>| {
+            Principal::from_api_key(ApiKey {
+                id: 7,
+                name: "ci".to_string(),
+                prefix: "ock_1234".to_string(),
+                scopes: sqlx::types::Json(scopes),
+                created_at: chrono::Utc::now(),
+                revoked_at: None,
+            })
+        };
+
+        let viewer = principal(vec![ApiScope::OrdersRead]);
+        assert_eq!(viewer.id, "api-key:7");
+        assert_eq!(viewer.role, Role::Viewer);
+        assert!(viewer.require_role(Role::Clerk, "create orders").is_err());
+
+        let clerk = principal(vec![ApiScope::OrdersRead, ApiScope::OrdersWrite]);
+        assert_eq!(clerk.role, Role::Clerk);
+        assert!(clerk.require_role(Role::Manager, "cancel orders").is_err());
+
+        let manager = principal(vec![ApiScope::Admin]);
+        assert_eq!(manager.role, Role::Manager);
+        assert!(manager.allows(ApiScope::OrdersWrite));
+        assert!(manager.require_role(Role::Manager, "cancel orders").is_ok());
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+use crate::rate_limiter::{RateLimit, RateLimits};
 use std::time::Duration;
 
 #[derive(Debug, Clone)]
@@ -7,6 +9,28 @@ pub struct AppConfig {
     pub server_port: u16,
     pub connection_pool_size: u32,
     pub request_timeout: Duration,
+    pub allow_price_override: bool,
+    pub idempotency_window: Duration,
+    pub deleted_retention: Duration,
+    pub auto_migrate: bool,
+    pub jwt_hs256_secret: Option<String>,
+    pub jwt_rs256_public_key_file: Option<PathBuf>,
+    pub jwt_jwks_file: Option<PathBuf>,
+    pub jwt_issuer: Option<String>,
+    pub jwt_audience: Option<String>,
+    pub jwt_roles_claim: String,
+    pub rate_limits: RateLimits,
+    pub health_check_timeout: Duration,
+    pub shutdown_timeout: Duration,
+    pub status_max_attempts: u32,
+    pub status_retry_base: Duration,
+    pub status_retry_max: Duration,
+    pub status_poll_interval: Duration,
+    pub status_retention: Duration,
+    pub webhook_max_attempts: u32,
+    pub webhook_retry_base: Duration,
+    pub webhook_retry_max: Duration,
+    pub webhook_poll_interval: Duration,
 }
 
 impl AppConfig {
@@ -29,6 +53,101 @@ impl AppConfig {
                     .unwrap_or_else(|_| "30".to_string())
                     .parse()?
             ),
+            allow_price_override: std::env::var("ALLOW_PRICE_OVERRIDE")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            idempotency_window: Duration::from_secs(
+                std::env::var("IDEMPOTENCY_WINDOW_SECONDS")
+                    .unwrap_or_else(|_| "86400".to_string())
+                    .parse()?
+            ),
+            deleted_retention: Duration::from_secs(
+                std::env::var("DELETED_RETENTION_DAYS")
+                    .unwrap_or_else(|_| "30".to_string())
+                    .parse::<u64>()? * 24 * 60 * 60
+            ),
+            auto_migrate: std::env::var("AUTO_MIGRATE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            jwt_hs256_secret: optional_env("JWT_HS256_SECRET"),
+            jwt_rs256_public_key_file: optional_env("JWT_RS256_PUBLIC_KEY_FILE").map(PathBuf::from),
+            jwt_jwks_file: optional_env("JWT_JWKS_FILE").map(PathBuf::from),
+            jwt_issuer: optional_env("JWT_ISSUER"),
+            jwt_audience: optional_env("JWT_AUDIENCE"),
+            jwt_roles_claim: std::env::var("JWT_ROLES_CLAIM")
+                .unwrap_or_else(|_| "roles".to_string()),
+            rate_limits: RateLimits {
+                public: rate_limit_env("RATE_LIMIT_PUBLIC", "60/60")?,
+                reads: rate_limit_env("RATE_LIMIT_READS", "600/60")?,
+                writes: rate_limit_env("RATE_LIMIT_WRITES", "120/60")?,
+                admin: rate_limit_env("RATE_LIMIT_ADMIN", "60/60")?,
+                failed_auth: rate_limit_env("RATE_LIMIT_FAILED_AUTH", "20/60")?,
+            },
+            health_check_timeout: Duration::from_millis(
+                std::env::var("HEALTH_CHECK_TIMEOUT_MS")
+                    .unwrap_or_else(|_| "1000".to_string())
+                    .parse()?
+            ),
+            shutdown_timeout: Duration::from_secs(
+                std::env::var("SHUTDOWN_TIMEOUT_SECONDS")
+                    .unwrap_or_else(|_| "30".to_string())
+                    .parse()?
+            ),
+            status_max_attempts: std::env::var("STATUS_MAX_ATTEMPTS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()?,
+            status_retry_base: Duration::from_secs(
+                std::env::var("STATUS_RETRY_BASE_SECONDS")
+                    .unwrap_or_else(|_| "2".to_string())
+                    .parse()?
+            ),
+            status_retry_max: Duration::from_secs(
+                std::env::var("STATUS_RETRY_MAX_SECONDS")
+                    .unwrap_or_else(|_| "900".to_string())
+                    .parse()?
+            ),
+            status_poll_interval: Duration::from_secs(
+                std::env::var("STATUS_POLL_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "5".to_string())
+                    .parse()?
+            ),
+            status_retention: Duration::from_secs(
+                std::env::var("STATUS_RETENTION_DAYS")
+                    .unwrap_or_else(|_| "7".to_string())
+                    .parse::<u64>()? * 24 * 60 * 60
+            ),
+            webhook_max_attempts: std::env::var("WEBHOOK_MAX_ATTEMPTS")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()?,
+            webhook_retry_base: Duration::from_secs(
+                std::env::var("WEBHOOK_RETRY_BASE_SECONDS")
+                    .unwrap_or_else(|_| "10".to_string())
+                    .parse()?
+            ),
+            webhook_retry_max: Duration::from_secs(
+                std::env::var("WEBHOOK_RETRY_MAX_SECONDS")
+                    .unwrap_or_else(|_| "3600".to_string())
+                    .parse()?
+            ),
+            webhook_poll_interval: Duration::from_secs(
+                std::env::var("WEBHOOK_POLL_INTERVAL_SECONDS")
+                    .unwrap_or_else(|_| "2".to_string())
+                    .parse()?
+            ),
         })
     }
-}
\ No newline at end of file
+}
+
+/// An environment variable that is unset when missing or empty.
+fn optional_env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// A route group's rate limit; `off` disables it.
+fn rate_limit_env(name: &str, default: &str) -> anyhow::Result<Option<RateLimit>> {
+    let value = std::env::var(name).unwrap_or_else(|_| default.to_string());
+    if value.trim().eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    Ok(Some(value.parse()?))
+}
@@ -0,0 +1,241 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::json;
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection};
+use crate::database::DatabasePool;
+use crate::models::{Customer, CreateCustomerRequest, UpdateCustomerRequest, CustomerQuery, OrderEventType};
+use crate::repository::{record_event, RepositoryError};
+
+const CUSTOMER_COLUMNS: &str = "id, name, email, phone, created_at, updated_at";
+
+/// Customer storage used by `CustomerService` and `OrderService`.
+/// `SqliteCustomerRepository` is the production implementation.
+#[async_trait]
+pub trait CustomerRepository: Send + Sync {
+    async fn create(&self, request: CreateCustomerRequest) -> Result<Customer, RepositoryError>;
+
+    async fn find_all(&self, query: &CustomerQuery) -> Result<Vec<Customer>, RepositoryError>;
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<Customer>, RepositoryError>;
+
+    /// Names are unique regardless of case, so "ACME" finds "Acme".
+    async fn find_by_name(&self, name: &str) -> Result<Option<Customer>, RepositoryError>;
+
+    /// Also renames the customer on its orders, which keep a copy of the
+    /// name for filtering. Each renamed order gets a new version and an
+    /// `updated` event by `actor`.
+    async fn update(&self, id: i32, request: UpdateCustomerRequest, actor: &str) -> Result<Customer, RepositoryError>;
+
+    /// Fails with a foreign key violation while the customer has orders.
+    async fn delete(&self, id: i32) -> Result<bool, RepositoryError>;
+
+    async fn count_orders(&self, id: i32) -> Result<i64, RepositoryError>;
+}
+
+pub struct SqliteCustomerRepository {
+    pool: DatabasePool,
+}
+
+impl SqliteCustomerRepository {
+    pub fn new(pool: DatabasePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl CustomerRepository for SqliteCustomerRepository {
+    async fn create(&self, request: CreateCustomerRequest) -> Result<Customer, RepositoryError> {
+        let now = Utc::now();
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO customers (name, email, phone, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&request.name)
+        .bind(&request.email)
+        .bind(&request.phone)
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Customer {
+            id: result.last_insert_rowid() as i32,
+            name: request.name,
+            email: request.email,
+            phone: request.phone,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    async fn find_all(&self, query: &CustomerQuery) -> Result<Vec<Customer>, RepositoryError> {
+        let mut select = QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM customers", CUSTOMER_COLUMNS));
+        if let Some(name) = &query.name {
+            // Case-insensitive prefix match, e.g. "acme" finds "ACME Ltd"
+            select.push(" WHERE name LIKE ").push_bind(format!("{}%", escape_like(name)));
+            select.push(" ESCAPE '\\'");
+        }
+        select.push(" ORDER BY name, id");
+        select.push(" LIMIT ").push_bind(query.limit());
+        select.push(" OFFSET ").push_bind(query.offset());
+
+        let rows = select
+            .build_query_as::<Customer>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows)
+    }
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<Customer>, RepositoryError> {
+        let row = sqlx::query_as::<_, Customer>(
+            &format!("SELECT {} FROM customers WHERE id = ?", CUSTOMER_COLUMNS)
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn find_by_name(&self, name: &str) -> Result<Option<Customer>, RepositoryError> {
+        let row = sqlx::query_as::<_, Customer>(
+            &format!("SELECT {} FROM customers WHERE name = ?", CUSTOMER_COLUMNS)
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn update(&self, id: i32, request: UpdateCustomerRequest, actor: &str) -> Result<Customer, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = sqlx::query_as::<_, Customer>(
+            &format!("SELECT {} FROM customers WHERE id = ?", CUSTOMER_COLUMNS)
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+
+        let name = request.name.unwrap_or_else(|| current.name.clone());
+        let email = request.email.or(current.email);
+        let phone = request.phone.or(current.phone);
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE customers
+            SET name = ?, email = ?, phone = ?, updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(&name)
+        .bind(&email)
+        .bind(&phone)
+        .bind(now.to_rfc3339())
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        // Orders keep a copy of the name for filtering; keep it in step and
+        // audit the change on each of them
+        if name != current.name {
+            let renamed: Vec<i32> = sqlx::query_scalar(
+                r#"
+                UPDATE orders
+                SET customer_name = ?, version = version + 1, updated_at = ?
+                WHERE customer_id = ? AND customer_name <> ?
+                RETURNING id
+                "#
+            )
+            .bind(&name)
+            .bind(now.to_rfc3339())
+            .bind(id)
+            .bind(&name)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for order_id in renamed {
+                record_event(
+                    &mut tx,
+                    order_id,
+                    OrderEventType::Updated,
+                    actor,
+                    Some(json!({ "customer_name": current.name })),
+                    Some(json!({ "customer_name": name })),
+                )
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(Customer {
+            id,
+            name,
+            email,
+            phone,
+            created_at: current.created_at,
+            updated_at: now,
+        })
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, RepositoryError> {
+        let result = sqlx::query("DELETE FROM customers WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn count_orders(&self, id: i32) -> Result<i64, RepositoryError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM orders WHERE customer_id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+}
+
+/// Looks a customer up by name on `conn`, creating it on first use. Orders
+/// that name their customer call this inside their own transaction.
+pub(crate) async fn find_or_create_by_name(conn: &mut SqliteConnection, name: &str) -> Result<Customer, RepositoryError> {
+    let now = Utc::now();
+
+    sqlx::query(
+        r#"
+        INSERT INTO customers (name, created_at, updated_at)
+        VALUES (?, ?, ?)
+        ON CONFLICT (name) DO NOTHING
+        "#
+    )
+    .bind(name)
+    .bind(now.to_rfc3339())
+    .bind(now.to_rfc3339())
+    .execute(&mut *conn)
+    .await?;
+
+    let customer = sqlx::query_as::<_, Customer>(
+        &format!("SELECT {} FROM customers WHERE name = ?", CUSTOMER_COLUMNS)
+    )
+    .bind(name)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(customer)
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -0,0 +1,313 @@
+use std::sync::Arc;
+use validator::Validate;
+use crate::customer_repository::CustomerRepository;
+use crate::models::{Customer, CreateCustomerRequest, UpdateCustomerRequest, CustomerQuery};
+use crate::repository::RepositoryError;
+use crate::service::ServiceError;
+use crate::status_reporter::StatusReporter;
+
+pub struct CustomerService {
+    repository: Arc<dyn CustomerRepository>,
+    status_reporter: Arc<dyn StatusReporter>,
+}
+
+impl CustomerService {
+    pub fn new(repository: Arc<dyn CustomerRepository>, status_reporter: Arc<dyn StatusReporter>) -> Self {
+        Self {
+            repository,
+            status_reporter,
+        }
+    }
+
+    pub async fn create_customer(&self, request: CreateCustomerRequest) -> Result<Customer, ServiceError> {
+        // Validate the request
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("create_customer", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let name = request.name.clone();
+        match self.repository.create(request).await {
+            Ok(customer) => {
+                self.status_reporter
+                    .report_success("create_customer", None)
+                    .await;
+                Ok(customer)
+            }
+            Err(e) if e.is_unique_violation() => {
+                let error = ServiceError::DuplicateCustomer { name };
+                self.status_reporter
+                    .report_failure("create_customer", &error.to_string(), None)
+                    .await;
+                Err(error)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to create customer: {}", e);
+                self.status_reporter
+                    .report_failure("create_customer", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_customers(&self, query: &CustomerQuery) -> Result<Vec<Customer>, ServiceError> {
+        // Validate the query
+        if let Err(validation_errors) = query.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("get_customers", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        match self.repository.find_all(query).await {
+            Ok(customers) => {
+                self.status_reporter
+                    .report_success("get_customers", None)
+                    .await;
+                Ok(customers)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get customers: {}", e);
+                self.status_reporter
+                    .report_failure("get_customers", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_customer(&self, id: i32) -> Result<Customer, ServiceError> {
+        match self.repository.find_by_id(id).await {
+            Ok(Some(customer)) => {
+                self.status_reporter
+                    .report_success("get_customer", None)
+                    .await;
+                Ok(customer)
+            }
+            Ok(None) => {
+                let error_msg = format!("Customer not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("get_customer", &error_msg, None)
+                    .await;
+                Err(ServiceError::CustomerNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get customer {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("get_customer", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// A rename is recorded against `actor` on each of the customer's orders.
+    pub async fn update_customer(&self, id: i32, request: UpdateCustomerRequest, actor: &str) -> Result<Customer, ServiceError> {
+        // Validate the request
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("update_customer", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let name = request.name.clone();
+        match self.repository.update(id, request, actor).await {
+            Ok(customer) => {
+                self.status_reporter
+                    .report_success("update_customer", None)
+                    .await;
+                Ok(customer)
+            }
+            Err(RepositoryError::Database(sqlx::Error::RowNotFound)) => {
+                let error_msg = format!("Customer not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("update_customer", &error_msg, None)
+                    .await;
+                Err(ServiceError::CustomerNotFound { id })
+            }
+            Err(e) if e.is_unique_violation() => {
+                let error = ServiceError::DuplicateCustomer { name: name.unwrap_or_default() };
+                self.status_reporter
+                    .report_failure("update_customer", &error.to_string(), None)
+                    .await;
+                Err(error)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to update customer {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("update_customer", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// Customers that still have orders cannot be deleted.
+    pub async fn delete_customer(&self, id: i32) -> Result<(), ServiceError> {
+        let order_count = match self.repository.count_orders(id).await {
+            Ok(count) => count,
+            Err(e) => {
+                let error_msg = format!("Failed to delete customer {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("delete_customer", &error_msg, None)
+                    .await;
+                return Err(ServiceError::Repository(e));
+            }
+        };
+
+        if order_count > 0 {
+            let error = ServiceError::CustomerHasOrders { id };
+            self.status_reporter
+                .report_failure("delete_customer", &error.to_string(), None)
+                .await;
+            return Err(error);
+        }
+
+        match self.repository.delete(id).await {
+            Ok(true) => {
+                self.status_reporter
+                    .report_success("delete_customer", None)
+                    .await;
+                Ok(())
+            }
+            Ok(false) => {
+                let error_msg = format!("Customer not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("delete_customer", &error_msg, None)
+                    .await;
+                Err(ServiceError::CustomerNotFound { id })
+            }
+            // An order was placed between the count and the delete
+            Err(e) if e.is_foreign_key_violation() => {
+                let error = ServiceError::CustomerHasOrders { id };
+                self.status_reporter
+                    .report_failure("delete_customer", &error.to_string(), None)
+                    .await;
+                Err(error)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to delete customer {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("delete_customer", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use rust_decimal::Decimal;
+    use crate::customer_repository::SqliteCustomerRepository;
+    use crate::database::test_pool;
+    use crate::memory_repository::InMemoryStatusReporter;
+    use crate::models::{CreateOrderRequest, CreateProductRequest, OrderEventType};
+    use crate::product_repository::{ProductRepository, SqliteProductRepository};
+    use crate::repository::{OrderRepository, SqliteOrderRepository};
+    use crate::service::OrderService;
+
+    struct Fixture {
+        customers: CustomerService,
+        orders: OrderService,
+        order_repository: Arc<SqliteOrderRepository>,
+        customer_repository: Arc<SqliteCustomerRepository>,
+    }
+
+    /// Customer and order services over a migrated in-memory database with
+    /// `WIDGET` in stock.
+    async fn fixture() -> Fixture {
+        let pool = test_pool().await;
+        let reporter = Arc::new(InMemoryStatusReporter::new());
+        let customer_repository = Arc::new(SqliteCustomerRepository::new(pool.clone()));
+        let order_repository = Arc::new(SqliteOrderRepository::new(pool.clone()));
+        let products = Arc::new(SqliteProductRepository::new(pool.clone()));
+        products
+            .create(CreateProductRequest {
+                sku: "WIDGET".to_string(),
+                name: "Widget".to_string(),
+                list_price: Decimal::new(250, 2),
+                active: None,
+                stock_on_hand: Some(100),
+            })
+            .await
+            .unwrap();
+
+        Fixture {
+            customers: CustomerService::new(customer_repository.clone(), reporter.clone()),
+            orders: OrderService::new(
+                order_repository.clone(),
+                customer_repository.clone(),
+                products,
+                reporter,
+                false,
+                Duration::from_secs(3600),
+                Duration::from_secs(3600),
+            ),
+            order_repository,
+            customer_repository,
+        }
+    }
+
+    fn order_request(customer: &str) -> CreateOrderRequest {
+        CreateOrderRequest {
+            customer_id: None,
+            customer_name: Some(customer.to_string()),
+            sku: Some("WIDGET".to_string()),
+            product_name: None,
+            quantity: Some(1),
+            unit_price: None,
+            items: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn rename_is_audited_on_every_order() {
+        let fixture = fixture().await;
+        let (first, _) = fixture.orders.create_order(order_request("Acme"), None, "client", "test").await.unwrap();
+        let (second, _) = fixture.orders.create_order(order_request("Acme"), None, "client", "test").await.unwrap();
+
+        let request = UpdateCustomerRequest {
+            name: Some("Acme Ltd".to_string()),
+            email: None,
+            phone: None,
+        };
+        fixture.customers.update_customer(first.customer_id, request, "alice").await.unwrap();
+
+        for order in [first, second] {
+            let renamed = fixture.orders.get_order(order.id, false).await.unwrap();
+            assert_eq!(renamed.customer_name, "Acme Ltd");
+            assert_eq!(renamed.version, order.version + 1);
+
+            let events = fixture.order_repository.find_events(order.id).await.unwrap();
+            let event = events.last().unwrap();
+            assert_eq!(event.event_type, OrderEventType::Updated);
+            assert_eq!(event.actor, "alice");
+            assert_eq!(event.old_values.as_ref().unwrap().0["customer_name"], "Acme");
+            assert_eq!(event.new_values.as_ref().unwrap().0["customer_name"], "Acme Ltd");
+        }
+    }
+
+    #[tokio::test]
+    async fn customers_with_orders_are_not_deleted() {
+        let fixture = fixture().await;
+        let (order, _) = fixture.orders.create_order(order_request("Acme"), None, "client", "test").await.unwrap();
+
+        let error = fixture.customers.delete_customer(order.customer_id).await.unwrap_err();
+        assert!(matches!(error, ServiceError::CustomerHasOrders { .. }), "{error}");
+
+        // The database refuses too, for orders placed after the count
+        let error = fixture.customer_repository.delete(order.customer_id).await.unwrap_err();
+        assert!(error.is_foreign_key_violation(), "{error}");
+    }
+}
@@ -1,11 +1,21 @@
-use sqlx::{SqlitePool, Pool, Sqlite};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use sqlx::{Pool, Sqlite};
 use std::time::Duration;
 use crate::config::AppConfig;
 
 pub type DatabasePool = Pool<Sqlite>;
 
+/// Amounts are `REAL` columns and sqlx cannot bind `Decimal` to SQLite, so
+/// they are bound through this. Rows decode them with
+/// `#[sqlx(try_from = "f64")]`.
+pub fn amount(value: Decimal) -> f64 {
+    // Every `Decimal` is within `f64` range, so this only loses precision
+    value.to_f64().expect("a Decimal always converts to f64")
+}
+
 pub async fn create_pool(config: &AppConfig) -> anyhow::Result<DatabasePool> {
-    let pool = sqlx::SqlitePoolOptions::new()
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
         .max_connections(config.connection_pool_size)
         .acquire_timeout(config.request_timeout)
         .idle_timeout(Some(Duration::from_secs(300)))
@@ -22,44 +32,19 @@ pub async fn create_pool(config: &AppConfig) -> anyhow::Result<DatabasePool> {
     Ok(pool)
 }
 
-pub async fn run_migrations(pool: &DatabasePool) -> anyhow::Result<()> {
-    tracing::info!("Running database migrations");
-    
-    // Create orders table if it doesn't exist
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS orders (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            customer_name TEXT NOT NULL,
-            product_name TEXT NOT NULL,
-            quantity INTEGER NOT NULL CHECK (quantity > 0),
-            unit_price REAL NOT NULL CHECK (unit_price > 0),
-            total_amount REAL NOT NULL,
-            order_date TEXT NOT NULL,
-            status TEXT NOT NULL DEFAULT 'Pending',
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            
-            CHECK (status IN ('Pending', 'Processing', 'Shipped', 'Delivered', 'Cancelled'))
-        );
-        "#
-    )
-    .execute(pool)
-    .await?;
-
-    // Create indexes if they don't exist
-    sqlx::query("CREATE INDEX IF NOT EXISTS IX_orders_customer_name ON orders(customer_name);")
-        .execute(pool)
-        .await?;
-
-    sqlx::query("CREATE INDEX IF NOT EXISTS IX_orders_order_date ON orders(order_date);")
-        .execute(pool)
-        .await?;
+/// A private in-memory database with every migration applied. One
+/// connection only, since each connection to `:memory:` is its own database.
+#[cfg(test)]
+pub async fn test_pool() -> DatabasePool {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to open an in-memory database");
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS IX_orders_status ON orders(status);")
-        .execute(pool)
-        .await?;
+    crate::migrations::run_migrations(&pool)
+        .await
+        .expect("Failed to migrate the test database");
 
-    tracing::info!("Database migrations completed successfully");
-    Ok(())
-}
\ No newline at end of file
+    pool
+}
@@ -1,36 +1,109 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::repository::RepositoryError;
 use crate::service::ServiceError;
 
+/// Body of every error response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+    /// Same as the HTTP status code
+    pub status: u16,
+    pub timestamp: DateTime<Utc>,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ApiError {
     #[error("Service error: {0}")]
-    Service(#[from] ServiceError),
+    Service(ServiceError),
     #[error("Validation error: {0}")]
     Validation(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Too many requests; retry after {retry_after} seconds")]
+    TooManyRequests { retry_after: u64 },
     #[error("Database connection error")]
     DatabaseConnection,
     #[error("Internal server error")]
     Internal,
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+/// A pool that times out or has closed means the database is unavailable
+/// rather than that the request failed, so it becomes a 503.
+impl From<ServiceError> for ApiError {
+    fn from(error: ServiceError) -> Self {
+        match error {
+            ServiceError::Repository(RepositoryError::Database(
+                sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed,
+            )) => ApiError::DatabaseConnection,
+            error => ApiError::Service(error),
+        }
+    }
+}
+
+impl ApiError {
+    /// The HTTP status and client-facing message for this error. Also used
+    /// to report per-item failures in bulk responses.
+    pub fn status_and_message(&self) -> (StatusCode, String) {
+        match self {
             ApiError::Service(ServiceError::OrderNotFound { id }) => {
                 (StatusCode::NOT_FOUND, format!("Order with id {} not found", id))
             }
             ApiError::Service(ServiceError::Validation(msg)) => {
                 (StatusCode::BAD_REQUEST, format!("Validation error: {}", msg))
             }
+            ApiError::Service(ServiceError::CustomerNotFound { id }) => {
+                (StatusCode::NOT_FOUND, format!("Customer with id {} not found", id))
+            }
+            ApiError::Service(ServiceError::ProductNotFound { id }) => {
+                (StatusCode::NOT_FOUND, format!("Product with id {} not found", id))
+            }
+            ApiError::Service(ServiceError::WebhookNotFound { id }) => {
+                (StatusCode::NOT_FOUND, format!("Webhook with id {} not found", id))
+            }
+            ApiError::Service(error @ ServiceError::WebhookDeliveryNotFound { .. }) => {
+                (StatusCode::NOT_FOUND, error.to_string())
+            }
+            ApiError::Service(ServiceError::ApiKeyNotFound { id }) => {
+                (StatusCode::NOT_FOUND, format!("API key with id {} not found", id))
+            }
+            ApiError::Service(error @ ServiceError::InvalidStatusTransition { .. })
+            | ApiError::Service(error @ ServiceError::DuplicateCustomer { .. })
+            | ApiError::Service(error @ ServiceError::CustomerHasOrders { .. })
+            | ApiError::Service(error @ ServiceError::DuplicateProduct { .. })
+            | ApiError::Service(error @ ServiceError::ProductInUse { .. })
+            | ApiError::Service(error @ ServiceError::InsufficientStock { .. })
+            | ApiError::Service(error @ ServiceError::StockBelowReserved { .. })
+            | ApiError::Service(error @ ServiceError::OrderLocked { .. })
+            | ApiError::Service(error @ ServiceError::ConcurrentModification { .. })
+            | ApiError::Service(error @ ServiceError::OrderNotDeleted { .. }) => {
+                (StatusCode::CONFLICT, error.to_string())
+            }
+            ApiError::Service(error @ ServiceError::PreconditionFailed { .. }) => {
+                (StatusCode::PRECONDITION_FAILED, error.to_string())
+            }
+            ApiError::Service(error @ ServiceError::IdempotencyKeyReused { .. }) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, error.to_string())
+            }
+            ApiError::Service(error @ ServiceError::BulkRolledBack { .. }) => {
+                (StatusCode::FAILED_DEPENDENCY, error.to_string())
+            }
             ApiError::Service(ServiceError::Repository(_)) => {
                 tracing::error!("Repository error: {}", self);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
             }
+            ApiError::Service(ServiceError::Export(_)) => {
+                tracing::error!("Export error: {}", self);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
+            }
             ApiError::Service(ServiceError::StatusReporting(_)) => {
                 tracing::warn!("Status reporting error: {}", self);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
@@ -38,6 +111,15 @@ impl IntoResponse for ApiError {
             ApiError::Validation(msg) => {
                 (StatusCode::BAD_REQUEST, format!("Validation error: {}", msg))
             }
+            ApiError::Unauthorized(msg) => {
+                (StatusCode::UNAUTHORIZED, msg.clone())
+            }
+            ApiError::Forbidden(msg) => {
+                (StatusCode::FORBIDDEN, msg.clone())
+            }
+            ApiError::TooManyRequests { .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, self.to_string())
+            }
             ApiError::DatabaseConnection => {
                 tracing::error!("Database connection error");
                 (StatusCode::SERVICE_UNAVAILABLE, "Service temporarily unavailable".to_string())
@@ -46,14 +128,32 @@ impl IntoResponse for ApiError {
                 tracing::error!("Internal server error: {}", self);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
             }
-        };
+        }
+    }
+}
 
-        let body = Json(json!({
-            "error": error_message,
-            "status": status.as_u16(),
-            "timestamp": chrono::Utc::now()
-        }));
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error_message) = self.status_and_message();
 
-        (status, body).into_response()
+        let body = Json(ErrorBody {
+            error: error_message,
+            status: status.as_u16(),
+            timestamp: Utc::now(),
+        });
+
+        let mut response = (status, body).into_response();
+        match self {
+            ApiError::Unauthorized(_) => {
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            }
+            ApiError::TooManyRequests { retry_after } => {
+                response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            }
+            _ => {}
+        }
+        response
     }
 }
\ No newline at end of file
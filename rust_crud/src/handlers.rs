@@ -1,58 +1,1499 @@
+use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Duration;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    async_trait,
+    extract::{FromRef, FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Json,
+    },
 };
-use crate::models::{Order, CreateOrderRequest, UpdateOrderRequest};
-use crate::service::{OrderService, ServiceError};
-use crate::errors::ApiError;
+use futures::Stream;
+use tokio::sync::watch;
+use crate::customer_service::CustomerService;
+use crate::product_service::ProductService;
+use crate::report_service::ReportService;
+use crate::webhook_service::WebhookService;
+use crate::api_key_service::ApiKeyService;
+use crate::models::{
+    Order, CreateOrderRequest, UpdateOrderRequest, OrderQuery, OrderPage, OrderStatus, OrderEvent, Customer,
+    CreateCustomerRequest, UpdateCustomerRequest, CustomerQuery, Product, CreateProductRequest,
+    UpdateProductRequest, ProductQuery, StockLevel, UpdateStockRequest,
+    OrderLookup, PurgeQuery, PurgeResult, BulkCreateRequest, BulkUpdateRequest, BulkDeleteRequest,
+    BulkResponse, BulkItemResult, ImportReport, ImportRowResult, ReportQuery, RevenuePoint,
+    CustomerRevenue, ProductRevenue, StatusBreakdown, Webhook, CreateWebhookRequest, UpdateWebhookRequest,
+    WebhookDelivery, WebhookDeliveryQuery, OrderStreamQuery, OrderStreamEvent, ApiKey, CreateApiKeyRequest,
+    CreatedApiKey, VersionMatch, BulkMode,
+};
+use crate::service::{OrderService, BulkOutcome};
+use crate::errors::{ApiError, ErrorBody};
+use crate::auth::{Principal, Role};
+use crate::rate_limiter::{RateLimitCounter, RateLimiter};
+use crate::metrics::Metrics;
+use crate::health::{HealthChecker, HealthReport, HealthStatus};
+
+/// Shared router state. Handlers pull out the service they need with
+/// `State<Arc<OrderService>>`, `State<Arc<CustomerService>>` and so on.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub orders: Arc<OrderService>,
+    pub customers: Arc<CustomerService>,
+    pub products: Arc<ProductService>,
+    pub reports: Arc<ReportService>,
+    pub webhooks: Arc<WebhookService>,
+    pub api_keys: Arc<ApiKeyService>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<HealthChecker>,
+    pub shutdown: Shutdown,
+}
+
+/// Turns true when the server starts shutting down, so long-lived responses
+/// such as the order stream can end instead of holding up the drain.
+#[derive(Clone)]
+pub struct Shutdown(pub watch::Receiver<bool>);
+
+const ANONYMOUS_ACTOR: &str = "anonymous";
+
+/// Who is making a change, recorded in the order audit trail: the subject
+/// of the authenticated `Principal`.
+pub struct Actor(pub String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let actor = parts
+            .extensions
+            .get::<Principal>()
+            .map(|principal| principal.subject.as_str())
+            .unwrap_or(ANONYMOUS_ACTOR);
+
+        Ok(Actor(actor.to_string()))
+    }
+}
+
+/// The order versions a client expects, from an `If-Match` header listing
+/// entity tags as issued in `ETag` (e.g. `"3"` or `"3", "4"`). Absent or `*`
+/// means any version.
+pub struct IfMatch(pub VersionMatch);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(header::IF_MATCH) else {
+            return Ok(IfMatch(VersionMatch::Any));
+        };
+
+        let value = value
+            .to_str()
+            .map_err(|_| ApiError::Validation("If-Match header is not valid ASCII".to_string()))?;
+
+        parse_if_match(value)
+            .map(IfMatch)
+            .ok_or_else(|| ApiError::Validation(format!("If-Match must be * or a list of ETags, got {}", value)))
+    }
+}
+
+/// Parses an `If-Match` value as RFC 9110 defines it: `*`, or a comma-separated
+/// list of entity tags. `If-Match` compares strongly, so weak tags (`W/"3"`)
+/// never match and are dropped, as are tags that do not name a version.
+fn parse_if_match(value: &str) -> Option<VersionMatch> {
+    let value = value.trim();
+    if value == "*" {
+        return Some(VersionMatch::Any);
+    }
+
+    let mut versions = Vec::new();
+    let mut tags = 0;
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        if rest.is_empty() {
+            break;
+        }
+
+        let (weak, tag) = match rest.strip_prefix("W/") {
+            Some(tag) => (true, tag),
+            None => (false, rest),
+        };
+        let tag = tag.strip_prefix('"')?;
+        let end = tag.find('"')?;
+        if !weak {
+            if let Ok(version) = tag[..end].parse::<i64>() {
+                versions.push(version);
+            }
+        }
+        tags += 1;
+
+        rest = tag[end + 1..].trim_start_matches([' ', '\t']);
+        if !rest.is_empty() && !rest.starts_with(',') {
+            return None;
+        }
+    }
+
+    (tags > 0).then_some(VersionMatch::OneOf(versions))
+}
+
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
 
-pub type AppState = Arc<OrderService>;
+/// The id of the last order stream event a reconnecting client saw, sent by
+/// `EventSource` as `Last-Event-ID`.
+pub struct LastEventId(pub Option<i64>);
 
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for LastEventId {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(LAST_EVENT_ID_HEADER) else {
+            return Ok(LastEventId(None));
+        };
+
+        value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|id| *id >= 0)
+            .map(|id| LastEventId(Some(id)))
+            .ok_or_else(|| ApiError::Validation("Last-Event-ID must be an event id".to_string()))
+    }
+}
+
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// The client's `Idempotency-Key`, if it sent one. Retrying a create with the
+/// same key and body returns the original order instead of a duplicate.
+pub struct IdempotencyKeyHeader(pub Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IdempotencyKeyHeader {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(IDEMPOTENCY_KEY_HEADER) else {
+            return Ok(IdempotencyKeyHeader(None));
+        };
+
+        let key = value
+            .to_str()
+            .map_err(|_| ApiError::Validation("Idempotency-Key header is not valid ASCII".to_string()))?;
+
+        Ok(IdempotencyKeyHeader(Some(key.trim().to_string())))
+    }
+}
+
+/// Pairs an order with its `ETag` so clients can send it back in `If-Match`.
+fn with_etag(order: Order) -> ([(HeaderName, String); 1], Json<Order>) {
+    ([(header::ETAG, order.etag())], Json(order))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/orders",
+    tag = "orders",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key and body from the same credentials return the original order"),
+    ),
+    request_body = CreateOrderRequest,
+    responses(
+        (status = 201, description = "Order created; `Idempotent-Replayed: true` when this is a replay", body = Order, headers(("ETag" = String, description = "Current order version"))),
+        (status = 400, description = "Invalid request, or an unknown customer id, SKU or product", body = ErrorBody),
+        (status = 409, description = "Not enough stock", body = ErrorBody),
+        (status = 422, description = "Idempotency key reused with a different body", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
 pub async fn create_order(
-    State(service): State<AppState>,
+    State(service): State<Arc<OrderService>>,
+    principal: Principal,
+    IdempotencyKeyHeader(idempotency_key): IdempotencyKeyHeader,
     Json(request): Json<CreateOrderRequest>,
-) -> Result<(StatusCode, Json<Order>), ApiError> {
-    let order = service.create_order(request).await?;
-    Ok((StatusCode::CREATED, Json(order)))
+) -> Result<(StatusCode, HeaderMap, Json<Order>), ApiError> {
+    let (order, replayed) = service
+        .create_order(request, idempotency_key, &principal.id, &principal.subject)
+        .await?;
+
+    let mut headers = HeaderMap::new();
+    if replayed {
+        headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    }
+    if let Ok(etag) = HeaderValue::from_str(&order.etag()) {
+        headers.insert(header::ETAG, etag);
+    }
+    Ok((StatusCode::CREATED, headers, Json(order)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/orders/bulk",
+    tag = "orders",
+    request_body = BulkCreateRequest,
+    responses(
+        (status = 201, description = "Every order created", body = BulkResponse<Order>),
+        (status = 207, description = "Some or all orders failed in per_item mode", body = BulkResponse<Order>),
+        (status = 422, description = "Batch rolled back in all_or_nothing mode", body = BulkResponse<Order>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn bulk_create_orders(
+    State(service): State<Arc<OrderService>>,
+    Actor(actor): Actor,
+    Json(request): Json<BulkCreateRequest>,
+) -> Result<(StatusCode, Json<BulkResponse<Order>>), ApiError> {
+    let outcome = service.bulk_create_orders(request, &actor).await?;
+    Ok(bulk_response(outcome, StatusCode::CREATED))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/orders/bulk",
+    tag = "orders",
+    request_body = BulkUpdateRequest,
+    responses(
+        (status = 200, description = "Every order updated", body = BulkResponse<Order>),
+        (status = 207, description = "Some or all updates failed in per_item mode", body = BulkResponse<Order>),
+        (status = 422, description = "Batch rolled back in all_or_nothing mode", body = BulkResponse<Order>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn bulk_update_orders(
+    State(service): State<Arc<OrderService>>,
+    principal: Principal,
+    Json(request): Json<BulkUpdateRequest>,
+) -> Result<(StatusCode, Json<BulkResponse<Order>>), ApiError> {
+    if request.updates.iter().any(|update| update.changes.status == Some(OrderStatus::Cancelled)) {
+        principal.require_role(Role::Manager, "cancel orders")?;
+    }
+    let outcome = service.bulk_update_orders(request, &principal.subject).await?;
+    Ok(bulk_response(outcome, StatusCode::OK))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/orders/bulk",
+    tag = "orders",
+    request_body = BulkDeleteRequest,
+    responses(
+        (status = 200, description = "Every order deleted", body = BulkResponse<serde_json::Value>),
+        (status = 207, description = "Some or all deletes failed in per_item mode", body = BulkResponse<serde_json::Value>),
+        (status = 422, description = "Batch rolled back in all_or_nothing mode", body = BulkResponse<serde_json::Value>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn bulk_delete_orders(
+    State(service): State<Arc<OrderService>>,
+    principal: Principal,
+    Json(request): Json<BulkDeleteRequest>,
+) -> Result<(StatusCode, Json<BulkResponse<()>>), ApiError> {
+    principal.require_role(Role::Manager, "delete orders")?;
+    let outcome = service.bulk_delete_orders(request, &principal.subject).await?;
+    Ok(bulk_response(outcome, StatusCode::OK))
+}
+
+/// Builds the bulk response body. The overall status is `success` when every
+/// item succeeded, 207 Multi-Status when any item of a per-item batch failed
+/// (even if all of them did), and 422 when an all-or-nothing batch was
+/// rolled back.
+fn bulk_response<T>(outcome: BulkOutcome<T>, success: StatusCode) -> (StatusCode, Json<BulkResponse<T>>) {
+    let failed = outcome.failed();
+    let status = match (failed, outcome.mode) {
+        (0, _) => success,
+        (_, BulkMode::PerItem) => StatusCode::MULTI_STATUS,
+        (_, BulkMode::AllOrNothing) => StatusCode::UNPROCESSABLE_ENTITY,
+    };
+
+    let results = outcome
+        .items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| match item.result {
+            Ok(data) => BulkItemResult {
+                index,
+                status: success.as_u16(),
+                id: item.id,
+                data: Some(data),
+                error: None,
+            },
+            Err(error) => {
+                let (status, message) = ApiError::from(error).status_and_message();
+                BulkItemResult {
+                    index,
+                    status: status.as_u16(),
+                    id: item.id,
+                    data: None,
+                    error: Some(message),
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let body = BulkResponse {
+        mode: outcome.mode,
+        committed: outcome.committed,
+        succeeded: results.len() - failed,
+        failed,
+        results,
+    };
+    (status, Json(body))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/orders",
+    tag = "orders",
+    params(
+        OrderQuery,
+    ),
+    responses(
+        (status = 200, description = "A page of orders", body = OrderPage),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
 pub async fn get_orders(
-    State(service): State<AppState>,
-) -> Result<Json<Vec<Order>>, ApiError> {
-    let orders = service.get_orders().await?;
-    Ok(Json(orders))
+    State(service): State<Arc<OrderService>>,
+    Query(query): Query<OrderQuery>,
+) -> Result<Json<OrderPage>, ApiError> {
+    let (orders, total) = service.get_orders(&query).await?;
+    Ok(Json(OrderPage::new(orders, total, &query, "/api/orders")))
+}
+
+/// How often an idle order stream checks for events written outside this
+/// process, such as by another instance sharing the database.
+const STREAM_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Server-sent events for order changes. Each event's id is its audit event
+/// id, so a reconnecting client resumes from `Last-Event-ID` without gaps.
+#[utoipa::path(
+    get,
+    path = "/api/orders/stream",
+    tag = "orders",
+    params(
+        OrderStreamQuery,
+        ("Last-Event-ID" = Option<i64>, Header, description = "Resume after this event"),
+    ),
+    responses(
+        (status = 200, description = "Server-sent events, one per order change", body = OrderStreamEvent, content_type = "text/event-stream"),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn stream_orders(
+    State(service): State<Arc<OrderService>>,
+    State(Shutdown(mut shutdown)): State<Shutdown>,
+    Query(query): Query<OrderStreamQuery>,
+    LastEventId(last_event_id): LastEventId,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let (mut cursor, mut changes) = service.open_order_stream(&query, last_event_id).await?;
+
+    let stream = async_stream::stream! {
+        loop {
+            match service.next_stream_events(cursor, &query).await {
+                Ok(batch) => {
+                    cursor = batch.cursor;
+                    for event in &batch.events {
+                        if let Some(event) = sse_event(event) {
+                            yield Ok(event);
+                        }
+                    }
+                    if batch.more {
+                        continue;
+                    }
+                }
+                Err(e) => tracing::warn!("Order stream failed to read events: {}", e),
+            }
+
+            tokio::select! {
+                changed = changes.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(STREAM_POLL_INTERVAL) => {}
+                _ = shutdown.wait_for(|shutdown| *shutdown) => break,
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn sse_event(event: &OrderStreamEvent) -> Option<Event> {
+    match Event::default()
+        .id(event.event.id.to_string())
+        .event(event.event.event_type.as_str())
+        .json_data(event)
+    {
+        Ok(sse) => Some(sse),
+        Err(e) => {
+            tracing::warn!("Failed to encode order stream event {}: {}", event.event.id, e);
+            None
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/orders/export.csv",
+    tag = "orders",
+    params(
+        OrderQuery,
+    ),
+    responses(
+        (status = 200, description = "One row per order line", body = String, content_type = "text/csv"),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn export_orders_csv(
+    State(service): State<Arc<OrderService>>,
+    Query(query): Query<OrderQuery>,
+) -> Result<([(HeaderName, &'static str); 2], String), ApiError> {
+    let csv = service.export_orders(&query).await?;
+    let headers = [
+        (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+        (header::CONTENT_DISPOSITION, "attachment; filename=\"orders.csv\""),
+    ];
+    Ok((headers, csv))
 }
 
+/// Takes the CSV as the raw request body. Responds 200 when every row was
+/// imported, 207 Multi-Status when some rows failed and 422 when none could
+/// be imported.
+#[utoipa::path(
+    post,
+    path = "/api/orders/import",
+    tag = "orders",
+    request_body(content = String, content_type = "text/csv", description = "One single-item order per row"),
+    responses(
+        (status = 200, description = "Every row imported", body = ImportReport),
+        (status = 207, description = "Some rows failed", body = ImportReport),
+        (status = 422, description = "No row could be imported", body = ImportReport),
+        (status = 400, description = "Unreadable CSV", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn import_orders_csv(
+    State(service): State<Arc<OrderService>>,
+    Actor(actor): Actor,
+    body: String,
+) -> Result<(StatusCode, Json<ImportReport>), ApiError> {
+    let outcome = service.import_orders(&body, &actor).await?;
+    let failed = outcome.orders.failed();
+
+    let rows = outcome
+        .lines
+        .into_iter()
+        .zip(outcome.orders.items)
+        .map(|(line, item)| match item.result {
+            Ok(order) => ImportRowResult {
+                line,
+                status: StatusCode::CREATED.as_u16(),
+                order_id: Some(order.id),
+                error: None,
+            },
+            Err(error) => {
+                let (status, message) = ApiError::from(error).status_and_message();
+                ImportRowResult {
+                    line,
+                    status: status.as_u16(),
+                    order_id: None,
+                    error: Some(message),
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let status = match failed {
+        0 => StatusCode::OK,
+        failed if failed == rows.len() => StatusCode::UNPROCESSABLE_ENTITY,
+        _ => StatusCode::MULTI_STATUS,
+    };
+    let report = ImportReport {
+        imported: rows.len() - failed,
+        failed,
+        rows,
+    };
+    Ok((status, Json(report)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+        OrderLookup,
+    ),
+    responses(
+        (status = 200, description = "The order", body = Order, headers(("ETag" = String, description = "Current order version"))),
+        (status = 404, description = "Order not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
 pub async fn get_order(
-    State(service): State<AppState>,
+    State(service): State<Arc<OrderService>>,
     Path(id): Path<i32>,
-) -> Result<Json<Order>, ApiError> {
-    let order = service.get_order(id).await?;
-    Ok(Json(order))
+    Query(lookup): Query<OrderLookup>,
+) -> Result<([(HeaderName, String); 1], Json<Order>), ApiError> {
+    let order = service.get_order(id, lookup.include_deleted.unwrap_or(false)).await?;
+    Ok(with_etag(order))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/orders/{id}",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+        ("If-Match" = Option<String>, Header, description = "ETags of the versions the change may apply to, or `*`"),
+    ),
+    request_body = UpdateOrderRequest,
+    responses(
+        (status = 200, description = "Order updated", body = Order, headers(("ETag" = String, description = "Current order version"))),
+        (status = 400, description = "Invalid request, or an unknown customer id, SKU or product", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Invalid status change, locked order or not enough stock", body = ErrorBody),
+        (status = 412, description = "The order is not at a version listed in If-Match", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
 pub async fn update_order(
-    State(service): State<AppState>,
+    State(service): State<Arc<OrderService>>,
     Path(id): Path<i32>,
+    principal: Principal,
+    IfMatch(if_match): IfMatch,
     Json(request): Json<UpdateOrderRequest>,
-) -> Result<Json<Order>, ApiError> {
-    let order = service.update_order(id, request).await?;
-    Ok(Json(order))
+) -> Result<([(HeaderName, String); 1], Json<Order>), ApiError> {
+    if request.status == Some(OrderStatus::Cancelled) {
+        principal.require_role(Role::Manager, "cancel orders")?;
+    }
+    let order = service.update_order(id, request, &if_match, &principal.subject).await?;
+    Ok(with_etag(order))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/orders/{id}/process",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+    ),
+    responses(
+        (status = 200, description = "Order moved to Processing", body = Order, headers(("ETag" = String, description = "Current order version"))),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "The order cannot move to Processing from its current status", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn process_order(
+    State(service): State<Arc<OrderService>>,
+    Path(id): Path<i32>,
+    Actor(actor): Actor,
+) -> Result<([(HeaderName, String); 1], Json<Order>), ApiError> {
+    let order = service.change_status(id, OrderStatus::Processing, &actor).await?;
+    Ok(with_etag(order))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/orders/{id}/ship",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+    ),
+    responses(
+        (status = 200, description = "Order moved to Shipped", body = Order, headers(("ETag" = String, description = "Current order version"))),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "The order cannot move to Shipped from its current status", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn ship_order(
+    State(service): State<Arc<OrderService>>,
+    Path(id): Path<i32>,
+    Actor(actor): Actor,
+) -> Result<([(HeaderName, String); 1], Json<Order>), ApiError> {
+    let order = service.change_status(id, OrderStatus::Shipped, &actor).await?;
+    Ok(with_etag(order))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/orders/{id}/deliver",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+    ),
+    responses(
+        (status = 200, description = "Order moved to Delivered", body = Order, headers(("ETag" = String, description = "Current order version"))),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "The order cannot move to Delivered from its current status", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn deliver_order(
+    State(service): State<Arc<OrderService>>,
+    Path(id): Path<i32>,
+    Actor(actor): Actor,
+) -> Result<([(HeaderName, String); 1], Json<Order>), ApiError> {
+    let order = service.change_status(id, OrderStatus::Delivered, &actor).await?;
+    Ok(with_etag(order))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/orders/{id}/cancel",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+    ),
+    responses(
+        (status = 200, description = "Order moved to Cancelled", body = Order, headers(("ETag" = String, description = "Current order version"))),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "The order cannot move to Cancelled from its current status", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn cancel_order(
+    State(service): State<Arc<OrderService>>,
+    Path(id): Path<i32>,
+    principal: Principal,
+) -> Result<([(HeaderName, String); 1], Json<Order>), ApiError> {
+    principal.require_role(Role::Manager, "cancel orders")?;
+    let order = service.change_status(id, OrderStatus::Cancelled, &principal.subject).await?;
+    Ok(with_etag(order))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/orders/{id}",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+        ("If-Match" = Option<String>, Header, description = "ETags of the versions the change may apply to, or `*`"),
+    ),
+    responses(
+        (status = 204, description = "Order soft-deleted"),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "The order cannot be deleted", body = ErrorBody),
+        (status = 412, description = "The order is not at a version listed in If-Match", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
 pub async fn delete_order(
-    State(service): State<AppState>,
+    State(service): State<Arc<OrderService>>,
     Path(id): Path<i32>,
+    principal: Principal,
+    IfMatch(if_match): IfMatch,
 ) -> Result<StatusCode, ApiError> {
-    service.delete_order(id).await?;
+    principal.require_role(Role::Manager, "delete orders")?;
+    service.delete_order(id, &if_match, &principal.subject).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/orders/{id}/restore",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+    ),
+    responses(
+        (status = 200, description = "Order restored", body = Order, headers(("ETag" = String, description = "Current order version"))),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "The order is not deleted or stock has run out", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn restore_order(
+    State(service): State<Arc<OrderService>>,
+    Path(id): Path<i32>,
+    principal: Principal,
+) -> Result<([(HeaderName, String); 1], Json<Order>), ApiError> {
+    principal.require_role(Role::Manager, "restore orders")?;
+    let order = service.restore_order(id, &principal.subject).await?;
+    Ok(with_etag(order))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/orders/purge",
+    tag = "admin",
+    params(
+        PurgeQuery,
+    ),
+    responses(
+        (status = 200, description = "Soft-deleted orders removed", body = PurgeResult),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn purge_deleted_orders(
+    State(service): State<Arc<OrderService>>,
+    Query(query): Query<PurgeQuery>,
+) -> Result<Json<PurgeResult>, ApiError> {
+    let result = service.purge_deleted_orders(&query).await?;
+    Ok(Json(result))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/history",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+    ),
+    responses(
+        (status = 200, description = "Audit trail, oldest first", body = Vec<OrderEvent>),
+        (status = 404, description = "Order not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn get_order_history(
+    State(service): State<Arc<OrderService>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<OrderEvent>>, ApiError> {
+    let events = service.get_order_history(id).await?;
+    Ok(Json(events))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/customers",
+    tag = "customers",
+    request_body = CreateCustomerRequest,
+    responses(
+        (status = 201, description = "Customer created", body = Customer),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+        (status = 409, description = "A customer with that name exists", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn create_customer(
+    State(service): State<Arc<CustomerService>>,
+    Json(request): Json<CreateCustomerRequest>,
+) -> Result<(StatusCode, Json<Customer>), ApiError> {
+    let customer = service.create_customer(request).await?;
+    Ok((StatusCode::CREATED, Json(customer)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/customers",
+    tag = "customers",
+    params(
+        CustomerQuery,
+    ),
+    responses(
+        (status = 200, description = "Customers", body = Vec<Customer>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn get_customers(
+    State(service): State<Arc<CustomerService>>,
+    Query(query): Query<CustomerQuery>,
+) -> Result<Json<Vec<Customer>>, ApiError> {
+    let customers = service.get_customers(&query).await?;
+    Ok(Json(customers))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/customers/{id}",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+    ),
+    responses(
+        (status = 200, description = "The customer", body = Customer),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn get_customer(
+    State(service): State<Arc<CustomerService>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Customer>, ApiError> {
+    let customer = service.get_customer(id).await?;
+    Ok(Json(customer))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/customers/{id}",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+    ),
+    request_body = UpdateCustomerRequest,
+    responses(
+        (status = 200, description = "Customer updated", body = Customer),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 409, description = "A customer with that name exists", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn update_customer(
+    State(service): State<Arc<CustomerService>>,
+    Path(id): Path<i32>,
+    Actor(actor): Actor,
+    Json(request): Json<UpdateCustomerRequest>,
+) -> Result<Json<Customer>, ApiError> {
+    let customer = service.update_customer(id, request, &actor).await?;
+    Ok(Json(customer))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/customers/{id}",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+    ),
+    responses(
+        (status = 204, description = "Customer deleted"),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 409, description = "The customer has orders", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn delete_customer(
+    State(service): State<Arc<CustomerService>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    service.delete_customer(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/customers/{id}/orders",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+        OrderQuery,
+    ),
+    responses(
+        (status = 200, description = "A page of the customer's orders", body = OrderPage),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn get_customer_orders(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Query(mut query): Query<OrderQuery>,
+) -> Result<Json<OrderPage>, ApiError> {
+    // 404 for unknown customers rather than an empty page
+    state.customers.get_customer(id).await?;
+
+    query.customer_id = Some(id);
+    let (orders, total) = state.orders.get_orders(&query).await?;
+    let path = format!("/api/customers/{}/orders", id);
+    Ok(Json(OrderPage::new(orders, total, &query, &path)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/products",
+    tag = "products",
+    request_body = CreateProductRequest,
+    responses(
+        (status = 201, description = "Product created", body = Product),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+        (status = 409, description = "A product with that SKU exists", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn create_product(
+    State(service): State<Arc<ProductService>>,
+    Json(request): Json<CreateProductRequest>,
+) -> Result<(StatusCode, Json<Product>), ApiError> {
+    let product = service.create_product(request).await?;
+    Ok((StatusCode::CREATED, Json(product)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/products",
+    tag = "products",
+    params(
+        ProductQuery,
+    ),
+    responses(
+        (status = 200, description = "Products", body = Vec<Product>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn get_products(
+    State(service): State<Arc<ProductService>>,
+    Query(query): Query<ProductQuery>,
+) -> Result<Json<Vec<Product>>, ApiError> {
+    let products = service.get_products(&query).await?;
+    Ok(Json(products))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/products/{id}",
+    tag = "products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+    ),
+    responses(
+        (status = 200, description = "The product", body = Product),
+        (status = 404, description = "Product not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn get_product(
+    State(service): State<Arc<ProductService>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Product>, ApiError> {
+    let product = service.get_product(id).await?;
+    Ok(Json(product))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/products/{id}",
+    tag = "products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+    ),
+    request_body = UpdateProductRequest,
+    responses(
+        (status = 200, description = "Product updated", body = Product),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+        (status = 404, description = "Product not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn update_product(
+    State(service): State<Arc<ProductService>>,
+    Path(id): Path<i32>,
+    Json(request): Json<UpdateProductRequest>,
+) -> Result<Json<Product>, ApiError> {
+    let product = service.update_product(id, request).await?;
+    Ok(Json(product))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/products/{id}",
+    tag = "products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+    ),
+    responses(
+        (status = 204, description = "Product deleted"),
+        (status = 404, description = "Product not found", body = ErrorBody),
+        (status = 409, description = "The product is on orders", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn delete_product(
+    State(service): State<Arc<ProductService>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    service.delete_product(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/products/{id}/stock",
+    tag = "products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+    ),
+    responses(
+        (status = 200, description = "Stock level", body = StockLevel),
+        (status = 404, description = "Product not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn get_product_stock(
+    State(service): State<Arc<ProductService>>,
+    Path(id): Path<i32>,
+) -> Result<Json<StockLevel>, ApiError> {
+    let stock = service.get_stock(id).await?;
+    Ok(Json(stock))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/products/{id}/stock",
+    tag = "products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+    ),
+    request_body = UpdateStockRequest,
+    responses(
+        (status = 200, description = "Stock level updated", body = StockLevel),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+        (status = 404, description = "Product not found", body = ErrorBody),
+        (status = 409, description = "Open orders reserve more than the new level", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:write"])),
+)]
+pub async fn set_product_stock(
+    State(service): State<Arc<ProductService>>,
+    Path(id): Path<i32>,
+    Json(request): Json<UpdateStockRequest>,
+) -> Result<Json<StockLevel>, ApiError> {
+    let stock = service.set_stock(id, request).await?;
+    Ok(Json(stock))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reports/revenue",
+    tag = "reports",
+    params(
+        ReportQuery,
+    ),
+    responses(
+        (status = 200, description = "Revenue per period", body = Vec<RevenuePoint>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn revenue_report(
+    State(service): State<Arc<ReportService>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<Vec<RevenuePoint>>, ApiError> {
+    let points = service.revenue(&query).await?;
+    Ok(Json(points))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reports/top-customers",
+    tag = "reports",
+    params(
+        ReportQuery,
+    ),
+    responses(
+        (status = 200, description = "Customers by revenue", body = Vec<CustomerRevenue>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn top_customers_report(
+    State(service): State<Arc<ReportService>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<Vec<CustomerRevenue>>, ApiError> {
+    let customers = service.top_customers(&query).await?;
+    Ok(Json(customers))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reports/top-products",
+    tag = "reports",
+    params(
+        ReportQuery,
+    ),
+    responses(
+        (status = 200, description = "Products by revenue", body = Vec<ProductRevenue>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn top_products_report(
+    State(service): State<Arc<ReportService>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<Vec<ProductRevenue>>, ApiError> {
+    let products = service.top_products(&query).await?;
+    Ok(Json(products))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reports/status-breakdown",
+    tag = "reports",
+    params(
+        ReportQuery,
+    ),
+    responses(
+        (status = 200, description = "Orders and revenue per status", body = Vec<StatusBreakdown>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["orders:read"])),
+)]
+pub async fn status_breakdown_report(
+    State(service): State<Arc<ReportService>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<Vec<StatusBreakdown>>, ApiError> {
+    let breakdown = service.status_breakdown(&query).await?;
+    Ok(Json(breakdown))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks",
+    tag = "webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "Webhook created", body = Webhook),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn create_webhook(
+    State(service): State<Arc<WebhookService>>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<Webhook>), ApiError> {
+    let webhook = service.create_webhook(request).await?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Webhooks", body = Vec<Webhook>),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn get_webhooks(
+    State(service): State<Arc<WebhookService>>,
+) -> Result<Json<Vec<Webhook>>, ApiError> {
+    let webhooks = service.get_webhooks().await?;
+    Ok(Json(webhooks))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+    ),
+    responses(
+        (status = 200, description = "The webhook", body = Webhook),
+        (status = 404, description = "Webhook not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn get_webhook(
+    State(service): State<Arc<WebhookService>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Webhook>, ApiError> {
+    let webhook = service.get_webhook(id).await?;
+    Ok(Json(webhook))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+    ),
+    request_body = UpdateWebhookRequest,
+    responses(
+        (status = 200, description = "Webhook updated", body = Webhook),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+        (status = 404, description = "Webhook not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn update_webhook(
+    State(service): State<Arc<WebhookService>>,
+    Path(id): Path<i32>,
+    Json(request): Json<UpdateWebhookRequest>,
+) -> Result<Json<Webhook>, ApiError> {
+    let webhook = service.update_webhook(id, request).await?;
+    Ok(Json(webhook))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+    ),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 404, description = "Webhook not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn delete_webhook(
+    State(service): State<Arc<WebhookService>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    service.delete_webhook(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+        WebhookDeliveryQuery,
+    ),
+    responses(
+        (status = 200, description = "Delivery log, newest first", body = Vec<WebhookDelivery>),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+        (status = 404, description = "Webhook not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn get_webhook_deliveries(
+    State(service): State<Arc<WebhookService>>,
+    Path(id): Path<i32>,
+    Query(query): Query<WebhookDeliveryQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, ApiError> {
+    let deliveries = service.get_webhook_deliveries(id, &query).await?;
+    Ok(Json(deliveries))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/{id}/deliveries/{delivery_id}/redeliver",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+        ("delivery_id" = i64, Path, description = "Delivery to send again"),
+    ),
+    responses(
+        (status = 202, description = "Redelivery queued", body = WebhookDelivery),
+        (status = 404, description = "Webhook or delivery not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn redeliver_webhook(
+    State(service): State<Arc<WebhookService>>,
+    Path((id, delivery_id)): Path<(i32, i64)>,
+) -> Result<(StatusCode, Json<WebhookDelivery>), ApiError> {
+    let delivery = service.redeliver(id, delivery_id).await?;
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
+
+/// The original probe, kept with its `healthy` body for existing monitors.
+/// It answers whenever `health_live` does.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses(
+        (status = 200, description = "The process is up", body = Object),
+    ),
+    security(()),
+)]
 pub async fn health_check() -> Result<Json<serde_json::Value>, ApiError> {
     Ok(Json(serde_json::json!({
         "status": "healthy",
         "timestamp": chrono::Utc::now()
     })))
-}
\ No newline at end of file
+}
+
+/// Liveness: the process is serving requests. Dependencies are checked by
+/// `health_ready`.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses(
+        (status = 200, description = "The process is up", body = Object),
+    ),
+    security(()),
+)]
+pub async fn health_live() -> Result<Json<serde_json::Value>, ApiError> {
+    Ok(Json(serde_json::json!({
+        "status": "up",
+        "timestamp": chrono::Utc::now()
+    })))
+}
+
+/// Readiness: 200 when the database is reachable and migrated, otherwise 503
+/// with the same body so callers can see which component failed. The status
+/// reporter is reported but never causes a 503.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "The database is up and migrated", body = HealthReport),
+        (status = 503, description = "The database is down or has pending migrations", body = HealthReport),
+    ),
+    security(()),
+)]
+pub async fn health_ready(
+    State(health): State<Arc<HealthChecker>>,
+) -> (StatusCode, Json<HealthReport>) {
+    let report = health.readiness().await;
+    let status = match report.status {
+        HealthStatus::Up => StatusCode::OK,
+        HealthStatus::Degraded | HealthStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(report))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/api-keys",
+    tag = "api-keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "Key created; `key` is only shown now", body = CreatedApiKey),
+        (status = 400, description = "Invalid request", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn create_api_key(
+    State(service): State<Arc<ApiKeyService>>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), ApiError> {
+    let api_key = service.create_api_key(request).await?;
+    Ok((StatusCode::CREATED, Json(api_key)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/api-keys",
+    tag = "api-keys",
+    responses(
+        (status = 200, description = "Every key, including revoked ones", body = Vec<ApiKey>),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn get_api_keys(
+    State(service): State<Arc<ApiKeyService>>,
+) -> Result<Json<Vec<ApiKey>>, ApiError> {
+    let api_keys = service.get_api_keys().await?;
+    Ok(Json(api_keys))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/api-keys/{id}/revoke",
+    tag = "api-keys",
+    params(
+        ("id" = i64, Path, description = "API key id"),
+    ),
+    responses(
+        (status = 200, description = "Key revoked", body = ApiKey),
+        (status = 404, description = "API key not found", body = ErrorBody),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn revoke_api_key(
+    State(service): State<Arc<ApiKeyService>>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiKey>, ApiError> {
+    let api_key = service.revoke_api_key(id).await?;
+    Ok(Json(api_key))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/rate-limits",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Every rate limit bucket", body = Vec<RateLimitCounter>),
+    ),
+    security(("bearer" = ["admin"])),
+)]
+pub async fn get_rate_limits(
+    State(limiter): State<Arc<RateLimiter>>,
+) -> Json<Vec<RateLimitCounter>> {
+    Json(limiter.counters())
+}
+
+/// Prometheus text exposition.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Prometheus text exposition", body = String, content_type = "text/plain"),
+    ),
+    security(()),
+)]
+pub async fn get_metrics(
+    State(metrics): State<Arc<Metrics>>,
+) -> ([(HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::{BulkItem, ServiceError};
+
+    #[test]
+    fn if_match_follows_rfc_9110() {
+        assert_eq!(parse_if_match("*"), Some(VersionMatch::Any));
+        assert_eq!(parse_if_match("\"3\""), Some(VersionMatch::OneOf(vec![3])));
+        assert_eq!(parse_if_match(" \"3\" , \"5\""), Some(VersionMatch::OneOf(vec![3, 5])));
+        assert_eq!(parse_if_match("W/\"3\""), Some(VersionMatch::OneOf(Vec::new())));
+        assert_eq!(parse_if_match("W/\"3\", \"4\""), Some(VersionMatch::OneOf(vec![4])));
+        assert_eq!(parse_if_match("\"abc\""), Some(VersionMatch::OneOf(Vec::new())));
+
+        assert_eq!(parse_if_match(""), None);
+        assert_eq!(parse_if_match("3"), None);
+        assert_eq!(parse_if_match("\"3"), None);
+        assert_eq!(parse_if_match("\"3\" \"4\""), None);
+    }
+
+    #[tokio::test]
+    async fn health_keeps_its_original_body() {
+        let Json(body) = health_check().await.unwrap();
+        assert_eq!(body["status"], "healthy");
+
+        let Json(body) = health_live().await.unwrap();
+        assert_eq!(body["status"], "up");
+    }
+
+    fn outcome(mode: BulkMode, results: Vec<Result<(), ServiceError>>) -> BulkOutcome<()> {
+        BulkOutcome {
+            mode,
+            committed: results.iter().any(Result::is_ok),
+            items: results.into_iter().map(|result| BulkItem { id: None, result }).collect(),
+        }
+    }
+
+    #[test]
+    fn bulk_status_follows_the_mode() {
+        let failure = || Err(ServiceError::OrderNotFound { id: 1 });
+
+        let (status, _) = bulk_response(outcome(BulkMode::PerItem, vec![Ok(()), Ok(())]), StatusCode::CREATED);
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, _) = bulk_response(outcome(BulkMode::PerItem, vec![Ok(()), failure()]), StatusCode::CREATED);
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+
+        let (status, Json(body)) = bulk_response(outcome(BulkMode::PerItem, vec![failure(), failure()]), StatusCode::CREATED);
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(body.results.iter().all(|item| item.status == 404));
+
+        let (status, _) = bulk_response(outcome(BulkMode::AllOrNothing, vec![failure()]), StatusCode::CREATED);
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::database::DatabasePool;
+use crate::migrations::migration_report;
+use crate::status_reporter::OutboxStatusReporter;
+
+/// Failed deliveries in a row after which the status reporter counts as
+/// degraded. Reports stay queued, so a short outage is not worth flagging.
+const STATUS_FAILURES_DEGRADED: u32 = 5;
+
+/// Components that requests cannot do without. The status reporter queues
+/// reports while its endpoint is away, so it is shown but never makes the
+/// instance unready.
+const REQUIRED_COMPONENTS: [&str; 2] = ["database", "migrations"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    Degraded,
+    Down,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+/// Body of `/health/ready`. `status` is the worst of the required
+/// components, the database and its migrations.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub timestamp: DateTime<Utc>,
+    #[schema(value_type = BTreeMap<String, ComponentHealth>)]
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+/// Checks the dependencies a request needs: the database, its schema, and
+/// delivery of status reports.
+pub struct HealthChecker {
+    pool: DatabasePool,
+    status_reporter: Arc<OutboxStatusReporter>,
+    timeout: Duration,
+}
+
+impl HealthChecker {
+    pub fn new(pool: DatabasePool, status_reporter: Arc<OutboxStatusReporter>, timeout: Duration) -> Self {
+        Self {
+            pool,
+            status_reporter,
+            timeout,
+        }
+    }
+
+    pub async fn readiness(&self) -> HealthReport {
+        let (database, migrations) = tokio::join!(self.check_database(), self.check_migrations());
+
+        let mut components = BTreeMap::new();
+        components.insert("database", database);
+        components.insert("migrations", migrations);
+        components.insert("status_reporter", self.check_status_reporter());
+
+        HealthReport {
+            status: overall_status(&components),
+            timestamp: Utc::now(),
+            components,
+        }
+    }
+
+    async fn check_database(&self) -> ComponentHealth {
+        self.timed(async {
+            sqlx::query("SELECT 1").execute(&self.pool).await?;
+            Ok((HealthStatus::Up, None))
+        })
+        .await
+    }
+
+    async fn check_migrations(&self) -> ComponentHealth {
+        self.timed(async {
+            let report = migration_report(&self.pool).await?;
+            if report.is_current() {
+                return Ok((HealthStatus::Up, None));
+            }
+            Ok((
+                HealthStatus::Down,
+                Some(format!(
+                    "{} pending and {} modified migrations",
+                    report.pending().count(),
+                    report.modified().count()
+                )),
+            ))
+        })
+        .await
+    }
+
+    fn check_status_reporter(&self) -> ComponentHealth {
+        let stats = self.status_reporter.delivery_stats();
+        let status = if stats.consecutive_failures >= STATUS_FAILURES_DEGRADED {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Up
+        };
+
+        let last_success = stats
+            .last_success_at
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_else(|| "never".to_string());
+
+        ComponentHealth {
+            status,
+            latency_ms: 0.0,
+            details: Some(format!(
+                "last delivery {}; {} failed attempts since",
+                last_success, stats.consecutive_failures
+            )),
+        }
+    }
+
+    /// Runs a check within the configured timeout; errors and timeouts mark
+    /// the component down.
+    async fn timed<F>(&self, check: F) -> ComponentHealth
+    where
+        F: Future<Output = anyhow::Result<(HealthStatus, Option<String>)>>,
+    {
+        let started = Instant::now();
+        let (status, details) = match tokio::time::timeout(self.timeout, check).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => (HealthStatus::Down, Some(e.to_string())),
+            Err(_) => (
+                HealthStatus::Down,
+                Some(format!("No response within {} ms", self.timeout.as_millis())),
+            ),
+        };
+
+        ComponentHealth {
+            status,
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+            details,
+        }
+    }
+}
+
+fn overall_status(components: &BTreeMap<&'static str, ComponentHealth>) -> HealthStatus {
+    components
+        .iter()
+        .filter(|(name, _)| REQUIRED_COMPONENTS.contains(name))
+        .map(|(_, component)| component.status)
+        .max()
+        .unwrap_or(HealthStatus::Up)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_pool;
+    use crate::metrics::Metrics;
+    use crate::outbox_repository::OutboxRepository;
+
+    fn component(status: HealthStatus) -> ComponentHealth {
+        ComponentHealth {
+            status,
+            latency_ms: 0.0,
+            details: None,
+        }
+    }
+
+    #[test]
+    fn only_required_components_decide_the_status() {
+        let mut components = BTreeMap::new();
+        components.insert("database", component(HealthStatus::Up));
+        components.insert("migrations", component(HealthStatus::Up));
+        components.insert("status_reporter", component(HealthStatus::Degraded));
+        assert_eq!(overall_status(&components), HealthStatus::Up);
+
+        components.insert("migrations", component(HealthStatus::Down));
+        assert_eq!(overall_status(&components), HealthStatus::Down);
+    }
+
+    fn checker(pool: DatabasePool) -> HealthChecker {
+        let metrics = Arc::new(Metrics::new(pool.clone()).unwrap());
+        let outbox = Arc::new(OutboxRepository::new(pool.clone()));
+        let reporter = Arc::new(OutboxStatusReporter::new(outbox, metrics));
+        HealthChecker::new(pool, reporter, Duration::from_secs(1))
+    }
+
+    #[tokio::test]
+    async fn migrated_database_is_ready() {
+        let report = checker(test_pool().await).readiness().await;
+
+        assert_eq!(report.status, HealthStatus::Up);
+        assert!(report.components.contains_key("status_reporter"));
+    }
+
+    #[tokio::test]
+    async fn pending_migrations_make_the_instance_unready() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        let report = checker(pool).readiness().await;
+
+        assert_eq!(report.status, HealthStatus::Down);
+        assert_eq!(report.components["migrations"].status, HealthStatus::Down);
+    }
+}
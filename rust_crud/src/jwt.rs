@@ -0,0 +1,234 @@
+use anyhow::Context;
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+use crate::config::AppConfig;
+
+/// A key tokens may be signed with.
+struct VerificationKey {
+    /// The JWKS `kid`; keys from the environment have none and are tried
+    /// for any token with their algorithm
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// What a valid token says about its bearer.
+#[derive(Debug, Clone)]
+pub struct TokenClaims {
+    pub subject: String,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwtError {
+    #[error("Token uses unsupported algorithm {0:?}")]
+    UnsupportedAlgorithm(Algorithm),
+    #[error("No configured key matches the token")]
+    UnknownKey,
+    #[error("Token has no subject")]
+    MissingSubject,
+    #[error(transparent)]
+    Invalid(#[from] jsonwebtoken::errors::Error),
+}
+
+/// Checks HS256 and RS256 tokens from the identity provider against the
+/// configured keys.
+pub struct JwtVerifier {
+    keys: Vec<VerificationKey>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    roles_claim: String,
+}
+
+impl JwtVerifier {
+    /// Loads the keys named in the config. Returns `None` when no key is
+    /// configured, in which case only API keys are accepted.
+    pub fn from_config(config: &AppConfig) -> anyhow::Result<Option<Self>> {
+        let mut keys = Vec::new();
+
+        if let Some(secret) = &config.jwt_hs256_secret {
+            keys.push(VerificationKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.as_bytes()),
+            });
+        }
+
+        if let Some(path) = &config.jwt_rs256_public_key_file {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed to read JWT public key {}", path.display()))?;
+            keys.push(VerificationKey {
+                kid: None,
+                algorithm: Algorithm::RS256,
+                key: DecodingKey::from_rsa_pem(&pem)
+                    .with_context(|| format!("Invalid RSA public key in {}", path.display()))?,
+            });
+        }
+
+        if let Some(path) = &config.jwt_jwks_file {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read JWKS file {}", path.display()))?;
+            let jwks: JwkSet = serde_json::from_str(&json)
+                .with_context(|| format!("Invalid JWKS in {}", path.display()))?;
+
+            for jwk in &jwks.keys {
+                let algorithm = match jwk.algorithm {
+                    AlgorithmParameters::RSA(_) => Algorithm::RS256,
+                    AlgorithmParameters::OctetKey(_) => Algorithm::HS256,
+                    _ => {
+                        tracing::warn!("Skipping JWKS key {:?}: only RSA and octet keys are supported", jwk.common.key_id);
+                        continue;
+                    }
+                };
+                keys.push(VerificationKey {
+                    kid: jwk.common.key_id.clone(),
+                    algorithm,
+                    key: DecodingKey::from_jwk(jwk)
+                        .with_context(|| format!("Invalid JWKS key {:?}", jwk.common.key_id))?,
+                });
+            }
+        }
+
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            keys,
+            issuer: config.jwt_issuer.clone(),
+            audience: config.jwt_audience.clone(),
+            roles_claim: config.jwt_roles_claim.clone(),
+        }))
+    }
+
+    pub fn verify(&self, token: &str) -> Result<TokenClaims, JwtError> {
+        let header = decode_header(token)?;
+        if !matches!(header.alg, Algorithm::HS256 | Algorithm::RS256) {
+            return Err(JwtError::UnsupportedAlgorithm(header.alg));
+        }
+
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        // A token naming a key is only checked against that key
+        let candidates = self.keys.iter().filter(|key| {
+            key.algorithm == header.alg
+                && match (&header.kid, &key.kid) {
+                    (Some(wanted), Some(kid)) => wanted == kid,
+                    _ => true,
+                }
+        });
+
+        let mut result = Err(JwtError::UnknownKey);
+        for candidate in candidates {
+            result = decode::<Value>(token, &candidate.key, &validation).map_err(JwtError::from);
+            if result.is_ok() {
+                break;
+            }
+        }
+        let claims = result?.claims;
+
+        let subject = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .filter(|subject| !subject.is_empty())
+            .ok_or(JwtError::MissingSubject)?
+            .to_string();
+
+        // The roles claim may be a single role or a list of them
+        let roles = match claims.get(&self.roles_claim) {
+            Some(Value::String(role)) => vec![role.clone()],
+            Some(Value::Array(roles)) => roles
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(TokenClaims { subject, roles })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &[u8] = b"a-test-secret-of-reasonable-length";
+
+    fn verifier() -> JwtVerifier {
+        JwtVerifier {
+            keys: vec![VerificationKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(SECRET),
+            }],
+            issuer: Some("https://id.example.com".to_string()),
+            audience: Some("orders".to_string()),
+            roles_claim: "roles".to_string(),
+        }
+    }
+
+    fn claims() -> Value {
+        json!({
+            "sub": "alice",
+            "iss": "https://id.example.com",
+            "aud": "orders",
+            "exp": chrono::Utc::now().timestamp() + 600,
+            "roles": ["clerk", "unknown"],
+        })
+    }
+
+    fn token(algorithm: Algorithm, secret: &[u8], claims: &Value) -> String {
+        encode(&Header::new(algorithm), claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    #[test]
+    fn valid_tokens_give_subject_and_roles() {
+        let verified = verifier().verify(&token(Algorithm::HS256, SECRET, &claims())).unwrap();
+        assert_eq!(verified.subject, "alice");
+        assert_eq!(verified.roles, vec!["clerk", "unknown"]);
+
+        let mut single_role = claims();
+        single_role["roles"] = json!("manager");
+        let verified = verifier().verify(&token(Algorithm::HS256, SECRET, &single_role)).unwrap();
+        assert_eq!(verified.roles, vec!["manager"]);
+    }
+
+    #[test]
+    fn tampered_expired_or_misdirected_tokens_are_invalid() {
+        let error = verifier().verify(&token(Algorithm::HS256, b"some-other-secret", &claims())).unwrap_err();
+        assert!(matches!(error, JwtError::Invalid(_)), "{error}");
+
+        for (claim, value) in [
+            ("exp", json!(chrono::Utc::now().timestamp() - 3600)),
+            ("iss", json!("https://elsewhere.example.com")),
+            ("aud", json!("billing")),
+        ] {
+            let mut claims = claims();
+            claims[claim] = value;
+            let error = verifier().verify(&token(Algorithm::HS256, SECRET, &claims)).unwrap_err();
+            assert!(matches!(error, JwtError::Invalid(_)), "{claim}: {error}");
+        }
+    }
+
+    #[test]
+    fn tokens_need_a_subject_and_a_supported_algorithm() {
+        let mut claims = claims();
+        claims.as_object_mut().unwrap().remove("sub");
+        let error = verifier().verify(&token(Algorithm::HS256, SECRET, &claims)).unwrap_err();
+        assert!(matches!(error, JwtError::MissingSubject), "{error}");
+
+        let error = verifier().verify(&token(Algorithm::HS512, SECRET, &self::claims())).unwrap_err();
+        assert!(matches!(error, JwtError::UnsupportedAlgorithm(Algorithm::HS512)), "{error}");
+    }
+}
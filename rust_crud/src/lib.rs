@@ -0,0 +1,33 @@
+//! The order API as a library. `main` serves it over HTTP with the SQLite
+//! repositories; the storage traits, the services and the `InMemory*`
+//! implementations in `memory_repository` let it be embedded without a
+//! database file.
+
+pub mod config;
+pub mod database;
+pub mod migrations;
+pub mod models;
+pub mod repository;
+pub mod memory_repository;
+pub mod customer_repository;
+pub mod product_repository;
+pub mod report_repository;
+pub mod outbox_repository;
+pub mod service;
+pub mod customer_service;
+pub mod product_service;
+pub mod report_service;
+pub mod webhook_repository;
+pub mod webhook_service;
+pub mod webhook_dispatcher;
+pub mod api_key_repository;
+pub mod api_key_service;
+pub mod auth;
+pub mod jwt;
+pub mod rate_limiter;
+pub mod metrics;
+pub mod health;
+pub mod openapi;
+pub mod handlers;
+pub mod status_reporter;
+pub mod errors;
@@ -1,27 +1,55 @@
-mod config;
-mod database;
-mod models;
-mod repository;
-mod service;
-mod handlers;
-mod status_reporter;
-mod errors;
-
+use std::future::IntoFuture;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
 use axum::{
-    routing::{get, post, put, delete},
+    middleware,
+    routing::{get, post, put, patch, delete},
     Router,
 };
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use order_crud_api::{
+    api_key_repository, api_key_service, auth, config, customer_repository, customer_service, database,
+    handlers, health, jwt, metrics, migrations, models, openapi, outbox_repository, product_repository,
+    product_service, rate_limiter, report_repository, report_service, repository, service,
+    status_reporter, webhook_dispatcher, webhook_repository, webhook_service,
+};
 use config::AppConfig;
-use database::{create_pool, run_migrations};
-use repository::OrderRepository;
+use database::create_pool;
+use migrations::{migration_report, run_migrations};
+use repository::SqliteOrderRepository;
+use customer_repository::SqliteCustomerRepository;
+use product_repository::SqliteProductRepository;
+use report_repository::ReportRepository;
+use outbox_repository::OutboxRepository;
 use service::OrderService;
-use status_reporter::StatusReporter;
+use customer_service::CustomerService;
+use product_service::ProductService;
+use report_service::ReportService;
+use webhook_repository::WebhookRepository;
+use webhook_service::WebhookService;
+use webhook_dispatcher::WebhookDispatcher;
+use api_key_repository::ApiKeyRepository;
+use api_key_service::ApiKeyService;
+use auth::{require_scope, Authenticator, RequireScope};
+use jwt::JwtVerifier;
+use rate_limiter::{limit_failed_auth, rate_limit, LimitGroup, RateLimiter, RouteGroup};
+use metrics::{track_http, Metrics};
+use health::HealthChecker;
+use openapi::ApiDoc;
+use models::{ApiScope, CreateApiKeyRequest};
+use database::DatabasePool;
+use status_reporter::{OutboxStatusReporter, RetryPolicy, StatusDispatcher};
 use handlers::*;
 
+/// How long closing the database pool may take once requests have drained.
+const POOL_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Initialize tracing
@@ -35,41 +63,415 @@ async fn main() -> anyhow::Result<()> {
 
     // Load configuration
     let config = AppConfig::from_env()?;
-    
+
+    // `migrate` applies pending migrations and exits; `migrate status` only
+    // reports them
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => {}
+        ["migrate"] => {
+            let pool = create_pool(&config).await?;
+            run_migrations(&pool).await?;
+            migration_report(&pool).await?.print();
+            return Ok(());
+        }
+        ["migrate", "status"] => {
+            let pool = create_pool(&config).await?;
+            let report = migration_report(&pool).await?;
+            report.print();
+            if !report.is_current() {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        ["api-key", "create", name, scopes @ ..] => {
+            let pool = create_pool(&config).await?;
+            ensure_migrated(&config, &pool).await?;
+            let request = CreateApiKeyRequest {
+                name: name.to_string(),
+                scopes: scopes
+                    .iter()
+                    .map(|scope| scope.parse())
+                    .collect::<Result<_, String>>()
+                    .map_err(anyhow::Error::msg)?,
+            };
+            let outbox_repository = Arc::new(OutboxRepository::new(pool.clone()));
+            let metrics = Arc::new(Metrics::new(pool.clone())?);
+            let api_keys = ApiKeyService::new(
+                Arc::new(ApiKeyRepository::new(pool)),
+                Arc::new(OutboxStatusReporter::new(outbox_repository, metrics)),
+            );
+            let created = api_keys.create_api_key(request).await?;
+            println!("Created API key {} ({}); it will not be shown again:", created.api_key.id, created.api_key.name);
+            println!("{}", created.key);
+            return Ok(());
+        }
+        _ => anyhow::bail!("Usage: order-crud-api [migrate [status] | api-key create <name> <scope>...]"),
+    }
+
     tracing::info!("Starting Order CRUD API server on port {}", config.server_port);
 
     // Initialize database connection pool
     let pool = create_pool(&config).await?;
-    
-    // Run database migrations
-    run_migrations(&pool).await?;
+
+    // Run database migrations, or make sure someone already has
+    ensure_migrated(&config, &pool).await?;
 
     // Initialize services
-    let repository = Arc::new(OrderRepository::new(pool));
-    let status_reporter = Arc::new(StatusReporter::new(
+    let repository = Arc::new(SqliteOrderRepository::new(pool.clone()));
+    let customer_repository = Arc::new(SqliteCustomerRepository::new(pool.clone()));
+    let product_repository = Arc::new(SqliteProductRepository::new(pool.clone()));
+    let report_repository = Arc::new(ReportRepository::new(pool.clone()));
+    let webhook_repository = Arc::new(WebhookRepository::new(pool.clone()));
+    let api_key_repository = Arc::new(ApiKeyRepository::new(pool.clone()));
+    let outbox_repository = Arc::new(OutboxRepository::new(pool.clone()));
+    let metrics = Arc::new(Metrics::new(pool.clone())?);
+    let status_reporter = Arc::new(OutboxStatusReporter::new(outbox_repository, metrics.clone()));
+
+    // Turns true once the server has drained, telling the dispatchers to
+    // flush what is due and stop
+    let (stop_dispatchers, dispatcher_stop) = watch::channel(false);
+
+    // Deliver queued status reports in the background
+    let status_dispatcher = StatusDispatcher::new(
+        &status_reporter,
         config.status_endpoint.clone(),
         config.request_timeout,
-    ));
-    let service = Arc::new(OrderService::new(repository, status_reporter));
+        RetryPolicy {
+            max_attempts: config.status_max_attempts.max(1),
+            base_delay: config.status_retry_base,
+            max_delay: config.status_retry_max,
+            poll_interval: config.status_poll_interval,
+        },
+        config.status_retention,
+    )
+    .spawn(dispatcher_stop.clone());
 
-    // Setup routes
-    let app = Router::new()
-        .route("/api/orders", post(create_order))
+    // Deliver queued webhook notifications in the background
+    let webhook_wakeup = Arc::new(Notify::new());
+    let webhook_dispatcher = WebhookDispatcher::new(
+        webhook_repository.clone(),
+        webhook_wakeup.clone(),
+        config.request_timeout,
+        RetryPolicy {
+            max_attempts: config.webhook_max_attempts.max(1),
+            base_delay: config.webhook_retry_base,
+            max_delay: config.webhook_retry_max,
+            poll_interval: config.webhook_poll_interval,
+        },
+    )
+    .spawn(dispatcher_stop);
+
+    let (begin_shutdown, shutdown) = watch::channel(false);
+
+    let state = AppState {
+        orders: Arc::new(OrderService::new(
+            repository,
+            customer_repository.clone(),
+            product_repository.clone(),
+            status_reporter.clone(),
+            config.allow_price_override,
+            config.idempotency_window,
+            config.deleted_retention,
+        )),
+        customers: Arc::new(CustomerService::new(customer_repository, status_reporter.clone())),
+        products: Arc::new(ProductService::new(product_repository, status_reporter.clone())),
+        reports: Arc::new(ReportService::new(report_repository, status_reporter.clone())),
+        webhooks: Arc::new(WebhookService::new(webhook_repository, status_reporter.clone(), webhook_wakeup)),
+        api_keys: Arc::new(ApiKeyService::new(api_key_repository, status_reporter.clone())),
+        rate_limiter: Arc::new(RateLimiter::new(config.rate_limits)),
+        metrics,
+        health: Arc::new(HealthChecker::new(pool.clone(), status_reporter, config.health_check_timeout)),
+        shutdown: Shutdown(shutdown.clone()),
+    };
+
+    // Setup routes. Each group needs an API key or token with its scope;
+    // `admin` keys can use every group. Route layers run last-added first,
+    // so a client IP with too many failed authentications is turned away
+    // first, then callers are authenticated before they are counted against
+    // the group's rate limit
+    let authenticator = Arc::new(Authenticator::new(state.api_keys.clone(), JwtVerifier::from_config(&config)?));
+    let scoped = |scope| {
+        middleware::from_fn_with_state(
+            RequireScope {
+                authenticator: authenticator.clone(),
+                scope,
+            },
+            require_scope,
+        )
+    };
+    let limited = |group| {
+        middleware::from_fn_with_state(
+            LimitGroup {
+                limiter: state.rate_limiter.clone(),
+                group,
+            },
+            rate_limit,
+        )
+    };
+    let failed_auth = middleware::from_fn_with_state(state.rate_limiter.clone(), limit_failed_auth);
+
+    let reads = Router::new()
         .route("/api/orders", get(get_orders))
+        .route("/api/orders/stream", get(stream_orders))
+        .route("/api/orders/export.csv", get(export_orders_csv))
         .route("/api/orders/:id", get(get_order))
+        .route("/api/orders/:id/history", get(get_order_history))
+        .route("/api/customers", get(get_customers))
+        .route("/api/customers/:id", get(get_customer))
+        .route("/api/customers/:id/orders", get(get_customer_orders))
+        .route("/api/products", get(get_products))
+        .route("/api/products/:id", get(get_product))
+        .route("/api/products/:id/stock", get(get_product_stock))
+        .route("/api/reports/revenue", get(revenue_report))
+        .route("/api/reports/top-customers", get(top_customers_report))
+        .route("/api/reports/top-products", get(top_products_report))
+        .route("/api/reports/status-breakdown", get(status_breakdown_report))
+        .route_layer(limited(RouteGroup::Reads))
+        .route_layer(scoped(ApiScope::OrdersRead))
+        .route_layer(failed_auth.clone());
+
+    let writes = Router::new()
+        .route("/api/orders", post(create_order))
+        .route("/api/orders/bulk", post(bulk_create_orders))
+        .route("/api/orders/bulk", patch(bulk_update_orders))
+        .route("/api/orders/bulk", delete(bulk_delete_orders))
+        .route("/api/orders/import", post(import_orders_csv))
         .route("/api/orders/:id", put(update_order))
         .route("/api/orders/:id", delete(delete_order))
+        .route("/api/orders/:id/restore", post(restore_order))
+        .route("/api/orders/:id/process", post(process_order))
+        .route("/api/orders/:id/ship", post(ship_order))
+        .route("/api/orders/:id/deliver", post(deliver_order))
+        .route("/api/orders/:id/cancel", post(cancel_order))
+        .route("/api/customers", post(create_customer))
+        .route("/api/customers/:id", put(update_customer))
+        .route("/api/customers/:id", delete(delete_customer))
+        .route("/api/products", post(create_product))
+        .route("/api/products/:id", put(update_product))
+        .route("/api/products/:id", delete(delete_product))
+        .route("/api/products/:id/stock", put(set_product_stock))
+        .route_layer(limited(RouteGroup::Writes))
+        .route_layer(scoped(ApiScope::OrdersWrite))
+        .route_layer(failed_auth.clone());
+
+    let admin = Router::new()
+        .route("/api/webhooks", post(create_webhook))
+        .route("/api/webhooks", get(get_webhooks))
+        .route("/api/webhooks/:id", get(get_webhook))
+        .route("/api/webhooks/:id", put(update_webhook))
+        .route("/api/webhooks/:id", delete(delete_webhook))
+        .route("/api/webhooks/:id/deliveries", get(get_webhook_deliveries))
+        .route("/api/webhooks/:id/deliveries/:delivery_id/redeliver", post(redeliver_webhook))
+        .route("/api/admin/orders/purge", post(purge_deleted_orders))
+        .route("/api/admin/api-keys", post(create_api_key))
+        .route("/api/admin/api-keys", get(get_api_keys))
+        .route("/api/admin/api-keys/:id/revoke", post(revoke_api_key))
+        .route("/api/admin/rate-limits", get(get_rate_limits))
+        .route_layer(limited(RouteGroup::Admin))
+        .route_layer(scoped(ApiScope::Admin))
+        .route_layer(failed_auth);
+
+    let public = Router::new()
+        .route("/metrics", get(get_metrics))
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
+        .route_layer(limited(RouteGroup::Public));
+
+    // Probes are never limited, so a busy load balancer cannot take the
+    // instance out of rotation
+    let health = Router::new()
         .route("/health", get(health_check))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready));
+
+    let app = Router::new()
+        .merge(reads)
+        .merge(writes)
+        .merge(admin)
+        .merge(public)
+        .merge(health)
+        .layer(middleware::from_fn_with_state(state.metrics.clone(), track_http))
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
-        .with_state(service);
+        .with_state(state);
 
     // Start server
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.server_port)).await?;
     
     tracing::info!("Server listening on http://0.0.0.0:{}", config.server_port);
     
-    axum::serve(listener, app).await?;
-    
+    // Connection info gives the rate limiter the client IP
+    let mut server_shutdown = shutdown;
+    let mut server = tokio::spawn(
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move {
+                let _ = server_shutdown.wait_for(|shutdown| *shutdown).await;
+            })
+            .into_future(),
+    );
+
+    shutdown_signal().await;
+    tracing::info!(
+        "Shutting down; draining requests for up to {:?}",
+        config.shutdown_timeout
+    );
+    let deadline = tokio::time::Instant::now() + config.shutdown_timeout;
+    let _ = begin_shutdown.send(true);
+
+    // Stop accepting connections and let in-flight requests finish; past
+    // the deadline the remaining connections are dropped
+    match finish_by(deadline, &mut server).await {
+        Some(Ok(Ok(()))) => tracing::info!("All requests drained"),
+        Some(Ok(Err(e))) => tracing::error!("Server error while draining: {}", e),
+        Some(Err(e)) => tracing::error!("Server task failed: {}", e),
+        None => tracing::warn!("Shutdown deadline passed with requests still in flight; they were aborted"),
+    }
+
+    // Flush status reports and webhooks queued by the drained requests
+    let _ = stop_dispatchers.send(true);
+    for (name, mut dispatcher) in [("Status", status_dispatcher), ("Webhook", webhook_dispatcher)] {
+        if finish_by(deadline, &mut dispatcher).await.is_none() {
+            tracing::warn!("{} dispatcher did not finish by the shutdown deadline; undelivered items stay queued", name);
+        }
+    }
+
+    // Checkpointing the database can still take a moment after the deadline,
+    // but never hold up exit for long
+    if tokio::time::timeout(POOL_CLOSE_TIMEOUT, pool.close()).await.is_err() {
+        tracing::warn!("Database pool did not close within {:?}", POOL_CLOSE_TIMEOUT);
+    }
+    tracing::info!("Shutdown complete");
+
+    Ok(())
+}
+
+/// Waits for `task` until `deadline` and aborts it if it is still running
+/// then. `None` means it was aborted.
+async fn finish_by<T>(
+    deadline: tokio::time::Instant,
+    task: &mut tokio::task::JoinHandle<T>,
+) -> Option<Result<T, tokio::task::JoinError>> {
+    match tokio::time::timeout_at(deadline, &mut *task).await {
+        Ok(result) => Some(result),
+        Err(_) => {
+            task.abort();
+            None
+        }
+    }
+}
+
+/// Resolves on Ctrl+C, or SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Applies pending migrations when `AUTO_MIGRATE` is on; otherwise refuses
+/// to go on until someone has run `order-crud-api migrate`.
+async fn ensure_migrated(config: &AppConfig, pool: &DatabasePool) -> anyhow::Result<()> {
+    if config.auto_migrate {
+        run_migrations(pool).await?;
+        return Ok(());
+    }
+
+    let report = migration_report(pool).await?;
+    if !report.is_current() {
+        anyhow::bail!(
+            "Database has {} pending and {} modified migrations; run `order-crud-api migrate`",
+            report.pending().count(),
+            report.modified().count()
+        );
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Instant;
+
+    #[tokio::test]
+    async fn tasks_that_finish_in_time_return_their_result() {
+        let mut task = tokio::spawn(async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            7
+        });
+
+        let result = finish_by(Instant::now() + Duration::from_secs(5), &mut task).await;
+        assert_eq!(result.unwrap().unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn tasks_still_running_at_the_deadline_are_aborted() {
+        let (_keep_open, mut never) = watch::channel(false);
+        let mut task = tokio::spawn(async move {
+            let _ = never.wait_for(|stop| *stop).await;
+        });
+
+        let result = finish_by(Instant::now() + Duration::from_millis(20), &mut task).await;
+        assert!(result.is_none());
+        assert!(task.await.unwrap_err().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn graceful_shutdown_drains_in_flight_requests() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let started = Arc::new(Notify::new());
+        let app = Router::new().route(
+            "/slow",
+            get({
+                let started = started.clone();
+                || async move {
+                    started.notify_one();
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    "done"
+                }
+            }),
+        );
+
+        let (begin_shutdown, mut shutdown) = watch::channel(false);
+        let mut server = tokio::spawn(
+            axum::serve(listener, app)
+                .with_graceful_shutdown(async move {
+                    let _ = shutdown.wait_for(|shutdown| *shutdown).await;
+                })
+                .into_future(),
+        );
+
+        let request = tokio::spawn(async move {
+            reqwest::get(format!("http://{}/slow", addr)).await?.text().await
+        });
+        started.notified().await;
+        let _ = begin_shutdown.send(true);
+
+        let result = finish_by(Instant::now() + Duration::from_secs(5), &mut server).await;
+        assert!(matches!(result, Some(Ok(Ok(())))));
+        assert_eq!(request.await.unwrap().unwrap(), "done");
+    }
+}
@@ -0,0 +1,794 @@
+//! In-process implementations of the storage traits, for tests and for
+//! embedding `OrderService` without a database file. They follow the SQLite
+//! repositories except where noted:
+//!
+//! - Product stock is never reserved, so orders cannot fail on stock and
+//!   `restore` brings an order back without re-checking it.
+//! - `InMemoryCustomerRepository::count_orders` and
+//!   `InMemoryProductRepository::count_order_items` are always zero, so
+//!   customers and products with orders can be deleted.
+//! - Renaming a customer does not rename it on orders.
+//! - Nothing survives a restart.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use sqlx::types::Json;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use crate::customer_repository::CustomerRepository;
+use crate::models::{
+    Order, OrderItem, NewOrder, NewOrderItem, OrderChanges, OrderCustomer, OrderQuery, OrderEvent, OrderEventType,
+    OrderSortField, SortOrder, OrderStatus, IdempotencyKey, IdempotencyRecord, BulkMode, Customer,
+    CreateCustomerRequest, UpdateCustomerRequest, CustomerQuery, Product, CreateProductRequest,
+    UpdateProductRequest, ProductQuery, StockLevel, StatusReport,
+};
+use crate::product_repository::ProductRepository;
+use crate::repository::{changed_fields, snapshot, OrderRepository, RepositoryError};
+use crate::status_reporter::StatusReporter;
+
+/// Keeps orders, audit events and idempotency keys in process behind a
+/// mutex. See the module docs for where it differs from
+/// `SqliteOrderRepository`.
+#[derive(Default)]
+pub struct InMemoryOrderRepository {
+    store: Mutex<Store>,
+    /// Where customers named by an order are created
+    customers: Customers,
+}
+
+type Customers = Arc<Mutex<BTreeMap<i32, Customer>>>;
+
+#[derive(Debug, Clone, Default)]
+struct Store {
+    last_order_id: i32,
+    last_event_id: i64,
+    orders: BTreeMap<i32, Order>,
+    events: Vec<OrderEvent>,
+    /// Keyed by `(principal, key)`
+    idempotency_keys: HashMap<(String, String), IdempotencyRecord>,
+}
+
+impl InMemoryOrderRepository {
+    /// Creates customers named by an order in a store of its own; use
+    /// `with_customers` to share them with an `InMemoryCustomerRepository`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates customers named by an order in `customers`, as the SQLite
+    /// repository does in the `customers` table.
+    pub fn with_customers(customers: &InMemoryCustomerRepository) -> Self {
+        Self {
+            store: Mutex::default(),
+            customers: customers.customers.clone(),
+        }
+    }
+
+    /// A panic while the lock was held cannot leave the store half-written,
+    /// since every change is applied after its checks pass, so a poisoned
+    /// lock is still safe to use.
+    fn store(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The order store is always locked first, so holding both cannot
+    /// deadlock with `InMemoryCustomerRepository`, which only takes this one.
+    fn customers(&self) -> MutexGuard<'_, BTreeMap<i32, Customer>> {
+        self.customers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs `apply` over a copy of the store and customers and keeps the
+    /// copies unless an item failed in `AllOrNothing` mode.
+    fn batch<I, T>(
+        &self,
+        items: Vec<I>,
+        mode: BulkMode,
+        mut apply: impl FnMut(&mut Store, &mut BTreeMap<i32, Customer>, I) -> Result<T, RepositoryError>,
+    ) -> Vec<Result<T, RepositoryError>> {
+        let mut store = self.store();
+        let mut customers = self.customers();
+        let mut working = store.clone();
+        let mut working_customers = customers.clone();
+        let mut results = Vec::with_capacity(items.len());
+
+        for item in items {
+            let result = apply(&mut working, &mut working_customers, item);
+            let failed = result.is_err();
+            results.push(result);
+            if failed && mode == BulkMode::AllOrNothing {
+                return results;
+            }
+        }
+
+        *store = working;
+        *customers = working_customers;
+        results
+    }
+}
+
+#[async_trait]
+impl OrderRepository for InMemoryOrderRepository {
+    async fn create(
+        &self,
+        new_order: NewOrder,
+        idempotency_key: Option<&IdempotencyKey>,
+        actor: &str,
+    ) -> Result<Order, RepositoryError> {
+        let mut store = self.store();
+
+        if let Some(idempotency_key) = idempotency_key {
+            let now = Utc::now();
+            store.idempotency_keys.retain(|_, record| record.expires_at > now);
+            let id = (idempotency_key.principal.clone(), idempotency_key.key.clone());
+            if store.idempotency_keys.contains_key(&id) {
+                return Err(RepositoryError::DuplicateIdempotencyKey(idempotency_key.key.clone()));
+            }
+        }
+
+        let order = store.insert_order(&mut self.customers(), new_order, actor);
+
+        if let Some(idempotency_key) = idempotency_key {
+            store.idempotency_keys.insert(
+                (idempotency_key.principal.clone(), idempotency_key.key.clone()),
+                IdempotencyRecord {
+                    principal: idempotency_key.principal.clone(),
+                    key: idempotency_key.key.clone(),
+                    fingerprint: idempotency_key.fingerprint.clone(),
+                    order_id: order.id,
+                    response: Json(order.clone()),
+                    created_at: order.created_at,
+                    expires_at: idempotency_key.expires_at,
+                },
+            );
+        }
+
+        Ok(order)
+    }
+
+    async fn find_idempotency_record(
+        &self,
+        principal: &str,
+        key: &str,
+    ) -> Result<Option<IdempotencyRecord>, RepositoryError> {
+        let store = self.store();
+        let record = store
+            .idempotency_keys
+            .get(&(principal.to_string(), key.to_string()))
+            .filter(|record| record.expires_at > Utc::now())
+            .cloned();
+
+        Ok(record)
+    }
+
+    async fn find_all(&self, query: &OrderQuery) -> Result<(Vec<Order>, i64), RepositoryError> {
+        let store = self.store();
+        let mut matches: Vec<&Order> = store
+            .orders
+            .values()
+            .filter(|order| matches_query(order, query))
+            .collect();
+
+        let sort = query.sort.unwrap_or_default();
+        let direction = query.order.unwrap_or_default();
+        // `id` breaks ties so that pages stay stable when the sort column has duplicates
+        matches.sort_by(|a, b| {
+            let ordering = compare_by(sort, a, b).then(a.id.cmp(&b.id));
+            match direction {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        });
+
+        let total = matches.len() as i64;
+        let page = matches
+            .into_iter()
+            .skip(query.offset() as usize)
+            .take(query.limit() as usize)
+            .cloned()
+            .collect();
+
+        Ok((page, total))
+    }
+
+    async fn find_by_id(&self, id: i32, include_deleted: bool) -> Result<Option<Order>, RepositoryError> {
+        let store = self.store();
+        let order = store
+            .orders
+            .get(&id)
+            .filter(|order| include_deleted || order.deleted_at.is_none())
+            .cloned();
+
+        Ok(order)
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        changes: OrderChanges,
+        expected_version: Option<i64>,
+        actor: &str,
+    ) -> Result<Order, RepositoryError> {
+        self.store().update_order(&mut self.customers(), id, changes, expected_version, actor)
+    }
+
+    async fn delete(&self, id: i32, expected_version: Option<i64>, actor: &str) -> Result<bool, RepositoryError> {
+        match self.store().soft_delete_order(id, expected_version, actor) {
+            Ok(()) => Ok(true),
+            Err(RepositoryError::Database(sqlx::Error::RowNotFound)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn create_many(
+        &self,
+        orders: Vec<NewOrder>,
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<Order, RepositoryError>>, RepositoryError> {
+        Ok(self.batch(orders, mode, |store, customers, new_order| {
+            Ok(store.insert_order(customers, new_order, actor))
+        }))
+    }
+
+    async fn update_many(
+        &self,
+        updates: Vec<(i32, OrderChanges, Option<i64>)>,
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<Order, RepositoryError>>, RepositoryError> {
+        Ok(self.batch(updates, mode, |store, customers, (id, changes, expected_version)| {
+            store.update_order(customers, id, changes, expected_version, actor)
+        }))
+    }
+
+    async fn delete_many(
+        &self,
+        ids: &[i32],
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<(), RepositoryError>>, RepositoryError> {
+        Ok(self.batch(ids.to_vec(), mode, |store, _, id| store.soft_delete_order(id, None, actor)))
+    }
+
+    async fn restore(&self, id: i32, actor: &str) -> Result<Option<Order>, RepositoryError> {
+        let mut store = self.store();
+
+        let current = store.orders.get(&id).cloned()
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+        if current.deleted_at.is_none() {
+            return Ok(None);
+        }
+
+        // Stock is not tracked, so unlike SQLite there is nothing to reserve again
+
+        let order = Order {
+            version: current.version + 1,
+            updated_at: Utc::now(),
+            deleted_at: None,
+            ..current.clone()
+        };
+        store.orders.insert(id, order.clone());
+
+        let (old_values, new_values) = changed_fields(&snapshot(&current), &snapshot(&order));
+        store.record_event(id, OrderEventType::Updated, actor, Some(old_values), Some(new_values));
+
+        Ok(Some(order))
+    }
+
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        let mut store = self.store();
+        let before = store.orders.len();
+        store
+            .orders
+            .retain(|_, order| order.deleted_at.is_none_or(|deleted_at| deleted_at >= deleted_before));
+
+        Ok((before - store.orders.len()) as u64)
+    }
+
+    async fn find_events(&self, order_id: i32) -> Result<Vec<OrderEvent>, RepositoryError> {
+        let store = self.store();
+        let events = store
+            .events
+            .iter()
+            .filter(|event| event.order_id == order_id)
+            .cloned()
+            .collect();
+
+        Ok(events)
+    }
+
+    async fn find_events_after(&self, after_id: i64, limit: i64) -> Result<Vec<OrderEvent>, RepositoryError> {
+        let store = self.store();
+        let events = store
+            .events
+            .iter()
+            .filter(|event| event.id > after_id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+
+        Ok(events)
+    }
+
+    async fn latest_event_id(&self) -> Result<i64, RepositoryError> {
+        Ok(self.store().last_event_id)
+    }
+}
+
+impl Store {
+    fn insert_order(&mut self, customers: &mut BTreeMap<i32, Customer>, new_order: NewOrder, actor: &str) -> Order {
+        self.last_order_id += 1;
+        let id = self.last_order_id;
+        let total_amount = new_order.total_amount();
+        let items = order_items(id, &new_order.items);
+        let (customer_id, customer_name) = order_customer(customers, new_order.customer);
+        let now = Utc::now();
+
+        let order = Order {
+            id,
+            customer_id,
+            customer_name,
+            product_name: items[0].product_name.clone(),
+            quantity: items[0].quantity,
+            unit_price: items[0].unit_price,
+            total_amount,
+            order_date: now,
+            status: OrderStatus::Pending,
+            version: 1,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            items,
+        };
+
+        self.orders.insert(id, order.clone());
+        self.record_event(id, OrderEventType::Created, actor, None, Some(snapshot(&order)));
+        order
+    }
+
+    fn update_order(
+        &mut self,
+        customers: &mut BTreeMap<i32, Customer>,
+        id: i32,
+        changes: OrderChanges,
+        expected_version: Option<i64>,
+        actor: &str,
+    ) -> Result<Order, RepositoryError> {
+        let current = self.live_order(id, expected_version)?;
+        let (customer_id, customer_name) = match changes.customer {
+            Some(customer) => order_customer(customers, customer),
+            None => (current.customer_id, current.customer_name.clone()),
+        };
+
+        let items = match &changes.items {
+            Some(new_items) => order_items(id, new_items),
+            None => current.items.clone(),
+        };
+        let total_amount: Decimal = items.iter().map(|item| item.line_total).sum();
+
+        let order = Order {
+            customer_id,
+            customer_name,
+            product_name: items[0].product_name.clone(),
+            quantity: items[0].quantity,
+            unit_price: items[0].unit_price,
+            total_amount,
+            status: changes.status.unwrap_or(current.status),
+            version: current.version + 1,
+            updated_at: Utc::now(),
+            items,
+            ..current.clone()
+        };
+        self.orders.insert(id, order.clone());
+
+        let event_type = if order.status != current.status {
+            OrderEventType::StatusChanged
+        } else {
+            OrderEventType::Updated
+        };
+        let (old_values, new_values) = changed_fields(&snapshot(&current), &snapshot(&order));
+        self.record_event(id, event_type, actor, Some(old_values), Some(new_values));
+
+        Ok(order)
+    }
+
+    fn soft_delete_order(&mut self, id: i32, expected_version: Option<i64>, actor: &str) -> Result<(), RepositoryError> {
+        let current = self.live_order(id, expected_version)?;
+
+        let now = Utc::now();
+        let order = Order {
+            version: current.version + 1,
+            updated_at: now,
+            deleted_at: Some(now),
+            ..current.clone()
+        };
+        self.orders.insert(id, order);
+
+        self.record_event(id, OrderEventType::Deleted, actor, Some(snapshot(&current)), None);
+        Ok(())
+    }
+
+    /// Returns the live order if it is at `expected_version` (or any version
+    /// when `None`).
+    fn live_order(&self, id: i32, expected_version: Option<i64>) -> Result<Order, RepositoryError> {
+        let current = self
+            .orders
+            .get(&id)
+            .filter(|order| order.deleted_at.is_none())
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+
+        let expected = expected_version.unwrap_or(current.version);
+        if current.version != expected {
+            return Err(RepositoryError::VersionConflict { id, expected, actual: current.version });
+        }
+
+        Ok(current.clone())
+    }
+
+    fn record_event(
+        &mut self,
+        order_id: i32,
+        event_type: OrderEventType,
+        actor: &str,
+        old_values: Option<Value>,
+        new_values: Option<Value>,
+    ) {
+        self.last_event_id += 1;
+        self.events.push(OrderEvent {
+            id: self.last_event_id,
+            order_id,
+            event_type,
+            actor: actor.to_string(),
+            old_values: old_values.map(Json),
+            new_values: new_values.map(Json),
+            created_at: Utc::now(),
+        });
+    }
+}
+
+/// The in-memory counterpart of `repository::order_customer`.
+fn order_customer(customers: &mut BTreeMap<i32, Customer>, customer: OrderCustomer) -> (i32, String) {
+    let customer = match customer {
+        OrderCustomer::Existing { id, name } => return (id, name),
+        OrderCustomer::New(name) => match find_customer(customers, &name) {
+            Some(customer) => customer.clone(),
+            None => insert_customer(customers, CreateCustomerRequest { name, email: None, phone: None }),
+        },
+    };
+
+    (customer.id, customer.name)
+}
+
+fn order_items(order_id: i32, items: &[NewOrderItem]) -> Vec<OrderItem> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| OrderItem {
+            order_id,
+            line_number: index as i32 + 1,
+            product_id: item.product_id,
+            sku: item.sku.clone(),
+            product_name: item.product_name.clone(),
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+            line_total: item.line_total(),
+        })
+        .collect()
+}
+
+/// The in-memory counterpart of `repository::push_filters`.
+fn matches_query(order: &Order, query: &OrderQuery) -> bool {
+    (query.include_deleted() || order.deleted_at.is_none())
+        && query.status.is_none_or(|status| order.status == status)
+        && query.customer_id.is_none_or(|customer_id| order.customer_id == customer_id)
+        && query.customer_name.as_ref().is_none_or(|name| &order.customer_name == name)
+        && query.product_name.as_ref().is_none_or(|name| {
+            order.items.iter().any(|item| &item.product_name == name)
+        })
+        && query.order_date_from.is_none_or(|from| order.order_date >= from)
+        && query.order_date_to.is_none_or(|to| order.order_date <= to)
+        && query.min_amount.is_none_or(|min| order.total_amount >= min)
+        && query.max_amount.is_none_or(|max| order.total_amount <= max)
+}
+
+fn compare_by(sort: OrderSortField, a: &Order, b: &Order) -> Ordering {
+    match sort {
+        OrderSortField::Id => a.id.cmp(&b.id),
+        OrderSortField::CustomerName => a.customer_name.cmp(&b.customer_name),
+        OrderSortField::ProductName => a.product_name.cmp(&b.product_name),
+        OrderSortField::TotalAmount => a.total_amount.cmp(&b.total_amount),
+        OrderSortField::OrderDate => a.order_date.cmp(&b.order_date),
+        OrderSortField::Status => a.status.as_str().cmp(b.status.as_str()),
+        OrderSortField::CreatedAt => a.created_at.cmp(&b.created_at),
+    }
+}
+
+/// Customers kept in process. Orders live in their own repository, so
+/// `count_orders` is always zero and a rename is not copied onto orders.
+/// Pass it to `InMemoryOrderRepository::with_customers` so that customers
+/// named by orders show up here.
+#[derive(Default)]
+pub struct InMemoryCustomerRepository {
+    customers: Customers,
+}
+
+impl InMemoryCustomerRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn customers(&self) -> MutexGuard<'_, BTreeMap<i32, Customer>> {
+        self.customers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Customer names are unique regardless of case, as in `customers.name`.
+fn find_customer<'a>(customers: &'a BTreeMap<i32, Customer>, name: &str) -> Option<&'a Customer> {
+    customers.values().find(|customer| customer.name.eq_ignore_ascii_case(name))
+}
+
+fn insert_customer(customers: &mut BTreeMap<i32, Customer>, request: CreateCustomerRequest) -> Customer {
+    let now = Utc::now();
+    let customer = Customer {
+        id: customers.keys().next_back().map_or(1, |id| id + 1),
+        name: request.name,
+        email: request.email,
+        phone: request.phone,
+        created_at: now,
+        updated_at: now,
+    };
+    customers.insert(customer.id, customer.clone());
+    customer
+}
+
+#[async_trait]
+impl CustomerRepository for InMemoryCustomerRepository {
+    async fn create(&self, request: CreateCustomerRequest) -> Result<Customer, RepositoryError> {
+        let mut customers = self.customers();
+        if find_customer(&customers, &request.name).is_some() {
+            return Err(RepositoryError::UniqueViolation("customers.name".to_string()));
+        }
+
+        Ok(insert_customer(&mut customers, request))
+    }
+
+    async fn find_all(&self, query: &CustomerQuery) -> Result<Vec<Customer>, RepositoryError> {
+        let customers = self.customers();
+        let prefix = query.name.as_deref().map(str::to_lowercase);
+        let mut matches: Vec<&Customer> = customers
+            .values()
+            .filter(|customer| {
+                prefix.as_ref().is_none_or(|prefix| customer.name.to_lowercase().starts_with(prefix))
+            })
+            .collect();
+        matches.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+
+        Ok(matches
+            .into_iter()
+            .skip(query.offset() as usize)
+            .take(query.limit() as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<Customer>, RepositoryError> {
+        Ok(self.customers().get(&id).cloned())
+    }
+
+    async fn find_by_name(&self, name: &str) -> Result<Option<Customer>, RepositoryError> {
+        Ok(find_customer(&self.customers(), name).cloned())
+    }
+
+    async fn update(&self, id: i32, request: UpdateCustomerRequest, _actor: &str) -> Result<Customer, RepositoryError> {
+        let mut customers = self.customers();
+        let current = customers.get(&id).cloned()
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+
+        if let Some(name) = &request.name {
+            if find_customer(&customers, name).is_some_and(|other| other.id != id) {
+                return Err(RepositoryError::UniqueViolation("customers.name".to_string()));
+            }
+        }
+
+        let customer = Customer {
+            name: request.name.unwrap_or(current.name),
+            email: request.email.or(current.email),
+            phone: request.phone.or(current.phone),
+            updated_at: Utc::now(),
+            ..current
+        };
+        customers.insert(id, customer.clone());
+
+        Ok(customer)
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, RepositoryError> {
+        Ok(self.customers().remove(&id).is_some())
+    }
+
+    async fn count_orders(&self, _id: i32) -> Result<i64, RepositoryError> {
+        Ok(0)
+    }
+}
+
+/// Products and their on-hand counts kept in process. Orders live in their
+/// own repository, so nothing is ever reserved and `count_order_items` is
+/// always zero.
+#[derive(Default)]
+pub struct InMemoryProductRepository {
+    products: Mutex<BTreeMap<i32, (Product, i64)>>,
+}
+
+impl InMemoryProductRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn products(&self) -> MutexGuard<'_, BTreeMap<i32, (Product, i64)>> {
+        self.products.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn stock_level(product: &Product, on_hand: i64) -> StockLevel {
+    StockLevel {
+        product_id: product.id,
+        sku: product.sku.clone(),
+        on_hand,
+        reserved: 0,
+        available: on_hand,
+    }
+}
+
+#[async_trait]
+impl ProductRepository for InMemoryProductRepository {
+    async fn create(&self, request: CreateProductRequest) -> Result<Product, RepositoryError> {
+        let mut products = self.products();
+        if products.values().any(|(product, _)| product.sku == request.sku) {
+            return Err(RepositoryError::UniqueViolation("products.sku".to_string()));
+        }
+
+        let now = Utc::now();
+        let product = Product {
+            id: products.keys().next_back().map_or(1, |id| id + 1),
+            sku: request.sku,
+            name: request.name,
+            list_price: request.list_price,
+            active: request.active.unwrap_or(true),
+            created_at: now,
+            updated_at: now,
+        };
+        products.insert(product.id, (product.clone(), request.stock_on_hand.unwrap_or(0)));
+
+        Ok(product)
+    }
+
+    async fn find_all(&self, query: &ProductQuery) -> Result<Vec<Product>, RepositoryError> {
+        let products = self.products();
+        let mut matches: Vec<&Product> = products
+            .values()
+            .map(|(product, _)| product)
+            .filter(|product| query.active.is_none_or(|active| product.active == active))
+            .collect();
+        matches.sort_by(|a, b| a.sku.cmp(&b.sku));
+
+        Ok(matches
+            .into_iter()
+            .skip(query.offset() as usize)
+            .take(query.limit() as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<Product>, RepositoryError> {
+        Ok(self.products().get(&id).map(|(product, _)| product.clone()))
+    }
+
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, RepositoryError> {
+        let products = self.products();
+        let product = products
+            .values()
+            .map(|(product, _)| product)
+            .find(|product| product.sku == sku)
+            .cloned();
+
+        Ok(product)
+    }
+
+    async fn find_by_name(&self, name: &str) -> Result<Vec<Product>, RepositoryError> {
+        let products = self.products();
+        let mut matches: Vec<Product> = products
+            .values()
+            .map(|(product, _)| product)
+            .filter(|product| product.name == name)
+            .cloned()
+            .collect();
+        matches.sort_by(|a, b| a.sku.cmp(&b.sku));
+
+        Ok(matches)
+    }
+
+    async fn update(&self, id: i32, request: UpdateProductRequest) -> Result<Product, RepositoryError> {
+        let mut products = self.products();
+        let (current, _) = products.get_mut(&id)
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+
+        if let Some(name) = request.name {
+            current.name = name;
+        }
+        if let Some(list_price) = request.list_price {
+            current.list_price = list_price;
+        }
+        if let Some(active) = request.active {
+            current.active = active;
+        }
+        current.updated_at = Utc::now();
+
+        Ok(current.clone())
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, RepositoryError> {
+        Ok(self.products().remove(&id).is_some())
+    }
+
+    async fn get_stock(&self, id: i32) -> Result<Option<StockLevel>, RepositoryError> {
+        Ok(self.products().get(&id).map(|(product, on_hand)| stock_level(product, *on_hand)))
+    }
+
+    async fn set_stock(&self, id: i32, on_hand: i64) -> Result<StockLevel, RepositoryError> {
+        let mut products = self.products();
+        let (product, current) = products.get_mut(&id)
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+        *current = on_hand;
+
+        Ok(stock_level(product, on_hand))
+    }
+
+    async fn count_order_items(&self, _id: i32) -> Result<i64, RepositoryError> {
+        Ok(0)
+    }
+}
+
+/// Keeps every report in memory instead of sending it, so tests can check
+/// what an operation reported.
+#[derive(Default)]
+pub struct InMemoryStatusReporter {
+    reports: Mutex<Vec<StatusReport>>,
+}
+
+impl InMemoryStatusReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `(operation, success)` of every report so far, oldest first.
+    pub fn outcomes(&self) -> Vec<(String, bool)> {
+        self.reports
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|report| (report.operation.clone(), report.success))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl StatusReporter for InMemoryStatusReporter {
+    async fn report_status(
+        &self,
+        operation: &str,
+        success: bool,
+        details: Option<String>,
+        order_id: Option<i32>,
+    ) {
+        self.reports.lock().unwrap_or_else(PoisonError::into_inner).push(StatusReport {
+            operation: operation.to_string(),
+            success,
+            timestamp: Utc::now(),
+            details,
+            order_id,
+        });
+    }
+}
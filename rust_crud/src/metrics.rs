@@ -0,0 +1,137 @@
+use std::sync::Arc;
+use std::time::Instant;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use crate::database::DatabasePool;
+
+/// Route label for requests that matched no route, so probing random paths
+/// cannot grow the label set.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Prometheus metrics for the process, served on `GET /metrics`.
+pub struct Metrics {
+    registry: Registry,
+    pool: DatabasePool,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    operations: IntCounterVec,
+    pool_size: IntGauge,
+    pool_idle: IntGauge,
+    status_reports_sent: IntCounter,
+    status_reports_failed: IntCounter,
+    status_reports_dead_lettered: IntCounter,
+}
+
+impl Metrics {
+    pub fn new(pool: DatabasePool) -> prometheus::Result<Self> {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route, method and status"),
+            &["method", "route", "status"],
+        )?;
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by route and method"),
+            &["method", "route"],
+        )?;
+        let operations = IntCounterVec::new(
+            Opts::new("service_operations_total", "Service operations by name and outcome"),
+            &["operation", "outcome"],
+        )?;
+        let pool_size = IntGauge::new("db_pool_connections", "Open SQLite pool connections")?;
+        let pool_idle = IntGauge::new("db_pool_idle_connections", "Idle SQLite pool connections")?;
+        let status_reports_sent = IntCounter::new(
+            "status_reports_sent_total",
+            "Status reports accepted by the status endpoint",
+        )?;
+        let status_reports_failed = IntCounter::new(
+            "status_reports_failed_total",
+            "Failed status report delivery attempts",
+        )?;
+        let status_reports_dead_lettered = IntCounter::new(
+            "status_reports_dead_lettered_total",
+            "Status reports given up on after the last attempt",
+        )?;
+
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_duration.clone()))?;
+        registry.register(Box::new(operations.clone()))?;
+        registry.register(Box::new(pool_size.clone()))?;
+        registry.register(Box::new(pool_idle.clone()))?;
+        registry.register(Box::new(status_reports_sent.clone()))?;
+        registry.register(Box::new(status_reports_failed.clone()))?;
+        registry.register(Box::new(status_reports_dead_lettered.clone()))?;
+
+        Ok(Self {
+            registry,
+            pool,
+            http_requests,
+            http_duration,
+            operations,
+            pool_size,
+            pool_idle,
+            status_reports_sent,
+            status_reports_failed,
+            status_reports_dead_lettered,
+        })
+    }
+
+    /// Counts an operation reported through `StatusReporter`.
+    pub fn record_operation(&self, operation: &str, success: bool) {
+        let outcome = if success { "success" } else { "failure" };
+        self.operations.with_label_values(&[operation, outcome]).inc();
+    }
+
+    pub fn record_status_report_sent(&self) {
+        self.status_reports_sent.inc();
+    }
+
+    /// Counts a failed delivery attempt; `dead_lettered` when it was the last.
+    pub fn record_status_report_failed(&self, dead_lettered: bool) {
+        self.status_reports_failed.inc();
+        if dead_lettered {
+            self.status_reports_dead_lettered.inc();
+        }
+    }
+
+    /// The text exposition of every metric. Pool gauges are read now.
+    pub fn render(&self) -> String {
+        self.pool_size.set(i64::from(self.pool.size()));
+        self.pool_idle.set(self.pool.num_idle() as i64);
+
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Middleware counting every request and its latency by route template.
+pub async fn track_http(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    metrics
+        .http_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    metrics
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    response
+}
@@ -0,0 +1,360 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, FromRow};
+use std::collections::HashMap;
+use crate::database::DatabasePool;
+
+/// A numbered schema change from the `migrations/` directory. Files are
+/// embedded at build time; add new ones to the end of `MIGRATIONS` and never
+/// edit one that has been released.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+impl Migration {
+    const fn new(version: i64, name: &'static str, sql: &'static str) -> Self {
+        Self { version, name, sql }
+    }
+
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.sql.as_bytes()))
+    }
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration::new(1, "create_orders", include_str!("../migrations/0001_create_orders.sql")),
+    Migration::new(2, "create_customers", include_str!("../migrations/0002_create_customers.sql")),
+    Migration::new(3, "create_order_items", include_str!("../migrations/0003_create_order_items.sql")),
+    Migration::new(4, "create_products", include_str!("../migrations/0004_create_products.sql")),
+    Migration::new(5, "create_order_events", include_str!("../migrations/0005_create_order_events.sql")),
+    Migration::new(6, "create_inventory", include_str!("../migrations/0006_create_inventory.sql")),
+    Migration::new(7, "add_order_version", include_str!("../migrations/0007_add_order_version.sql")),
+    Migration::new(8, "create_idempotency_keys", include_str!("../migrations/0008_create_idempotency_keys.sql")),
+    Migration::new(9, "add_order_deleted_at", include_str!("../migrations/0009_add_order_deleted_at.sql")),
+    Migration::new(10, "create_status_outbox", include_str!("../migrations/0010_create_status_outbox.sql")),
+    Migration::new(11, "create_webhooks", include_str!("../migrations/0011_create_webhooks.sql")),
+    Migration::new(12, "create_api_keys", include_str!("../migrations/0012_create_api_keys.sql")),
+    Migration::new(13, "scope_idempotency_keys", include_str!("../migrations/0013_scope_idempotency_keys.sql")),
+    Migration::new(14, "index_status_outbox_delivered_at", include_str!("../migrations/0014_index_status_outbox_delivered_at.sql")),
+];
+
+/// The schema that the hard-coded startup migration built before migrations
+/// were tracked. Databases without `schema_migrations` are recorded as being
+/// at this version and get every later migration applied as usual.
+pub const LEGACY_BASELINE: i64 = 1;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+pub enum MigrationState {
+    Applied(DateTime<Utc>),
+    Pending,
+    /// The file changed after it was applied
+    Modified(DateTime<Utc>),
+}
+
+pub struct MigrationReport {
+    pub migrations: Vec<(&'static Migration, MigrationState)>,
+    /// Versions recorded in the database that this build does not know about
+    pub unknown: Vec<AppliedMigration>,
+}
+
+impl MigrationReport {
+    pub fn pending(&self) -> impl Iterator<Item = &'static Migration> + '_ {
+        self.migrations
+            .iter()
+            .filter(|(_, state)| matches!(state, MigrationState::Pending))
+            .map(|(migration, _)| *migration)
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &'static Migration> + '_ {
+        self.migrations
+            .iter()
+            .filter(|(_, state)| matches!(state, MigrationState::Modified(_)))
+            .map(|(migration, _)| *migration)
+    }
+
+    pub fn is_current(&self) -> bool {
+        self.pending().next().is_none() && self.modified().next().is_none()
+    }
+
+    pub fn print(&self) {
+        for (migration, state) in &self.migrations {
+            let state = match state {
+                MigrationState::Applied(at) => format!("applied {}", at.to_rfc3339()),
+                MigrationState::Pending => "pending".to_string(),
+                MigrationState::Modified(at) => {
+                    format!("CHECKSUM MISMATCH (applied {})", at.to_rfc3339())
+                }
+            };
+            println!("{:04} {:<32} {}", migration.version, migration.name, state);
+        }
+
+        for applied in &self.unknown {
+            println!(
+                "{:04} {:<32} applied {}, not in this build",
+                applied.version,
+                applied.name,
+                applied.applied_at.to_rfc3339()
+            );
+        }
+
+        println!("{} pending", self.pending().count());
+    }
+}
+
+/// Compares the embedded migrations with the ones recorded in the database.
+pub async fn migration_report(pool: &DatabasePool) -> anyhow::Result<MigrationReport> {
+    let mut applied: HashMap<i64, AppliedMigration> = applied_migrations(pool)
+        .await?
+        .into_iter()
+        .map(|migration| (migration.version, migration))
+        .collect();
+
+    let migrations = MIGRATIONS
+        .iter()
+        .map(|migration| {
+            let state = match applied.remove(&migration.version) {
+                Some(record) if record.checksum == migration.checksum() => {
+                    MigrationState::Applied(record.applied_at)
+                }
+                Some(record) => MigrationState::Modified(record.applied_at),
+                None => MigrationState::Pending,
+            };
+            (migration, state)
+        })
+        .collect();
+
+    let mut unknown: Vec<AppliedMigration> = applied.into_values().collect();
+    unknown.sort_by_key(|migration| migration.version);
+
+    Ok(MigrationReport { migrations, unknown })
+}
+
+/// Applies every pending migration in version order, each in its own
+/// transaction. Refuses to run if an applied migration has been edited.
+pub async fn run_migrations(pool: &DatabasePool) -> anyhow::Result<()> {
+    tracing::info!("Running database migrations");
+
+    ensure_migrations_table(pool).await?;
+    adopt_legacy_schema(pool).await?;
+
+    let report = migration_report(pool).await?;
+
+    if let Some(migration) = report.modified().next() {
+        anyhow::bail!(
+            "Migration {:04}_{} was changed after it was applied; restore the original file and add a new migration instead",
+            migration.version,
+            migration.name
+        );
+    }
+
+    for applied in &report.unknown {
+        tracing::warn!(
+            "Database has migration {:04}_{} which this build does not know about",
+            applied.version,
+            applied.name
+        );
+    }
+
+    for migration in report.pending() {
+        tracing::info!("Applying migration {:04}_{}", migration.version, migration.name);
+
+        let mut tx = pool.begin().await?;
+
+        (&mut *tx).execute(migration.sql).await.map_err(|e| {
+            anyhow::anyhow!("Migration {:04}_{} failed: {}", migration.version, migration.name, e)
+        })?;
+        record_migration(&mut tx, migration).await?;
+
+        tx.commit().await?;
+    }
+
+    tracing::info!("Database migrations completed successfully");
+    Ok(())
+}
+
+async fn ensure_migrations_table(pool: &DatabasePool) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        );
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Reads `schema_migrations` without creating it, so reporting never
+/// changes the database.
+async fn applied_migrations(pool: &DatabasePool) -> anyhow::Result<Vec<AppliedMigration>> {
+    if !table_exists(pool, "schema_migrations").await? {
+        return Ok(Vec::new());
+    }
+
+    let applied = sqlx::query_as::<_, AppliedMigration>(
+        "SELECT version, name, checksum, applied_at FROM schema_migrations ORDER BY version"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(applied)
+}
+
+async fn table_exists(pool: &DatabasePool, table: &str) -> anyhow::Result<bool> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?"
+    )
+    .bind(table)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count > 0)
+}
+
+/// A database with an `orders` table but no recorded migrations was built by
+/// the old hard-coded migration, whose schema is `LEGACY_BASELINE`. Record
+/// it as such so that only the later migrations run.
+async fn adopt_legacy_schema(pool: &DatabasePool) -> anyhow::Result<()> {
+    if !table_exists(pool, "orders").await? || !applied_migrations(pool).await?.is_empty() {
+        return Ok(());
+    }
+
+    tracing::info!("Found an untracked schema; recording it as migration {:04}", LEGACY_BASELINE);
+
+    let mut tx = pool.begin().await?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= LEGACY_BASELINE) {
+        record_migration(&mut tx, migration).await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn record_migration(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    migration: &Migration,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        "INSERT INTO schema_migrations (version, name, checksum, applied_at) VALUES (?, ?, ?, ?)"
+    )
+    .bind(migration.version)
+    .bind(migration.name)
+    .bind(migration.checksum())
+    .bind(Utc::now())
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The schema the server created at startup before migrations were tracked.
+    const LEGACY_SCHEMA: &str = r#"
+        CREATE TABLE IF NOT EXISTS orders (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            customer_name TEXT NOT NULL,
+            product_name TEXT NOT NULL,
+            quantity INTEGER NOT NULL CHECK (quantity > 0),
+            unit_price REAL NOT NULL CHECK (unit_price > 0),
+            total_amount REAL NOT NULL,
+            order_date TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'Pending',
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+
+            CHECK (status IN ('Pending', 'Processing', 'Shipped', 'Delivered', 'Cancelled'))
+        );
+        CREATE INDEX IF NOT EXISTS IX_orders_customer_name ON orders(customer_name);
+        CREATE INDEX IF NOT EXISTS IX_orders_order_date ON orders(order_date);
+        CREATE INDEX IF NOT EXISTS IX_orders_status ON orders(status);
+    "#;
+
+    async fn empty_pool() -> DatabasePool {
+        sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fresh_database_gets_every_migration() {
+        let pool = empty_pool().await;
+
+        run_migrations(&pool).await.unwrap();
+
+        let report = migration_report(&pool).await.unwrap();
+        assert!(report.is_current());
+        assert!(report.unknown.is_empty());
+    }
+
+    #[tokio::test]
+    async fn untracked_schema_is_adopted_and_upgraded() {
+        let pool = empty_pool().await;
+        pool.execute(LEGACY_SCHEMA).await.unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO orders (customer_name, product_name, quantity, unit_price, total_amount, order_date, status, created_at, updated_at)
+            VALUES ('Ada', 'Widget', 2, 2.5, 5.0, '2024-01-01T00:00:00Z', 'Pending', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')
+            "#
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        run_migrations(&pool).await.unwrap();
+
+        let report = migration_report(&pool).await.unwrap();
+        assert!(report.is_current());
+
+        // The legacy order was backfilled by the migrations after the baseline
+        let (customer, version): (String, i64) = sqlx::query_as(
+            "SELECT c.name, o.version FROM orders o JOIN customers c ON c.id = o.customer_id"
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(customer, "Ada");
+        assert_eq!(version, 1);
+
+        let lines: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM order_items")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(lines, 1);
+
+        // Running again is a no-op
+        run_migrations(&pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn edited_migration_is_refused() {
+        let pool = empty_pool().await;
+        run_migrations(&pool).await.unwrap();
+
+        sqlx::query("UPDATE schema_migrations SET checksum = 'edited' WHERE version = 2")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let error = run_migrations(&pool).await.unwrap_err();
+        assert!(error.to_string().contains("0002_create_customers"));
+    }
+}
@@ -1,25 +1,85 @@
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use std::borrow::Cow;
+use sqlx::types::Json;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub const MAX_ORDER_ITEMS: usize = 100;
+
+/// An order and its line items. `product_name`, `quantity` and `unit_price`
+/// mirror the first line so that single-item clients keep working;
+/// `total_amount` is the sum of every line.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct Order {
     pub id: i32,
+    pub customer_id: i32,
     pub customer_name: String,
     pub product_name: String,
     pub quantity: i32,
+    #[sqlx(try_from = "f64")]
     pub unit_price: Decimal,
+    #[sqlx(try_from = "f64")]
     pub total_amount: Decimal,
     pub order_date: DateTime<Utc>,
     pub status: OrderStatus,
+    /// Bumped on every write; exposed to HTTP clients as the `ETag`
+    pub version: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Set when the order is soft-deleted
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[sqlx(skip)]
+    pub items: Vec<OrderItem>,
+}
+
+impl Order {
+    /// Strong entity tag for the current version, e.g. `"3"`.
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version)
+    }
+}
+
+/// The order versions a write may apply to, from the `If-Match` header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum VersionMatch {
+    /// No `If-Match`, or `*`
+    #[default]
+    Any,
+    /// The versions of the strong entity tags listed. Empty when none of
+    /// them names a version, so that nothing matches.
+    OneOf(Vec<i64>),
+}
+
+impl VersionMatch {
+    pub fn matches(&self, version: i64) -> bool {
+        match self {
+            VersionMatch::Any => true,
+            VersionMatch::OneOf(versions) => versions.contains(&version),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct OrderItem {
+    #[serde(skip)]
+    pub order_id: i32,
+    pub line_number: i32,
+    pub product_id: Option<i32>,
+    pub sku: Option<String>,
+    pub product_name: String,
+    pub quantity: i32,
+    #[sqlx(try_from = "f64")]
+    pub unit_price: Decimal,
+    #[sqlx(try_from = "f64")]
+    pub line_total: Decimal,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
 #[sqlx(type_name = "varchar", rename_all = "PascalCase")]
 pub enum OrderStatus {
+    #[default]
     Pending,
     Processing,
     Shipped,
@@ -27,42 +87,1198 @@ pub enum OrderStatus {
     Cancelled,
 }
 
-impl Default for OrderStatus {
-    fn default() -> Self {
-        OrderStatus::Pending
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "Pending",
+            OrderStatus::Processing => "Processing",
+            OrderStatus::Shipped => "Shipped",
+            OrderStatus::Delivered => "Delivered",
+            OrderStatus::Cancelled => "Cancelled",
+        }
+    }
+
+    /// The order lifecycle. Every status change must follow one of these
+    /// edges; `Delivered` and `Cancelled` are terminal.
+    pub fn allowed_transitions(&self) -> &'static [OrderStatus] {
+        match self {
+            OrderStatus::Pending => &[OrderStatus::Processing, OrderStatus::Cancelled],
+            OrderStatus::Processing => &[OrderStatus::Shipped, OrderStatus::Cancelled],
+            OrderStatus::Shipped => &[OrderStatus::Delivered],
+            OrderStatus::Delivered => &[],
+            OrderStatus::Cancelled => &[],
+        }
+    }
+
+    /// Staying in the same status is always allowed so that clients can
+    /// resend an unchanged `status` alongside other fields.
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        *self == next || self.allowed_transitions().contains(&next)
+    }
+}
+
+impl std::fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum OrderEventType {
+    Created,
+    Updated,
+    StatusChanged,
+    Deleted,
+}
+
+impl OrderEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderEventType::Created => "created",
+            OrderEventType::Updated => "updated",
+            OrderEventType::StatusChanged => "status_changed",
+            OrderEventType::Deleted => "deleted",
+        }
+    }
+}
+
+/// One entry in an order's audit trail. `old_values`/`new_values` hold only
+/// the fields that changed; creates have no old values and deletes no new ones.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct OrderEvent {
+    pub id: i64,
+    pub order_id: i32,
+    pub event_type: OrderEventType,
+    pub actor: String,
+    #[schema(value_type = Option<Object>)]
+    pub old_values: Option<Json<serde_json::Value>>,
+    #[schema(value_type = Option<Object>)]
+    pub new_values: Option<Json<serde_json::Value>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters for `GET /api/orders/stream`. An event matches when the order
+/// had the status or customer before or after the change, so subscribers
+/// also hear about orders leaving their filter. `last_event_id` is for
+/// clients that cannot send the `Last-Event-ID` header.
+#[derive(Debug, Clone, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OrderStreamQuery {
+    pub status: Option<OrderStatus>,
+
+    pub customer_id: Option<i32>,
+
+    #[validate(range(min = 0, message = "Last event id must not be negative"))]
+    #[param(minimum = 0)]
+    pub last_event_id: Option<i64>,
+}
+
+/// An audit event as pushed on the order stream, with the order as it is
+/// now. `order` is `None` once the order has been purged.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OrderStreamEvent {
+    #[serde(flatten)]
+    pub event: OrderEvent,
+    pub order: Option<Order>,
+}
+
+/// A line on an order request. The product is looked up by `sku`, or by
+/// `product_name` for clients that predate the catalog. `unit_price` is only
+/// honoured when price overrides are enabled; otherwise it must match the
+/// catalog price.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_order_item"))]
+pub struct OrderItemRequest {
+    #[validate(length(min = 1, max = 64, message = "SKU must be between 1 and 64 characters"))]
+    #[schema(min_length = 1, max_length = 64)]
+    pub sku: Option<String>,
+
+    #[validate(length(min = 1, max = 100, message = "Product name must be between 1 and 100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub product_name: Option<String>,
+
+    #[validate(range(min = 1, message = "Quantity must be at least 1"))]
+    #[schema(minimum = 1)]
+    pub quantity: i32,
+
+    #[validate(custom(function = "positive_decimal", message = "Unit price must be greater than 0"))]
+    #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
+    pub unit_price: Option<Decimal>,
+}
+
+impl OrderItemRequest {
+    pub fn product_ref(&self) -> ProductRef {
+        match &self.sku {
+            Some(sku) => ProductRef::Sku(sku.clone()),
+            None => ProductRef::Name(self.product_name.clone().unwrap_or_default()),
+        }
+    }
+}
+
+fn validate_order_item(item: &OrderItemRequest) -> Result<(), ValidationError> {
+    if item.sku.is_none() && item.product_name.is_none() {
+        return Err(schema_error("product", "Either sku or product_name is required"));
+    }
+    Ok(())
+}
+
+/// How an order line names its product.
+#[derive(Debug, Clone)]
+pub enum ProductRef {
+    Sku(String),
+    Name(String),
+}
+
+impl std::fmt::Display for ProductRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProductRef::Sku(sku) => write!(f, "SKU '{}'", sku),
+            ProductRef::Name(name) => write!(f, "product '{}'", name),
+        }
     }
 }
 
-#[derive(Debug, Deserialize, Validate)]
+/// The customer is given by `customer_id`, or by `customer_name` which is
+/// matched case-insensitively and created if it does not exist yet.
+/// Either `items` or the single-item fields (`sku`/`product_name`,
+/// `quantity`, `unit_price`) must be given, but not both.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_create_order"))]
 pub struct CreateOrderRequest {
+    pub customer_id: Option<i32>,
+
     #[validate(length(min = 1, max = 100, message = "Customer name must be between 1 and 100 characters"))]
-    pub customer_name: String,
+    #[schema(min_length = 1, max_length = 100)]
+    pub customer_name: Option<String>,
+
+    #[validate(length(min = 1, max = 64, message = "SKU must be between 1 and 64 characters"))]
+    #[schema(min_length = 1, max_length = 64)]
+    pub sku: Option<String>,
     
     #[validate(length(min = 1, max = 100, message = "Product name must be between 1 and 100 characters"))]
-    pub product_name: String,
+    #[schema(min_length = 1, max_length = 100)]
+    pub product_name: Option<String>,
     
     #[validate(range(min = 1, message = "Quantity must be at least 1"))]
-    pub quantity: i32,
+    #[schema(minimum = 1)]
+    pub quantity: Option<i32>,
     
-    #[validate(range(min = 0.01, message = "Unit price must be greater than 0"))]
-    pub unit_price: Decimal,
+    #[validate(custom(function = "positive_decimal", message = "Unit price must be greater than 0"))]
+    #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
+    pub unit_price: Option<Decimal>,
+
+    #[validate]
+    #[schema(min_items = 1, max_items = 100)]
+    pub items: Option<Vec<OrderItemRequest>>,
+}
+
+impl CreateOrderRequest {
+    /// The requested lines of a validated request, turning the single-item
+    /// shape into a one-line order.
+    pub fn line_items(&self) -> Vec<OrderItemRequest> {
+        match &self.items {
+            Some(items) => items.clone(),
+            None => vec![OrderItemRequest {
+                sku: self.sku.clone(),
+                product_name: self.product_name.clone(),
+                quantity: self.quantity.unwrap_or_default(),
+                unit_price: self.unit_price,
+            }],
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Validate)]
+fn validate_create_order(request: &CreateOrderRequest) -> Result<(), ValidationError> {
+    match (&request.customer_id, &request.customer_name) {
+        (None, None) => {
+            return Err(schema_error("customer", "Either customer_id or customer_name is required"));
+        }
+        (Some(_), Some(_)) => {
+            return Err(schema_error("customer", "Use either customer_id or customer_name, not both"));
+        }
+        _ => {}
+    }
+
+    let has_product = request.sku.is_some() || request.product_name.is_some();
+    let has_single_item_fields = has_product || request.quantity.is_some() || request.unit_price.is_some();
+
+    match &request.items {
+        Some(items) => {
+            if has_single_item_fields {
+                return Err(schema_error("line_items", "Use either items or sku/product_name/quantity/unit_price, not both"));
+            }
+            check_item_count(items.len())
+        }
+        None if has_product && request.quantity.is_some() => Ok(()),
+        None => Err(schema_error("line_items", "Either items or sku/product_name and quantity are required")),
+    }
+}
+
+/// Without `items`, the single-item fields edit the first line of the order.
+/// With `items`, every line is replaced.
+#[derive(Debug, Default, Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_update_order"))]
 pub struct UpdateOrderRequest {
+    pub customer_id: Option<i32>,
+
     #[validate(length(min = 1, max = 100, message = "Customer name must be between 1 and 100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
     pub customer_name: Option<String>,
+
+    #[validate(length(min = 1, max = 64, message = "SKU must be between 1 and 64 characters"))]
+    #[schema(min_length = 1, max_length = 64)]
+    pub sku: Option<String>,
     
     #[validate(length(min = 1, max = 100, message = "Product name must be between 1 and 100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
     pub product_name: Option<String>,
     
     #[validate(range(min = 1, message = "Quantity must be at least 1"))]
+    #[schema(minimum = 1)]
     pub quantity: Option<i32>,
     
-    #[validate(range(min = 0.01, message = "Unit price must be greater than 0"))]
+    #[validate(custom(function = "positive_decimal", message = "Unit price must be greater than 0"))]
+    #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
     pub unit_price: Option<Decimal>,
     
     pub status: Option<OrderStatus>,
+
+    #[validate]
+    #[schema(min_items = 1, max_items = 100)]
+    pub items: Option<Vec<OrderItemRequest>>,
+}
+
+impl UpdateOrderRequest {
+    /// True when the request edits the first line through the single-item fields.
+    pub fn has_single_item_fields(&self) -> bool {
+        self.sku.is_some() || self.product_name.is_some() || self.quantity.is_some() || self.unit_price.is_some()
+    }
+}
+
+fn validate_update_order(request: &UpdateOrderRequest) -> Result<(), ValidationError> {
+    if request.customer_id.is_some() && request.customer_name.is_some() {
+        return Err(schema_error("customer", "Use either customer_id or customer_name, not both"));
+    }
+
+    let Some(items) = &request.items else {
+        return Ok(());
+    };
+
+    if request.has_single_item_fields() {
+        return Err(schema_error("line_items", "Use either items or sku/product_name/quantity/unit_price, not both"));
+    }
+    check_item_count(items.len())
+}
+
+fn check_item_count(count: usize) -> Result<(), ValidationError> {
+    if count == 0 || count > MAX_ORDER_ITEMS {
+        return Err(schema_error("line_items", "An order must have between 1 and 100 items"));
+    }
+    Ok(())
+}
+
+/// `range` only works on primitive numbers, so prices are checked here.
+fn positive_decimal(value: &Decimal) -> Result<(), ValidationError> {
+    if *value <= Decimal::ZERO {
+        return Err(ValidationError::new("range"));
+    }
+    Ok(())
+}
+
+fn schema_error(code: &'static str, message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    error
+}
+
+/// A validated order ready to be written, with its customer and products
+/// resolved.
+#[derive(Debug, Clone)]
+pub struct NewOrder {
+    pub customer: OrderCustomer,
+    pub items: Vec<NewOrderItem>,
+}
+
+impl NewOrder {
+    pub fn total_amount(&self) -> Decimal {
+        self.items.iter().map(NewOrderItem::line_total).sum()
+    }
+}
+
+/// The customer an order is written for. A name seen for the first time is
+/// created in the same transaction as the order, so an order that fails
+/// leaves no customer behind.
+#[derive(Debug, Clone)]
+pub enum OrderCustomer {
+    Existing { id: i32, name: String },
+    New(String),
+}
+
+impl From<Customer> for OrderCustomer {
+    fn from(customer: Customer) -> Self {
+        Self::Existing { id: customer.id, name: customer.name }
+    }
+}
+
+/// A priced order line. `product_id` and `sku` are only missing on lines
+/// written before the product catalog existed.
+#[derive(Debug, Clone)]
+pub struct NewOrderItem {
+    pub product_id: Option<i32>,
+    pub sku: Option<String>,
+    pub product_name: String,
+    pub quantity: i32,
+    pub unit_price: Decimal,
+}
+
+impl NewOrderItem {
+    pub fn line_total(&self) -> Decimal {
+        self.unit_price * Decimal::from(self.quantity)
+    }
+}
+
+impl From<&OrderItem> for NewOrderItem {
+    fn from(item: &OrderItem) -> Self {
+        Self {
+            product_id: item.product_id,
+            sku: item.sku.clone(),
+            product_name: item.product_name.clone(),
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+        }
+    }
+}
+
+/// A validated set of changes to apply to an existing order. `None` leaves
+/// the field as it is; `items` replaces every line.
+#[derive(Debug, Clone, Default)]
+pub struct OrderChanges {
+    pub customer: Option<OrderCustomer>,
+    pub status: Option<OrderStatus>,
+    pub items: Option<Vec<NewOrderItem>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderSortField {
+    Id,
+    CustomerName,
+    ProductName,
+    TotalAmount,
+    OrderDate,
+    Status,
+    #[default]
+    CreatedAt,
+}
+
+impl OrderSortField {
+    pub fn column(&self) -> &'static str {
+        match self {
+            OrderSortField::Id => "id",
+            OrderSortField::CustomerName => "customer_name",
+            OrderSortField::ProductName => "product_name",
+            OrderSortField::TotalAmount => "total_amount",
+            OrderSortField::OrderDate => "order_date",
+            OrderSortField::Status => "status",
+            OrderSortField::CreatedAt => "created_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 50;
+pub const MAX_PAGE_LIMIT: i64 = 500;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OrderQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<OrderStatus>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer_id: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 1, max = 100, message = "Customer name must be between 1 and 100 characters"))]
+    #[param(min_length = 1, max_length = 100)]
+    pub customer_name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 1, max = 100, message = "Product name must be between 1 and 100 characters"))]
+    #[param(min_length = 1, max_length = 100)]
+    pub product_name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_date_from: Option<DateTime<Utc>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_date_to: Option<DateTime<Utc>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<Decimal>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_amount: Option<Decimal>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<OrderSortField>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 500, message = "Limit must be between 1 and 500"))]
+    #[param(minimum = 1, maximum = 500)]
+    pub limit: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 0, message = "Offset must not be negative"))]
+    #[param(minimum = 0)]
+    pub offset: Option<i64>,
+
+    /// Also return soft-deleted orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_deleted: Option<bool>,
+}
+
+impl OrderQuery {
+    pub fn include_deleted(&self) -> bool {
+        self.include_deleted.unwrap_or(false)
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+
+    /// Returns a copy of this query pointing at a different page.
+    pub fn with_offset(&self, offset: i64) -> Self {
+        Self {
+            limit: Some(self.limit()),
+            offset: Some(offset),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrderPage {
+    pub data: Vec<Order>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl OrderPage {
+    pub fn new(data: Vec<Order>, total: i64, query: &OrderQuery, path: &str) -> Self {
+        let limit = query.limit();
+        let offset = query.offset();
+
+        let link = |offset: i64| {
+            let params = serde_urlencoded::to_string(query.with_offset(offset)).unwrap_or_default();
+            format!("{}?{}", path, params)
+        };
+
+        let next = (offset + limit < total).then(|| link(offset + limit));
+        let prev = (offset > 0).then(|| link((offset - limit).max(0)));
+
+        Self {
+            data,
+            total,
+            limit,
+            offset,
+            next,
+            prev,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct Customer {
+    pub id: i32,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateCustomerRequest {
+    #[validate(length(min = 1, max = 100, message = "Customer name must be between 1 and 100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+
+    #[validate(email(message = "Email must be a valid email address"))]
+    #[schema(format = Email)]
+    pub email: Option<String>,
+
+    #[validate(length(min = 1, max = 30, message = "Phone must be between 1 and 30 characters"))]
+    #[schema(min_length = 1, max_length = 30)]
+    pub phone: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateCustomerRequest {
+    #[validate(length(min = 1, max = 100, message = "Customer name must be between 1 and 100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: Option<String>,
+
+    #[validate(email(message = "Email must be a valid email address"))]
+    #[schema(format = Email)]
+    pub email: Option<String>,
+
+    #[validate(length(min = 1, max = 30, message = "Phone must be between 1 and 30 characters"))]
+    #[schema(min_length = 1, max_length = 30)]
+    pub phone: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CustomerQuery {
+    #[validate(length(min = 1, max = 100, message = "Customer name must be between 1 and 100 characters"))]
+    #[param(min_length = 1, max_length = 100)]
+    pub name: Option<String>,
+
+    #[validate(range(min = 1, max = 500, message = "Limit must be between 1 and 500"))]
+    #[param(minimum = 1, maximum = 500)]
+    pub limit: Option<i64>,
+
+    #[validate(range(min = 0, message = "Offset must not be negative"))]
+    #[param(minimum = 0)]
+    pub offset: Option<i64>,
+}
+
+impl CustomerQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct Product {
+    pub id: i32,
+    pub sku: String,
+    pub name: String,
+    #[sqlx(try_from = "f64")]
+    pub list_price: Decimal,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateProductRequest {
+    #[validate(length(min = 1, max = 64, message = "SKU must be between 1 and 64 characters"))]
+    #[schema(min_length = 1, max_length = 64)]
+    pub sku: String,
+
+    #[validate(length(min = 1, max = 100, message = "Product name must be between 1 and 100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+
+    #[validate(custom(function = "positive_decimal", message = "List price must be greater than 0"))]
+    #[schema(value_type = f64, exclusive_minimum = 0)]
+    pub list_price: Decimal,
+
+    pub active: Option<bool>,
+
+    /// Opening stock level, zero when omitted
+    #[validate(range(min = 0, message = "Stock on hand must not be negative"))]
+    #[schema(minimum = 0)]
+    pub stock_on_hand: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateProductRequest {
+    #[validate(length(min = 1, max = 100, message = "Product name must be between 1 and 100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: Option<String>,
+
+    #[validate(custom(function = "positive_decimal", message = "List price must be greater than 0"))]
+    #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
+    pub list_price: Option<Decimal>,
+
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProductQuery {
+    pub active: Option<bool>,
+
+    #[validate(range(min = 1, max = 500, message = "Limit must be between 1 and 500"))]
+    #[param(minimum = 1, maximum = 500)]
+    pub limit: Option<i64>,
+
+    #[validate(range(min = 0, message = "Offset must not be negative"))]
+    #[param(minimum = 0)]
+    pub offset: Option<i64>,
+}
+
+impl ProductQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+/// How a bulk request treats a failing item: `all_or_nothing` rolls back the
+/// whole batch, `per_item` keeps the items that succeeded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    #[default]
+    AllOrNothing,
+    PerItem,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct BulkCreateRequest {
+    #[serde(default)]
+    pub mode: BulkMode,
+
+    #[validate(length(min = 1, max = 500, message = "A bulk request must have between 1 and 500 items"))]
+    #[schema(min_items = 1, max_items = 500)]
+    pub orders: Vec<CreateOrderRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BulkUpdateItem {
+    pub id: i32,
+    /// Expected order version, checked like `If-Match`
+    pub version: Option<i64>,
+    pub changes: UpdateOrderRequest,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct BulkUpdateRequest {
+    #[serde(default)]
+    pub mode: BulkMode,
+
+    #[validate(length(min = 1, max = 500, message = "A bulk request must have between 1 and 500 items"))]
+    #[schema(min_items = 1, max_items = 500)]
+    pub updates: Vec<BulkUpdateItem>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct BulkDeleteRequest {
+    #[serde(default)]
+    pub mode: BulkMode,
+
+    #[validate(length(min = 1, max = 500, message = "A bulk request must have between 1 and 500 items"))]
+    #[schema(min_items = 1, max_items = 500)]
+    pub ids: Vec<i32>,
+}
+
+/// Response body for the bulk endpoints, one result per requested item.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkResponse<T> {
+    pub mode: BulkMode,
+    pub committed: bool,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BulkItemResult<T>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkItemResult<T> {
+    pub index: usize,
+    /// HTTP status the item would have had as a single request
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One row of an order CSV export: a line item with its order's fields
+/// repeated, so every row stands on its own in a spreadsheet. Free-text
+/// cells go through `spreadsheet_text`.
+#[derive(Debug, Serialize)]
+pub struct OrderCsvLine<'a> {
+    pub order_id: i32,
+    pub order_date: DateTime<Utc>,
+    pub status: OrderStatus,
+    pub customer_id: i32,
+    pub customer_name: Cow<'a, str>,
+    pub line_number: i32,
+    pub sku: Option<Cow<'a, str>>,
+    pub product_name: Cow<'a, str>,
+    pub quantity: i32,
+    pub unit_price: Decimal,
+    pub line_total: Decimal,
+    pub order_total: Decimal,
+}
+
+impl<'a> OrderCsvLine<'a> {
+    pub fn new(order: &'a Order, item: &'a OrderItem) -> Self {
+        Self {
+            order_id: order.id,
+            order_date: order.order_date,
+            status: order.status,
+            customer_id: order.customer_id,
+            customer_name: spreadsheet_text(&order.customer_name),
+            line_number: item.line_number,
+            sku: item.sku.as_deref().map(spreadsheet_text),
+            product_name: spreadsheet_text(&item.product_name),
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+            line_total: item.line_total,
+            order_total: order.total_amount,
+        }
+    }
+}
+
+/// Spreadsheets run a cell starting with `=`, `+`, `-`, `@`, tab or carriage
+/// return as a formula, so such text is prefixed with `'` to keep it text.
+fn spreadsheet_text(value: &str) -> Cow<'_, str> {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        Cow::Owned(format!("'{}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// One row of an order CSV import, creating a single-item order. Columns
+/// may appear in any order and unused ones may be left out; the row must
+/// pass the same rules as a `CreateOrderRequest`.
+#[derive(Debug, Deserialize)]
+pub struct OrderCsvRow {
+    pub customer_id: Option<i32>,
+    pub customer_name: Option<String>,
+    pub sku: Option<String>,
+    pub product_name: Option<String>,
+    pub quantity: Option<i32>,
+    pub unit_price: Option<Decimal>,
+}
+
+impl From<OrderCsvRow> for CreateOrderRequest {
+    fn from(row: OrderCsvRow) -> Self {
+        Self {
+            customer_id: row.customer_id,
+            customer_name: row.customer_name,
+            sku: row.sku,
+            product_name: row.product_name,
+            quantity: row.quantity,
+            unit_price: row.unit_price,
+            items: None,
+        }
+    }
+}
+
+/// Response body for a CSV import, one result per data row.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportRowResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportRowResult {
+    /// Line number in the uploaded file; the header is line 1
+    pub line: u64,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// How revenue reports group orders. Weeks start on Monday and are labelled
+/// with that date; days and months use `YYYY-MM-DD` and `YYYY-MM`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportInterval {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl ReportInterval {
+    /// SQL expression giving the period label for `orders.order_date`.
+    pub fn period_expression(&self) -> &'static str {
+        match self {
+            ReportInterval::Day => "substr(order_date, 1, 10)",
+            ReportInterval::Week => "date(substr(order_date, 1, 10), 'weekday 0', '-6 days')",
+            ReportInterval::Month => "substr(order_date, 1, 7)",
+        }
+    }
+}
+
+/// Query string shared by the `/api/reports/*` endpoints. `from` and `to`
+/// bound `order_date` inclusively; `interval` applies to the revenue report
+/// and `limit` to the top-N reports.
+#[derive(Debug, Clone, Default, Deserialize, Validate, IntoParams)]
+#[validate(schema(function = "validate_report_query"))]
+#[into_params(parameter_in = Query)]
+pub struct ReportQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub interval: Option<ReportInterval>,
+
+    #[validate(range(min = 1, max = 100, message = "Limit must be between 1 and 100"))]
+    #[param(minimum = 1, maximum = 100)]
+    pub limit: Option<i64>,
+}
+
+impl ReportQuery {
+    pub fn interval(&self) -> ReportInterval {
+        self.interval.unwrap_or_default()
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(10).clamp(1, 100)
+    }
+}
+
+fn validate_report_query(query: &ReportQuery) -> Result<(), ValidationError> {
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(schema_error("date_range", "from must not be after to"));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct RevenuePoint {
+    pub period: String,
+    pub order_count: i64,
+    #[sqlx(try_from = "f64")]
+    pub revenue: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct CustomerRevenue {
+    pub customer_id: i32,
+    pub customer_name: String,
+    pub order_count: i64,
+    #[sqlx(try_from = "f64")]
+    pub revenue: Decimal,
+}
+
+/// Products are grouped by catalog id; lines that predate the catalog are
+/// grouped by name and have no `product_id` or `sku`.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct ProductRevenue {
+    pub product_id: Option<i32>,
+    pub sku: Option<String>,
+    pub product_name: String,
+    pub quantity: i64,
+    pub order_count: i64,
+    #[sqlx(try_from = "f64")]
+    pub revenue: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct StatusBreakdown {
+    pub status: OrderStatus,
+    pub order_count: i64,
+    #[sqlx(try_from = "f64")]
+    pub revenue: Decimal,
+}
+
+/// Query string for fetching a single order.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OrderLookup {
+    pub include_deleted: Option<bool>,
+}
+
+/// Query string for purging soft-deleted orders. Defaults to the configured
+/// retention period.
+#[derive(Debug, Clone, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PurgeQuery {
+    #[validate(range(min = 1, max = 36500, message = "Retention must be between 1 and 36500 days"))]
+    #[param(minimum = 1, maximum = 36500)]
+    pub older_than_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PurgeResult {
+    pub purged: u64,
+    pub deleted_before: DateTime<Utc>,
+}
+
+/// An `Idempotency-Key` to store alongside a new order. `fingerprint` is a
+/// hash of the request body, so a reused key can be told apart from a retry.
+/// Keys belong to `principal`, the id of the credential that sent them.
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey {
+    pub principal: String,
+    pub key: String,
+    pub fingerprint: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A stored key together with the order it created, as first returned.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct IdempotencyRecord {
+    pub principal: String,
+    pub key: String,
+    pub fingerprint: String,
+    pub order_id: i32,
+    pub response: Json<Order>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Stock for one product. `reserved` is held by open (pending or processing)
+/// orders; `available` is what new orders can still take.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct StockLevel {
+    pub product_id: i32,
+    pub sku: String,
+    pub on_hand: i64,
+    pub reserved: i64,
+    pub available: i64,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateStockRequest {
+    #[validate(range(min = 0, message = "Stock on hand must not be negative"))]
+    #[schema(minimum = 0)]
+    pub on_hand: i64,
+}
+
+/// A partner subscription to order events. Deliveries carry
+/// `X-Webhook-Timestamp` and an `X-Webhook-Signature` computed with
+/// `secret`, which is write-only, over `<timestamp>.<body>`.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    #[schema(value_type = Vec<OrderEventType>)]
+    pub event_types: Json<Vec<OrderEventType>>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_create_webhook"))]
+pub struct CreateWebhookRequest {
+    #[validate(url(message = "URL must be a valid URL"), length(max = 2048, message = "URL must be at most 2048 characters"))]
+    #[schema(format = "uri", max_length = 2048)]
+    pub url: String,
+
+    #[validate(length(min = 16, max = 256, message = "Secret must be between 16 and 256 characters"))]
+    #[schema(min_length = 16, max_length = 256)]
+    pub secret: String,
+
+    #[validate(length(min = 1, message = "At least one event type is required"))]
+    #[schema(min_items = 1)]
+    pub event_types: Vec<OrderEventType>,
+
+    pub active: Option<bool>,
+}
+
+fn validate_create_webhook(request: &CreateWebhookRequest) -> Result<(), ValidationError> {
+    validate_webhook_url(&request.url)
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_update_webhook"))]
+pub struct UpdateWebhookRequest {
+    #[validate(url(message = "URL must be a valid URL"), length(max = 2048, message = "URL must be at most 2048 characters"))]
+    #[schema(format = "uri", max_length = 2048)]
+    pub url: Option<String>,
+
+    #[validate(length(min = 16, max = 256, message = "Secret must be between 16 and 256 characters"))]
+    #[schema(min_length = 16, max_length = 256)]
+    pub secret: Option<String>,
+
+    #[validate(length(min = 1, message = "At least one event type is required"))]
+    #[schema(min_items = 1)]
+    pub event_types: Option<Vec<OrderEventType>>,
+
+    pub active: Option<bool>,
+}
+
+fn validate_update_webhook(request: &UpdateWebhookRequest) -> Result<(), ValidationError> {
+    match &request.url {
+        Some(url) => validate_webhook_url(url),
+        None => Ok(()),
+    }
+}
+
+fn validate_webhook_url(url: &str) -> Result<(), ValidationError> {
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(schema_error("url", "URL must use http or https"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryState {
+    Pending,
+    Delivered,
+    /// Ran out of attempts; only a manual redelivery sends it again
+    Failed,
+}
+
+/// One attempt record in a webhook's delivery log. A redelivery is a new
+/// row pointing at the delivery it repeats.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i32,
+    pub event_id: i64,
+    pub event_type: OrderEventType,
+    pub order_id: i32,
+    #[schema(value_type = Object)]
+    pub payload: Json<serde_json::Value>,
+    pub state: DeliveryState,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub response_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub redelivery_of: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A delivery that is due, with what the dispatcher needs to send it. The
+/// payload is kept as stored so the signature covers the exact bytes sent.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PendingDelivery {
+    pub id: i64,
+    pub webhook_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub event_type: OrderEventType,
+    pub payload: String,
+    pub attempts: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WebhookDeliveryQuery {
+    pub state: Option<DeliveryState>,
+
+    #[validate(range(min = 1, max = 500, message = "Limit must be between 1 and 500"))]
+    #[param(minimum = 1, maximum = 500)]
+    pub limit: Option<i64>,
+
+    #[validate(range(min = 0, message = "Offset must not be negative"))]
+    #[param(minimum = 0)]
+    pub offset: Option<i64>,
+}
+
+impl WebhookDeliveryQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+/// What an API key may do. `admin` covers everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub enum ApiScope {
+    #[serde(rename = "orders:read")]
+    OrdersRead,
+    #[serde(rename = "orders:write")]
+    OrdersWrite,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::OrdersRead => "orders:read",
+            ApiScope::OrdersWrite => "orders:write",
+            ApiScope::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "orders:read" => Ok(ApiScope::OrdersRead),
+            "orders:write" => Ok(ApiScope::OrdersWrite),
+            "admin" => Ok(ApiScope::Admin),
+            other => Err(format!("Unknown scope '{}'; expected orders:read, orders:write or admin", other)),
+        }
+    }
+}
+
+/// A client credential. The key itself is only shown once, when it is
+/// created; the database keeps its hash.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    pub prefix: String,
+    #[schema(value_type = Vec<ApiScope>)]
+    pub scopes: Json<Vec<ApiScope>>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateApiKeyRequest {
+    #[validate(length(min = 1, max = 100, message = "Name must be between 1 and 100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    #[schema(min_items = 1)]
+    pub scopes: Vec<ApiScope>,
+}
+
+/// Response to creating a key; `key` is not stored and cannot be shown again.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -72,4 +1288,28 @@ pub struct StatusReport {
     pub timestamp: DateTime<Utc>,
     pub details: Option<String>,
     pub order_id: Option<i32>,
+}
+
+/// A status report waiting in `status_outbox` for delivery.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub operation: String,
+    pub success: bool,
+    pub details: Option<String>,
+    pub order_id: Option<i32>,
+    pub reported_at: DateTime<Utc>,
+    pub attempts: i64,
+}
+
+impl OutboxEntry {
+    pub fn report(&self) -> StatusReport {
+        StatusReport {
+            operation: self.operation.clone(),
+            success: self.success,
+            timestamp: self.reported_at,
+            details: self.details.clone(),
+            order_id: self.order_id,
+        }
+    }
 }
\ No newline at end of file
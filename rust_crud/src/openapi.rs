@@ -0,0 +1,140 @@
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+use crate::errors::ErrorBody;
+use crate::handlers;
+use crate::models::OrderStatus;
+
+/// The OpenAPI document served on `/openapi.json`. Every handler routed in
+/// `main.rs` must be listed in `paths`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Order CRUD API",
+        description = "Orders, customers, products and reports. Send an API key or token as \
+                       `Authorization: Bearer <credentials>`; each route lists the scope it needs."
+    ),
+    paths(
+        handlers::get_orders,
+        handlers::create_order,
+        handlers::stream_orders,
+        handlers::export_orders_csv,
+        handlers::import_orders_csv,
+        handlers::bulk_create_orders,
+        handlers::bulk_update_orders,
+        handlers::bulk_delete_orders,
+        handlers::get_order,
+        handlers::update_order,
+        handlers::delete_order,
+        handlers::get_order_history,
+        handlers::restore_order,
+        handlers::process_order,
+        handlers::ship_order,
+        handlers::deliver_order,
+        handlers::cancel_order,
+        handlers::get_customers,
+        handlers::create_customer,
+        handlers::get_customer,
+        handlers::update_customer,
+        handlers::delete_customer,
+        handlers::get_customer_orders,
+        handlers::get_products,
+        handlers::create_product,
+        handlers::get_product,
+        handlers::update_product,
+        handlers::delete_product,
+        handlers::get_product_stock,
+        handlers::set_product_stock,
+        handlers::revenue_report,
+        handlers::top_customers_report,
+        handlers::top_products_report,
+        handlers::status_breakdown_report,
+        handlers::create_webhook,
+        handlers::get_webhooks,
+        handlers::get_webhook,
+        handlers::update_webhook,
+        handlers::delete_webhook,
+        handlers::get_webhook_deliveries,
+        handlers::redeliver_webhook,
+        handlers::purge_deleted_orders,
+        handlers::create_api_key,
+        handlers::get_api_keys,
+        handlers::revoke_api_key,
+        handlers::get_rate_limits,
+        handlers::health_check,
+        handlers::health_live,
+        handlers::health_ready,
+        handlers::get_metrics,
+    ),
+    components(schemas(OrderStatus, ErrorBody)),
+    modifiers(&CommonResponses),
+    tags(
+        (name = "orders", description = "Orders and their lifecycle; needs `orders:read` or `orders:write`"),
+        (name = "customers", description = "Customers; needs `orders:read` or `orders:write`"),
+        (name = "products", description = "Product catalog and stock; needs `orders:read` or `orders:write`"),
+        (name = "reports", description = "Sales reports; needs `orders:read`"),
+        (name = "webhooks", description = "Partner subscriptions to order events; needs `admin`"),
+        (name = "api-keys", description = "Client credentials; needs `admin`"),
+        (name = "admin", description = "Maintenance; needs `admin`"),
+        (name = "health", description = "Probes and metrics; no credentials needed"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Adds what every route shares rather than repeating it on each handler:
+/// the bearer scheme, the auth failures of `/api` routes and the rate limit
+/// response, which `/health` probes never get.
+struct CommonResponses;
+
+impl Modify for CommonResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("An API key (`ock_…`) or an HS256/RS256 JWT"))
+                    .build(),
+            ),
+        );
+
+        let common = [
+            ("Unauthorized", "Missing, invalid or revoked credentials"),
+            ("Forbidden", "The credentials lack the scope or role"),
+            ("TooManyRequests", "Rate limit exceeded; see `Retry-After`"),
+        ];
+        for (name, description) in common {
+            let response = ResponseBuilder::new()
+                .description(description)
+                .content(
+                    "application/json",
+                    ContentBuilder::new()
+                        .schema(Some(Ref::from_schema_name("ErrorBody")))
+                        .build(),
+                )
+                .build();
+            components.responses.insert(name.to_string(), response.into());
+        }
+
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                let responses = &mut operation.responses.responses;
+                if path.starts_with("/api/") {
+                    responses.insert("401".to_string(), Ref::from_response_name("Unauthorized").into());
+                    responses.insert("403".to_string(), Ref::from_response_name("Forbidden").into());
+                }
+                if !path.starts_with("/health") {
+                    responses.insert("429".to_string(), Ref::from_response_name("TooManyRequests").into());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use crate::database::DatabasePool;
+use crate::models::{OutboxEntry, StatusReport};
+use crate::repository::RepositoryError;
+
+const OUTBOX_COLUMNS: &str = "id, operation, success, details, order_id, reported_at, attempts";
+
+pub struct OutboxRepository {
+    pool: DatabasePool,
+}
+
+impl OutboxRepository {
+    pub fn new(pool: DatabasePool) -> Self {
+        Self { pool }
+    }
+
+    /// Queues a report for immediate delivery.
+    pub async fn enqueue(&self, report: &StatusReport) -> Result<i64, RepositoryError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO status_outbox (operation, success, details, order_id, reported_at, state, attempts, next_attempt_at)
+            VALUES (?, ?, ?, ?, ?, 'pending', 0, ?)
+            "#
+        )
+        .bind(&report.operation)
+        .bind(report.success)
+        .bind(&report.details)
+        .bind(report.order_id)
+        .bind(report.timestamp.to_rfc3339())
+        .bind(report.timestamp.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Pending reports whose next attempt is due, oldest first.
+    pub async fn find_due(&self, now: DateTime<Utc>, limit: i64) -> Result<Vec<OutboxEntry>, RepositoryError> {
+        let entries = sqlx::query_as::<_, OutboxEntry>(&format!(
+            "SELECT {} FROM status_outbox WHERE state = 'pending' AND next_attempt_at <= ? ORDER BY id LIMIT ?",
+            OUTBOX_COLUMNS
+        ))
+        .bind(now.to_rfc3339())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    pub async fn mark_delivered(&self, id: i64) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+            UPDATE status_outbox
+            SET state = 'delivered', attempts = attempts + 1, last_error = NULL, delivered_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Deletes reports delivered before `delivered_before`. Dead letters are
+    /// kept for inspection.
+    pub async fn purge_delivered(&self, delivered_before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        let result = sqlx::query("DELETE FROM status_outbox WHERE state = 'delivered' AND delivered_at < ?")
+            .bind(delivered_before.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Records a failed attempt. With `next_attempt_at` the report is retried
+    /// then; without it the report has run out of attempts and is moved to
+    /// the dead-letter state.
+    pub async fn mark_failed(
+        &self,
+        id: i64,
+        error: &str,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> Result<(), RepositoryError> {
+        let state = if next_attempt_at.is_some() { "pending" } else { "dead" };
+
+        sqlx::query(
+            r#"
+            UPDATE status_outbox
+            SET state = ?, attempts = attempts + 1, last_error = ?,
+                next_attempt_at = COALESCE(?, next_attempt_at)
+            WHERE id = ?
+            "#
+        )
+        .bind(state)
+        .bind(error)
+        .bind(next_attempt_at.map(|at| at.to_rfc3339()))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_pool;
+
+    fn report(operation: &str) -> StatusReport {
+        StatusReport {
+            operation: operation.to_string(),
+            success: true,
+            details: None,
+            order_id: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn purge_removes_only_old_delivered_reports() {
+        let pool = test_pool().await;
+        let outbox = OutboxRepository::new(pool.clone());
+
+        let old = outbox.enqueue(&report("old")).await.unwrap();
+        let recent = outbox.enqueue(&report("recent")).await.unwrap();
+        let dead = outbox.enqueue(&report("dead")).await.unwrap();
+        let pending = outbox.enqueue(&report("pending")).await.unwrap();
+
+        outbox.mark_delivered(old).await.unwrap();
+        outbox.mark_delivered(recent).await.unwrap();
+        outbox.mark_failed(dead, "gone", None).await.unwrap();
+        let long_ago = (Utc::now() - chrono::Duration::days(30)).to_rfc3339();
+        sqlx::query("UPDATE status_outbox SET delivered_at = ? WHERE id = ?")
+            .bind(&long_ago)
+            .bind(old)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let purged = outbox.purge_delivered(Utc::now() - chrono::Duration::days(7)).await.unwrap();
+        assert_eq!(purged, 1);
+
+        let remaining: Vec<i64> = sqlx::query_scalar("SELECT id FROM status_outbox ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![recent, dead, pending]);
+    }
+}
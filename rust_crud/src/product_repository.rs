@@ -0,0 +1,246 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{QueryBuilder, Sqlite};
+use crate::database::{amount, DatabasePool};
+use crate::models::{Product, CreateProductRequest, UpdateProductRequest, ProductQuery, StockLevel};
+use crate::repository::RepositoryError;
+
+const PRODUCT_COLUMNS: &str = "id, sku, name, list_price, active, created_at, updated_at";
+const STOCK_SELECT: &str = r#"
+    SELECT i.product_id, p.sku, i.on_hand, i.reserved, i.on_hand - i.reserved AS available
+    FROM inventory i
+    JOIN products p ON p.id = i.product_id
+"#;
+
+/// Product catalog and stock used by `ProductService` and `OrderService`.
+/// `SqliteProductRepository` is the production implementation.
+#[async_trait]
+pub trait ProductRepository: Send + Sync {
+    /// Creates the product together with its stock row.
+    async fn create(&self, request: CreateProductRequest) -> Result<Product, RepositoryError>;
+
+    async fn find_all(&self, query: &ProductQuery) -> Result<Vec<Product>, RepositoryError>;
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<Product>, RepositoryError>;
+
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, RepositoryError>;
+
+    /// Names are not unique, so this returns every match and leaves the
+    /// caller to decide what to do with more than one.
+    async fn find_by_name(&self, name: &str) -> Result<Vec<Product>, RepositoryError>;
+
+    async fn update(&self, id: i32, request: UpdateProductRequest) -> Result<Product, RepositoryError>;
+
+    async fn delete(&self, id: i32) -> Result<bool, RepositoryError>;
+
+    async fn get_stock(&self, id: i32) -> Result<Option<StockLevel>, RepositoryError>;
+
+    /// Sets the on-hand count after a stock take or delivery. The count may
+    /// not drop below what open orders have already reserved; that case is
+    /// reported as `InsufficientStock` with the reservation as `requested`.
+    async fn set_stock(&self, id: i32, on_hand: i64) -> Result<StockLevel, RepositoryError>;
+
+    async fn count_order_items(&self, id: i32) -> Result<i64, RepositoryError>;
+}
+
+pub struct SqliteProductRepository {
+    pool: DatabasePool,
+}
+
+impl SqliteProductRepository {
+    pub fn new(pool: DatabasePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ProductRepository for SqliteProductRepository {
+    async fn create(&self, request: CreateProductRequest) -> Result<Product, RepositoryError> {
+        let active = request.active.unwrap_or(true);
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO products (sku, name, list_price, active, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&request.sku)
+        .bind(&request.name)
+        .bind(amount(request.list_price))
+        .bind(active)
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        let id = result.last_insert_rowid() as i32;
+
+        sqlx::query("INSERT INTO inventory (product_id, on_hand, reserved, updated_at) VALUES (?, ?, 0, ?)")
+            .bind(id)
+            .bind(request.stock_on_hand.unwrap_or(0))
+            .bind(now.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(Product {
+            id,
+            sku: request.sku,
+            name: request.name,
+            list_price: request.list_price,
+            active,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    async fn find_all(&self, query: &ProductQuery) -> Result<Vec<Product>, RepositoryError> {
+        let mut select = QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM products", PRODUCT_COLUMNS));
+        if let Some(active) = query.active {
+            select.push(" WHERE active = ").push_bind(active);
+        }
+        select.push(" ORDER BY sku");
+        select.push(" LIMIT ").push_bind(query.limit());
+        select.push(" OFFSET ").push_bind(query.offset());
+
+        let rows = select
+            .build_query_as::<Product>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows)
+    }
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<Product>, RepositoryError> {
+        let row = sqlx::query_as::<_, Product>(
+            &format!("SELECT {} FROM products WHERE id = ?", PRODUCT_COLUMNS)
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, RepositoryError> {
+        let row = sqlx::query_as::<_, Product>(
+            &format!("SELECT {} FROM products WHERE sku = ?", PRODUCT_COLUMNS)
+        )
+        .bind(sku)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn find_by_name(&self, name: &str) -> Result<Vec<Product>, RepositoryError> {
+        let rows = sqlx::query_as::<_, Product>(
+            &format!("SELECT {} FROM products WHERE name = ? ORDER BY sku", PRODUCT_COLUMNS)
+        )
+        .bind(name)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn update(&self, id: i32, request: UpdateProductRequest) -> Result<Product, RepositoryError> {
+        let current = self.find_by_id(id).await?
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+
+        let name = request.name.unwrap_or(current.name);
+        let list_price = request.list_price.unwrap_or(current.list_price);
+        let active = request.active.unwrap_or(current.active);
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE products
+            SET name = ?, list_price = ?, active = ?, updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(&name)
+        .bind(amount(list_price))
+        .bind(active)
+        .bind(now.to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Product {
+            id,
+            sku: current.sku,
+            name,
+            list_price,
+            active,
+            created_at: current.created_at,
+            updated_at: now,
+        })
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, RepositoryError> {
+        let result = sqlx::query("DELETE FROM products WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_stock(&self, id: i32) -> Result<Option<StockLevel>, RepositoryError> {
+        let row = sqlx::query_as::<_, StockLevel>(&format!("{} WHERE i.product_id = ?", STOCK_SELECT))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row)
+    }
+
+    async fn set_stock(&self, id: i32, on_hand: i64) -> Result<StockLevel, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE inventory
+            SET on_hand = ?, updated_at = ?
+            WHERE product_id = ? AND reserved <= ?
+            "#
+        )
+        .bind(on_hand)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .bind(on_hand)
+        .execute(&mut *tx)
+        .await?;
+
+        let stock = sqlx::query_as::<_, StockLevel>(&format!("{} WHERE i.product_id = ?", STOCK_SELECT))
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::InsufficientStock {
+                product_id: id,
+                requested: stock.reserved,
+                available: on_hand,
+            });
+        }
+
+        tx.commit().await?;
+        Ok(stock)
+    }
+
+    async fn count_order_items(&self, id: i32) -> Result<i64, RepositoryError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM order_items WHERE product_id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,326 @@
+use std::sync::Arc;
+use validator::Validate;
+use crate::product_repository::ProductRepository;
+use crate::models::{Product, CreateProductRequest, UpdateProductRequest, ProductQuery, StockLevel, UpdateStockRequest};
+use crate::repository::RepositoryError;
+use crate::service::ServiceError;
+use crate::status_reporter::StatusReporter;
+
+pub struct ProductService {
+    repository: Arc<dyn ProductRepository>,
+    status_reporter: Arc<dyn StatusReporter>,
+}
+
+impl ProductService {
+    pub fn new(repository: Arc<dyn ProductRepository>, status_reporter: Arc<dyn StatusReporter>) -> Self {
+        Self {
+            repository,
+            status_reporter,
+        }
+    }
+
+    pub async fn create_product(&self, request: CreateProductRequest) -> Result<Product, ServiceError> {
+        // Validate the request
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("create_product", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let sku = request.sku.clone();
+        match self.repository.create(request).await {
+            Ok(product) => {
+                self.status_reporter
+                    .report_success("create_product", None)
+                    .await;
+                Ok(product)
+            }
+            Err(e) if e.is_unique_violation() => {
+                let error = ServiceError::DuplicateProduct { sku };
+                self.status_reporter
+                    .report_failure("create_product", &error.to_string(), None)
+                    .await;
+                Err(error)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to create product: {}", e);
+                self.status_reporter
+                    .report_failure("create_product", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_products(&self, query: &ProductQuery) -> Result<Vec<Product>, ServiceError> {
+        // Validate the query
+        if let Err(validation_errors) = query.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("get_products", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        match self.repository.find_all(query).await {
+            Ok(products) => {
+                self.status_reporter
+                    .report_success("get_products", None)
+                    .await;
+                Ok(products)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get products: {}", e);
+                self.status_reporter
+                    .report_failure("get_products", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_product(&self, id: i32) -> Result<Product, ServiceError> {
+        match self.repository.find_by_id(id).await {
+            Ok(Some(product)) => {
+                self.status_reporter
+                    .report_success("get_product", None)
+                    .await;
+                Ok(product)
+            }
+            Ok(None) => {
+                let error_msg = format!("Product not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("get_product", &error_msg, None)
+                    .await;
+                Err(ServiceError::ProductNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get product {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("get_product", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn update_product(&self, id: i32, request: UpdateProductRequest) -> Result<Product, ServiceError> {
+        // Validate the request
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("update_product", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        match self.repository.update(id, request).await {
+            Ok(product) => {
+                self.status_reporter
+                    .report_success("update_product", None)
+                    .await;
+                Ok(product)
+            }
+            Err(RepositoryError::Database(sqlx::Error::RowNotFound)) => {
+                let error_msg = format!("Product not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("update_product", &error_msg, None)
+                    .await;
+                Err(ServiceError::ProductNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to update product {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("update_product", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// Products that appear on orders cannot be deleted; deactivate them instead.
+    pub async fn delete_product(&self, id: i32) -> Result<(), ServiceError> {
+        let item_count = match self.repository.count_order_items(id).await {
+            Ok(count) => count,
+            Err(e) => {
+                let error_msg = format!("Failed to delete product {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("delete_product", &error_msg, None)
+                    .await;
+                return Err(ServiceError::Repository(e));
+            }
+        };
+
+        if item_count > 0 {
+            let error = ServiceError::ProductInUse { id };
+            self.status_reporter
+                .report_failure("delete_product", &error.to_string(), None)
+                .await;
+            return Err(error);
+        }
+
+        match self.repository.delete(id).await {
+            Ok(true) => {
+                self.status_reporter
+                    .report_success("delete_product", None)
+                    .await;
+                Ok(())
+            }
+            Ok(false) => {
+                let error_msg = format!("Product not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("delete_product", &error_msg, None)
+                    .await;
+                Err(ServiceError::ProductNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to delete product {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("delete_product", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_stock(&self, id: i32) -> Result<StockLevel, ServiceError> {
+        match self.repository.get_stock(id).await {
+            Ok(Some(stock)) => {
+                self.status_reporter
+                    .report_success("get_stock", None)
+                    .await;
+                Ok(stock)
+            }
+            Ok(None) => {
+                let error_msg = format!("Product not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("get_stock", &error_msg, None)
+                    .await;
+                Err(ServiceError::ProductNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get stock for product {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("get_stock", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// On-hand stock cannot be set below what open orders have reserved.
+    pub async fn set_stock(&self, id: i32, request: UpdateStockRequest) -> Result<StockLevel, ServiceError> {
+        // Validate the request
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("set_stock", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        match self.repository.set_stock(id, request.on_hand).await {
+            Ok(stock) => {
+                self.status_reporter
+                    .report_success("set_stock", None)
+                    .await;
+                Ok(stock)
+            }
+            Err(RepositoryError::Database(sqlx::Error::RowNotFound)) => {
+                let error_msg = format!("Product not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("set_stock", &error_msg, None)
+                    .await;
+                Err(ServiceError::ProductNotFound { id })
+            }
+            Err(RepositoryError::InsufficientStock { requested: reserved, .. }) => {
+                let error = ServiceError::StockBelowReserved { product_id: id, on_hand: request.on_hand, reserved };
+                self.status_reporter
+                    .report_failure("set_stock", &error.to_string(), None)
+                    .await;
+                Err(error)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to set stock for product {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("set_stock", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use rust_decimal::Decimal;
+    use crate::customer_repository::SqliteCustomerRepository;
+    use crate::database::test_pool;
+    use crate::memory_repository::InMemoryStatusReporter;
+    use crate::models::{CreateOrderRequest, OrderStatus};
+    use crate::product_repository::SqliteProductRepository;
+    use crate::repository::SqliteOrderRepository;
+    use crate::service::OrderService;
+
+    #[tokio::test]
+    async fn open_orders_reserve_stock_until_cancelled() {
+        let pool = test_pool().await;
+        let reporter = Arc::new(InMemoryStatusReporter::new());
+        let products: Arc<dyn ProductRepository> = Arc::new(SqliteProductRepository::new(pool.clone()));
+        let product_service = ProductService::new(products.clone(), reporter.clone());
+        let order_service = OrderService::new(
+            Arc::new(SqliteOrderRepository::new(pool.clone())),
+            Arc::new(SqliteCustomerRepository::new(pool.clone())),
+            products,
+            reporter,
+            false,
+            Duration::from_secs(3600),
+            Duration::from_secs(3600),
+        );
+
+        let product = product_service
+            .create_product(CreateProductRequest {
+                sku: "WIDGET".to_string(),
+                name: "Widget".to_string(),
+                list_price: Decimal::new(250, 2),
+                active: None,
+                stock_on_hand: Some(10),
+            })
+            .await
+            .unwrap();
+
+        let request = |quantity| CreateOrderRequest {
+            customer_id: None,
+            customer_name: Some("Acme".to_string()),
+            sku: Some("WIDGET".to_string()),
+            product_name: None,
+            quantity: Some(quantity),
+            unit_price: None,
+            items: None,
+        };
+
+        let (order, _) = order_service.create_order(request(4), None, "client", "test").await.unwrap();
+        let stock = product_service.get_stock(product.id).await.unwrap();
+        assert_eq!((stock.on_hand, stock.reserved, stock.available), (10, 4, 6));
+
+        let error = order_service.create_order(request(7), None, "client", "test").await.unwrap_err();
+        assert!(matches!(error, ServiceError::InsufficientStock { requested: 7, available: 6, .. }), "{error}");
+
+        let error = product_service
+            .set_stock(product.id, UpdateStockRequest { on_hand: 3 })
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ServiceError::StockBelowReserved { on_hand: 3, reserved: 4, .. }), "{error}");
+
+        order_service.change_status(order.id, OrderStatus::Cancelled, "test").await.unwrap();
+        let stock = product_service
+            .set_stock(product.id, UpdateStockRequest { on_hand: 3 })
+            .await
+            .unwrap();
+        assert_eq!((stock.on_hand, stock.reserved, stock.available), (3, 0, 3));
+    }
+}
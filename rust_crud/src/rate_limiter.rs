@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::auth::Principal;
+use crate::errors::ApiError;
+
+/// How often buckets that have refilled completely are dropped.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Routes that share a limit. Each caller has a separate bucket per group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteGroup {
+    Public,
+    Reads,
+    Writes,
+    Admin,
+    /// Not routes: requests to `/api` rejected with 401, counted by client
+    /// IP before credentials are checked
+    FailedAuth,
+}
+
+/// `requests` per `period`, parsed from `<requests>/<seconds>`. Callers may
+/// burst up to `requests` at once.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    fn tokens_per_second(&self) -> f64 {
+        f64::from(self.requests) / self.period.as_secs_f64()
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parsed = value.split_once('/').and_then(|(requests, seconds)| {
+            Some(RateLimit {
+                requests: requests.trim().parse().ok().filter(|requests| *requests > 0)?,
+                period: Duration::from_secs(seconds.trim().parse().ok().filter(|seconds| *seconds > 0)?),
+            })
+        });
+        parsed.ok_or_else(|| anyhow::anyhow!("Invalid rate limit '{}'; expected <requests>/<seconds>", value))
+    }
+}
+
+/// Limits per route group; `None` leaves a group unlimited.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimits {
+    pub public: Option<RateLimit>,
+    pub reads: Option<RateLimit>,
+    pub writes: Option<RateLimit>,
+    pub admin: Option<RateLimit>,
+    pub failed_auth: Option<RateLimit>,
+}
+
+impl RateLimits {
+    fn get(&self, group: RouteGroup) -> Option<RateLimit> {
+        match group {
+            RouteGroup::Public => self.public,
+            RouteGroup::Reads => self.reads,
+            RouteGroup::Writes => self.writes,
+            RouteGroup::Admin => self.admin,
+            RouteGroup::FailedAuth => self.failed_auth,
+        }
+    }
+}
+
+/// One caller's bucket in a route group, as shown on the admin endpoint.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RateLimitCounter {
+    pub group: RouteGroup,
+    pub key: String,
+    pub limit: u32,
+    pub period_seconds: u64,
+    pub remaining: u32,
+    pub allowed: u64,
+    pub limited: u64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    allowed: u64,
+    limited: u64,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.tokens_per_second()).min(f64::from(limit.requests));
+        self.updated = now;
+    }
+}
+
+/// The outcome of taking a token, with what the `RateLimit-*` headers
+/// report.
+struct Decision {
+    allowed: bool,
+    limit: RateLimit,
+    remaining: u32,
+    /// Until the bucket is full again
+    reset: Duration,
+    /// Until the next token is available
+    retry_after: Duration,
+}
+
+struct Buckets {
+    by_key: HashMap<(RouteGroup, String), Bucket>,
+    swept: Instant,
+}
+
+/// In-process token buckets, one per route group and caller. Counts are
+/// per instance and start again on restart.
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    fn buckets(&self) -> std::sync::MutexGuard<'_, Buckets> {
+        self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Takes a token from the caller's bucket in `group`, if the group is
+    /// limited.
+    fn check(&self, group: RouteGroup, key: &str) -> Option<Decision> {
+        let limit = self.limits.get(group)?;
+        let now = Instant::now();
+        let mut buckets = self.buckets();
+
+        if now.duration_since(buckets.swept) >= SWEEP_INTERVAL {
+            self.sweep(&mut buckets.by_key, now);
+            buckets.swept = now;
+        }
+
+        let bucket = buckets.by_key.entry((group, key.to_string())).or_insert_with(|| Bucket {
+            tokens: f64::from(limit.requests),
+            updated: now,
+            allowed: 0,
+            limited: 0,
+        });
+        bucket.refill(limit, now);
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+            bucket.allowed += 1;
+        } else {
+            bucket.limited += 1;
+        }
+
+        let rate = limit.tokens_per_second();
+        Some(Decision {
+            allowed,
+            limit,
+            remaining: bucket.tokens.floor() as u32,
+            reset: Duration::from_secs_f64((f64::from(limit.requests) - bucket.tokens) / rate),
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) / rate),
+        })
+    }
+
+    /// How long until the caller's bucket in `group` has a token again, or
+    /// `None` if it has one now. Takes nothing.
+    fn blocked_for(&self, group: RouteGroup, key: &str) -> Option<Duration> {
+        let limit = self.limits.get(group)?;
+        let mut buckets = self.buckets();
+        let bucket = buckets.by_key.get_mut(&(group, key.to_string()))?;
+        bucket.refill(limit, Instant::now());
+
+        (bucket.tokens < 1.0)
+            .then(|| Duration::from_secs_f64((1.0 - bucket.tokens) / limit.tokens_per_second()))
+    }
+
+    /// Drops the buckets that have refilled completely, since they hold
+    /// nothing a new bucket would not. Runs at most once per
+    /// `SWEEP_INTERVAL` so that a full map is not scanned on every request.
+    fn sweep(&self, by_key: &mut HashMap<(RouteGroup, String), Bucket>, now: Instant) {
+        by_key.retain(|(group, _), bucket| {
+            let Some(limit) = self.limits.get(*group) else {
+                return false;
+            };
+            bucket.refill(limit, now);
+            bucket.tokens < f64::from(limit.requests)
+        });
+    }
+
+    /// Current state of every bucket, for the admin endpoint.
+    pub fn counters(&self) -> Vec<RateLimitCounter> {
+        let now = Instant::now();
+        let mut buckets = self.buckets();
+
+        let mut counters: Vec<RateLimitCounter> = buckets
+            .by_key
+            .iter_mut()
+            .filter_map(|((group, key), bucket)| {
+                let limit = self.limits.get(*group)?;
+                bucket.refill(limit, now);
+                Some(RateLimitCounter {
+                    group: *group,
+                    key: key.clone(),
+                    limit: limit.requests,
+                    period_seconds: limit.period.as_secs(),
+                    remaining: bucket.tokens.floor() as u32,
+                    allowed: bucket.allowed,
+                    limited: bucket.limited,
+                })
+            })
+            .collect();
+        counters.sort_by(|a, b| (a.group, &a.key).cmp(&(b.group, &b.key)));
+        counters
+    }
+}
+
+/// State for `rate_limit`: the group a set of routes is counted against.
+#[derive(Clone)]
+pub struct LimitGroup {
+    pub limiter: Arc<RateLimiter>,
+    pub group: RouteGroup,
+}
+
+/// Middleware for a route group. Authenticated callers are counted by their
+/// credential (`api-key:<id>` or `jwt:<sub>`), anyone else by client IP.
+/// Over-limit requests get 429 with `Retry-After`; every counted response
+/// carries `RateLimit-*` headers.
+pub async fn rate_limit(State(limited): State<LimitGroup>, request: Request, next: Next) -> Response {
+    let key = match request.extensions().get::<Principal>() {
+        Some(principal) => principal.id.clone(),
+        None => client_ip(&request),
+    };
+
+    let Some(decision) = limited.limiter.check(limited.group, &key) else {
+        return next.run(request).await;
+    };
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        tracing::warn!("Rate limit exceeded for {} on {:?} routes", key, limited.group);
+        ApiError::TooManyRequests {
+            retry_after: ceil_seconds(decision.retry_after),
+        }
+        .into_response()
+    };
+
+    insert_rate_limit_headers(response.headers_mut(), &decision);
+    response
+}
+
+/// Middleware outside `require_scope`: once a client IP has used up its
+/// `FailedAuth` allowance of 401 responses, its requests get 429 before
+/// their credentials are checked, which slows down key guessing.
+pub async fn limit_failed_auth(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    let key = client_ip(&request);
+    if let Some(retry_after) = limiter.blocked_for(RouteGroup::FailedAuth, &key) {
+        tracing::warn!("Too many failed authentications from {}", key);
+        return ApiError::TooManyRequests {
+            retry_after: ceil_seconds(retry_after),
+        }
+        .into_response();
+    }
+
+    let response = next.run(request).await;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        limiter.check(RouteGroup::FailedAuth, &key);
+    }
+    response
+}
+
+fn client_ip(request: &Request) -> String {
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let values = [
+        ("ratelimit-limit", decision.limit.requests.to_string()),
+        ("ratelimit-remaining", decision.remaining.to_string()),
+        ("ratelimit-reset", ceil_seconds(decision.reset).to_string()),
+        (
+            "ratelimit-policy",
+            format!("{};w={}", decision.limit.requests, decision.limit.period.as_secs()),
+        ),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    }
+}
+
+fn ceil_seconds(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+    use crate::auth::Role;
+
+    fn limits(limit: &str) -> RateLimits {
+        let limit = Some(limit.parse().unwrap());
+        RateLimits {
+            public: limit,
+            reads: limit,
+            writes: limit,
+            admin: limit,
+            failed_auth: limit,
+        }
+    }
+
+    #[test]
+    fn rate_limits_parse_requests_per_seconds() {
+        let limit: RateLimit = "120/60".parse().unwrap();
+        assert_eq!(limit.requests, 120);
+        assert_eq!(limit.period, Duration::from_secs(60));
+
+        for invalid in ["", "120", "0/60", "120/0", "many/60"] {
+            assert!(invalid.parse::<RateLimit>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn buckets_allow_a_burst_then_limit() {
+        let limiter = RateLimiter::new(limits("3/60"));
+
+        for remaining in [2, 1, 0] {
+            let decision = limiter.check(RouteGroup::Reads, "api-key:1").unwrap();
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+
+        let decision = limiter.check(RouteGroup::Reads, "api-key:1").unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(ceil_seconds(decision.retry_after), 20);
+        assert_eq!(ceil_seconds(decision.reset), 60);
+
+        // Other callers and other groups have their own buckets
+        assert!(limiter.check(RouteGroup::Reads, "api-key:2").unwrap().allowed);
+        assert!(limiter.check(RouteGroup::Writes, "api-key:1").unwrap().allowed);
+    }
+
+    #[test]
+    fn buckets_refill_over_the_period() {
+        let limit: RateLimit = "2/10".parse().unwrap();
+        let start = Instant::now();
+        let mut bucket = Bucket { tokens: 0.0, updated: start, allowed: 0, limited: 0 };
+
+        bucket.refill(limit, start + Duration::from_secs(5));
+        assert_eq!(bucket.tokens, 1.0);
+
+        bucket.refill(limit, start + Duration::from_secs(60));
+        assert_eq!(bucket.tokens, 2.0);
+    }
+
+    #[test]
+    fn unlimited_groups_are_not_counted() {
+        let limiter = RateLimiter::new(RateLimits { reads: None, ..limits("1/60") });
+
+        assert!(limiter.check(RouteGroup::Reads, "api-key:1").is_none());
+        assert!(limiter.counters().is_empty());
+    }
+
+    #[test]
+    fn sweep_drops_only_full_buckets() {
+        let limiter = RateLimiter::new(limits("2/10"));
+        limiter.check(RouteGroup::Reads, "api-key:1");
+
+        let mut buckets = limiter.buckets();
+        let now = Instant::now();
+        limiter.sweep(&mut buckets.by_key, now);
+        assert_eq!(buckets.by_key.len(), 1);
+
+        limiter.sweep(&mut buckets.by_key, now + Duration::from_secs(10));
+        assert!(buckets.by_key.is_empty());
+    }
+
+    fn principal(id: &str, subject: &str) -> Principal {
+        Principal {
+            id: id.to_string(),
+            subject: subject.to_string(),
+            role: Role::Clerk,
+            scopes: Vec::new(),
+        }
+    }
+
+    async fn send(app: &Router, principal: Option<Principal>) -> Response {
+        let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        if let Some(principal) = principal {
+            request.extensions_mut().insert(principal);
+        }
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn callers_are_counted_by_credential_not_name() {
+        let limiter = Arc::new(RateLimiter::new(limits("1/60")));
+        let app = Router::new().route("/", get(|| async { "ok" })).layer(middleware::from_fn_with_state(
+            LimitGroup { limiter: limiter.clone(), group: RouteGroup::Reads },
+            rate_limit,
+        ));
+
+        let response = send(&app, Some(principal("api-key:1", "api-key:shared"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["ratelimit-limit"], "1");
+        assert_eq!(response.headers()["ratelimit-remaining"], "0");
+        assert_eq!(response.headers()["ratelimit-policy"], "1;w=60");
+
+        // A second key with the same name, or a token whose subject is that
+        // name, has a bucket of its own
+        let response = send(&app, Some(principal("api-key:2", "api-key:shared"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&app, Some(principal("jwt:api-key:shared", "api-key:shared"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send(&app, Some(principal("api-key:1", "api-key:shared"))).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "60");
+    }
+
+    #[tokio::test]
+    async fn failed_authentications_block_the_client_ip() {
+        let limiter = Arc::new(RateLimiter::new(limits("2/60")));
+        let app = Router::new()
+            .route("/", get(|| async { StatusCode::UNAUTHORIZED }))
+            .layer(middleware::from_fn_with_state(limiter.clone(), limit_failed_auth));
+
+        for _ in 0..2 {
+            assert_eq!(send(&app, None).await.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let response = send(&app, None).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "30");
+    }
+
+    #[tokio::test]
+    async fn successful_authentications_are_not_counted() {
+        let limiter = Arc::new(RateLimiter::new(limits("1/60")));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(limiter.clone(), limit_failed_auth));
+
+        for _ in 0..3 {
+            assert_eq!(send(&app, None).await.status(), StatusCode::OK);
+        }
+        assert!(limiter.counters().is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use sqlx::{QueryBuilder, Sqlite};
+use crate::database::DatabasePool;
+use crate::models::{ReportQuery, RevenuePoint, CustomerRevenue, ProductRevenue, StatusBreakdown};
+use crate::repository::RepositoryError;
+
+/// Aggregates over `orders`, computed in SQL. Soft-deleted orders never
+/// count, and cancelled orders are left out of every report except the
+/// status breakdown.
+pub struct ReportRepository {
+    pool: DatabasePool,
+}
+
+impl ReportRepository {
+    pub fn new(pool: DatabasePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn revenue(&self, query: &ReportQuery) -> Result<Vec<RevenuePoint>, RepositoryError> {
+        let period = query.interval().period_expression();
+        let mut select = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {} AS period, COUNT(*) AS order_count, ROUND(COALESCE(SUM(total_amount), 0), 2) AS revenue FROM orders",
+            period
+        ));
+        push_range(&mut select, query, "");
+        select.push(" AND status <> 'Cancelled'");
+        select.push(" GROUP BY period ORDER BY period");
+
+        let rows = select
+            .build_query_as::<RevenuePoint>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn top_customers(&self, query: &ReportQuery) -> Result<Vec<CustomerRevenue>, RepositoryError> {
+        let mut select = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT o.customer_id, c.name AS customer_name, COUNT(*) AS order_count,
+                   ROUND(SUM(o.total_amount), 2) AS revenue
+            FROM orders o
+            JOIN customers c ON c.id = o.customer_id
+            "#
+        );
+        push_range(&mut select, query, "o.");
+        select.push(" AND o.status <> 'Cancelled'");
+        select.push(" GROUP BY o.customer_id, c.name ORDER BY revenue DESC, o.customer_id LIMIT ");
+        select.push_bind(query.limit());
+
+        let rows = select
+            .build_query_as::<CustomerRevenue>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows)
+    }
+
+    /// Ranks products by the line totals they contributed to orders' `total_amount`.
+    pub async fn top_products(&self, query: &ReportQuery) -> Result<Vec<ProductRevenue>, RepositoryError> {
+        let mut select = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT oi.product_id, MAX(oi.sku) AS sku, MAX(oi.product_name) AS product_name,
+                   SUM(oi.quantity) AS quantity, COUNT(DISTINCT o.id) AS order_count,
+                   ROUND(SUM(oi.line_total), 2) AS revenue
+            FROM orders o
+            JOIN order_items oi ON oi.order_id = o.id
+            "#
+        );
+        push_range(&mut select, query, "o.");
+        select.push(" AND o.status <> 'Cancelled'");
+        select.push(" GROUP BY COALESCE(CAST(oi.product_id AS TEXT), 'name:' || oi.product_name)");
+        select.push(" ORDER BY revenue DESC, product_name LIMIT ");
+        select.push_bind(query.limit());
+
+        let rows = select
+            .build_query_as::<ProductRevenue>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn status_breakdown(&self, query: &ReportQuery) -> Result<Vec<StatusBreakdown>, RepositoryError> {
+        let mut select = QueryBuilder::<Sqlite>::new(
+            "SELECT status, COUNT(*) AS order_count, ROUND(COALESCE(SUM(total_amount), 0), 2) AS revenue FROM orders"
+        );
+        push_range(&mut select, query, "");
+        select.push(" GROUP BY status ORDER BY status");
+
+        let rows = select
+            .build_query_as::<StatusBreakdown>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows)
+    }
+}
+
+/// Appends the `WHERE` clause for live orders in the query's date range.
+/// `prefix` is the `orders` table alias, e.g. `"o."`, or empty.
+fn push_range(builder: &mut QueryBuilder<'_, Sqlite>, query: &ReportQuery, prefix: &str) {
+    builder.push(format!(" WHERE {}deleted_at IS NULL", prefix));
+    if let Some(from) = query.from {
+        builder.push(format!(" AND {}order_date >= ", prefix)).push_bind(from.to_rfc3339());
+    }
+    if let Some(to) = query.to {
+        builder.push(format!(" AND {}order_date <= ", prefix)).push_bind(to.to_rfc3339());
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use validator::Validate;
+use crate::report_repository::ReportRepository;
+use crate::models::{ReportQuery, RevenuePoint, CustomerRevenue, ProductRevenue, StatusBreakdown};
+use crate::service::ServiceError;
+use crate::status_reporter::StatusReporter;
+
+pub struct ReportService {
+    repository: Arc<ReportRepository>,
+    status_reporter: Arc<dyn StatusReporter>,
+}
+
+impl ReportService {
+    pub fn new(repository: Arc<ReportRepository>, status_reporter: Arc<dyn StatusReporter>) -> Self {
+        Self {
+            repository,
+            status_reporter,
+        }
+    }
+
+    pub async fn revenue(&self, query: &ReportQuery) -> Result<Vec<RevenuePoint>, ServiceError> {
+        self.validate("revenue_report", query).await?;
+
+        match self.repository.revenue(query).await {
+            Ok(points) => {
+                self.status_reporter
+                    .report_success("revenue_report", None)
+                    .await;
+                Ok(points)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to build revenue report: {}", e);
+                self.status_reporter
+                    .report_failure("revenue_report", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn top_customers(&self, query: &ReportQuery) -> Result<Vec<CustomerRevenue>, ServiceError> {
+        self.validate("top_customers_report", query).await?;
+
+        match self.repository.top_customers(query).await {
+            Ok(customers) => {
+                self.status_reporter
+                    .report_success("top_customers_report", None)
+                    .await;
+                Ok(customers)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to build top customers report: {}", e);
+                self.status_reporter
+                    .report_failure("top_customers_report", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn top_products(&self, query: &ReportQuery) -> Result<Vec<ProductRevenue>, ServiceError> {
+        self.validate("top_products_report", query).await?;
+
+        match self.repository.top_products(query).await {
+            Ok(products) => {
+                self.status_reporter
+                    .report_success("top_products_report", None)
+                    .await;
+                Ok(products)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to build top products report: {}", e);
+                self.status_reporter
+                    .report_failure("top_products_report", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn status_breakdown(&self, query: &ReportQuery) -> Result<Vec<StatusBreakdown>, ServiceError> {
+        self.validate("status_breakdown_report", query).await?;
+
+        match self.repository.status_breakdown(query).await {
+            Ok(breakdown) => {
+                self.status_reporter
+                    .report_success("status_breakdown_report", None)
+                    .await;
+                Ok(breakdown)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to build status breakdown report: {}", e);
+                self.status_reporter
+                    .report_failure("status_breakdown_report", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    async fn validate(&self, operation: &str, query: &ReportQuery) -> Result<(), ServiceError> {
+        if let Err(validation_errors) = query.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure(operation, &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,19 @@
-use chrono::Utc;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
-use sqlx::Row;
-use crate::database::DatabasePool;
-use crate::models::{Order, CreateOrderRequest, UpdateOrderRequest, OrderStatus};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+use sqlx::{Connection, QueryBuilder, Row, Sqlite, SqliteConnection};
+use crate::database::{amount, DatabasePool};
+use std::collections::{BTreeMap, HashMap};
+use crate::models::{
+    Order, OrderItem, NewOrder, NewOrderItem, OrderChanges, OrderStatus, OrderQuery, OrderEvent,
+    OrderEventType, OrderCustomer, IdempotencyKey, IdempotencyRecord, BulkMode,
+};
+use crate::customer_repository::find_or_create_by_name;
+
+const ORDER_ITEM_COLUMNS: &str = "order_id, line_number, product_id, sku, product_name, quantity, unit_price, line_total";
+const ORDER_COLUMNS: &str = "id, customer_id, customer_name, product_name, quantity, unit_price, total_amount, order_date, status, version, created_at, updated_at, deleted_at";
 
 #[derive(Debug, thiserror::Error)]
 pub enum RepositoryError {
@@ -10,137 +21,1203 @@ pub enum RepositoryError {
     Database(#[from] sqlx::Error),
     #[error("Connection pool error: {0}")]
     Pool(String),
+    #[error("Insufficient stock for product {product_id}: requested {requested}, available {available}")]
+    InsufficientStock { product_id: i32, requested: i64, available: i64 },
+    #[error("Order {id} is at version {actual}, expected {expected}")]
+    VersionConflict { id: i32, expected: i64, actual: i64 },
+    #[error("Idempotency key '{0}' is already in use")]
+    DuplicateIdempotencyKey(String),
+    /// Raised by the in-memory repositories where SQLite would fail a
+    /// `UNIQUE` constraint
+    #[error("UNIQUE constraint failed: {0}")]
+    UniqueViolation(String),
 }
 
-pub struct OrderRepository {
+impl RepositoryError {
+    /// True when a write hit a `UNIQUE` constraint.
+    pub fn is_unique_violation(&self) -> bool {
+        match self {
+            RepositoryError::Database(sqlx::Error::Database(e)) => e.is_unique_violation(),
+            RepositoryError::UniqueViolation(_) => true,
+            _ => false,
+        }
+    }
+
+    /// True when a delete was refused because other rows still reference it.
+    pub fn is_foreign_key_violation(&self) -> bool {
+        matches!(self, RepositoryError::Database(sqlx::Error::Database(e)) if e.is_foreign_key_violation())
+    }
+}
+
+/// Order storage used by `OrderService`. `SqliteOrderRepository` is the
+/// production implementation; `InMemoryOrderRepository` keeps everything in
+/// process for tests and embedding.
+///
+/// A missing order is reported as `Database(sqlx::Error::RowNotFound)` by
+/// every implementation, so callers match one error regardless of backend.
+#[async_trait]
+pub trait OrderRepository: Send + Sync {
+    /// Inserts the order. With an `idempotency_key` the key is stored
+    /// atomically with the order; if it is already taken nothing is written
+    /// and the call fails with `DuplicateIdempotencyKey`.
+    async fn create(
+        &self,
+        new_order: NewOrder,
+        idempotency_key: Option<&IdempotencyKey>,
+        actor: &str,
+    ) -> Result<Order, RepositoryError>;
+
+    /// Returns the unexpired record `principal` stored for `key`, if any.
+    async fn find_idempotency_record(
+        &self,
+        principal: &str,
+        key: &str,
+    ) -> Result<Option<IdempotencyRecord>, RepositoryError>;
+
+    /// Returns one page of orders matching `query` together with the total
+    /// number of matching orders.
+    async fn find_all(&self, query: &OrderQuery) -> Result<(Vec<Order>, i64), RepositoryError>;
+
+    /// Soft-deleted orders are only returned when `include_deleted` is set.
+    async fn find_by_id(&self, id: i32, include_deleted: bool) -> Result<Option<Order>, RepositoryError>;
+
+    /// Applies `changes` if the order is still at `expected_version`, or at
+    /// the version it has when the caller has none. A concurrent write that
+    /// lands first surfaces as `VersionConflict` instead of being overwritten.
+    async fn update(
+        &self,
+        id: i32,
+        changes: OrderChanges,
+        expected_version: Option<i64>,
+        actor: &str,
+    ) -> Result<Order, RepositoryError>;
+
+    /// Soft-deletes the order if it is still at `expected_version` (any
+    /// version when `None`). Returns `false` when there is no live order.
+    async fn delete(&self, id: i32, expected_version: Option<i64>, actor: &str) -> Result<bool, RepositoryError>;
+
+    /// Creates every order as one batch. In `PerItem` mode a failed item is
+    /// skipped and the rest are kept. In `AllOrNothing` mode the first
+    /// failure discards the whole batch; the results then stop at the failed
+    /// item.
+    async fn create_many(
+        &self,
+        orders: Vec<NewOrder>,
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<Order, RepositoryError>>, RepositoryError>;
+
+    /// Applies each `(id, changes, expected_version)` as one batch, with the
+    /// same per-mode behaviour as `create_many`.
+    async fn update_many(
+        &self,
+        updates: Vec<(i32, OrderChanges, Option<i64>)>,
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<Order, RepositoryError>>, RepositoryError>;
+
+    /// Soft-deletes each order as one batch, with the same per-mode
+    /// behaviour as `create_many`. A missing order fails with `RowNotFound`.
+    async fn delete_many(
+        &self,
+        ids: &[i32],
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<(), RepositoryError>>, RepositoryError>;
+
+    /// Brings a soft-deleted order back. Returns `None` when the order is
+    /// not deleted.
+    async fn restore(&self, id: i32, actor: &str) -> Result<Option<Order>, RepositoryError>;
+
+    /// Permanently removes orders soft-deleted before `deleted_before`.
+    /// Audit events are kept.
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, RepositoryError>;
+
+    /// Returns the audit trail for an order, oldest first. Events outlive the
+    /// order itself, so this still answers for deleted orders.
+    async fn find_events(&self, order_id: i32) -> Result<Vec<OrderEvent>, RepositoryError>;
+
+    /// Returns up to `limit` events of any order with an id above
+    /// `after_id`, oldest first. Event ids only ever increase, so this is
+    /// how the order stream catches up.
+    async fn find_events_after(&self, after_id: i64, limit: i64) -> Result<Vec<OrderEvent>, RepositoryError>;
+
+    /// The id of the newest event, or 0 when there are none.
+    async fn latest_event_id(&self) -> Result<i64, RepositoryError>;
+}
+
+pub struct SqliteOrderRepository {
     pool: DatabasePool,
 }
 
-impl OrderRepository {
+impl SqliteOrderRepository {
     pub fn new(pool: DatabasePool) -> Self {
         Self { pool }
     }
+}
 
-    pub async fn create(&self, request: CreateOrderRequest) -> Result<Order, RepositoryError> {
-        let total_amount = request.unit_price * Decimal::from(request.quantity);
-        let now = Utc::now();
+/// Every write runs in a transaction together with its stock movements and
+/// audit event; restoring an open order reserves its stock again. Batches
+/// share one transaction with a savepoint per item.
+#[async_trait]
+impl OrderRepository for SqliteOrderRepository {
+    async fn create(
+        &self,
+        new_order: NewOrder,
+        idempotency_key: Option<&IdempotencyKey>,
+        actor: &str,
+    ) -> Result<Order, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let order = insert_order(&mut tx, new_order, actor).await?;
+        if let Some(idempotency_key) = idempotency_key {
+            store_idempotency_key(&mut tx, idempotency_key, &order).await?;
+        }
+        tx.commit().await?;
+
+        Ok(order)
+    }
+
+    async fn find_idempotency_record(
+        &self,
+        principal: &str,
+        key: &str,
+    ) -> Result<Option<IdempotencyRecord>, RepositoryError> {
+        let record = sqlx::query_as::<_, IdempotencyRecord>(
+            r#"
+            SELECT principal, key, fingerprint, order_id, response, created_at, expires_at
+            FROM idempotency_keys
+            WHERE principal = ? AND key = ? AND expires_at > ?
+            "#
+        )
+        .bind(principal)
+        .bind(key)
+        .bind(Utc::now().to_rfc3339())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    async fn find_all(&self, query: &OrderQuery) -> Result<(Vec<Order>, i64), RepositoryError> {
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM orders");
+        push_filters(&mut count, query);
+        let total: i64 = count
+            .build()
+            .fetch_one(&self.pool)
+            .await?
+            .try_get(0)?;
+
+        let sort = query.sort.unwrap_or_default();
+        let order = query.order.unwrap_or_default();
+
+        let mut select = QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM orders", ORDER_COLUMNS));
+        push_filters(&mut select, query);
+        // `id` breaks ties so that pages stay stable when the sort column has duplicates
+        select.push(format!(
+            " ORDER BY {} {}, id {}",
+            sort.column(),
+            order.keyword(),
+            order.keyword()
+        ));
+        select.push(" LIMIT ").push_bind(query.limit());
+        select.push(" OFFSET ").push_bind(query.offset());
+
+        let mut conn = self.pool.acquire().await?;
+        let mut rows: Vec<Order> = select
+            .build_query_as::<Order>()
+            .fetch_all(&mut *conn)
+            .await?;
+        attach_items(&mut conn, &mut rows).await?;
+
+        Ok((rows, total))
+    }
+
+    async fn find_by_id(&self, id: i32, include_deleted: bool) -> Result<Option<Order>, RepositoryError> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_order(&mut conn, id, include_deleted).await
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        changes: OrderChanges,
+        expected_version: Option<i64>,
+        actor: &str,
+    ) -> Result<Order, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let order = update_order(&mut tx, id, changes, expected_version, actor).await?;
+        tx.commit().await?;
+
+        Ok(order)
+    }
+
+    async fn delete(&self, id: i32, expected_version: Option<i64>, actor: &str) -> Result<bool, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        match soft_delete_order(&mut tx, id, expected_version, actor).await {
+            Ok(()) => {}
+            Err(RepositoryError::Database(sqlx::Error::RowNotFound)) => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn create_many(
+        &self,
+        orders: Vec<NewOrder>,
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<Order, RepositoryError>>, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(orders.len());
+
+        for new_order in orders {
+            let mut savepoint = tx.begin().await?;
+            match insert_order(&mut savepoint, new_order, actor).await {
+                Ok(order) => {
+                    savepoint.commit().await?;
+                    results.push(Ok(order));
+                }
+                Err(e) => {
+                    savepoint.rollback().await?;
+                    results.push(Err(e));
+                    if mode == BulkMode::AllOrNothing {
+                        tx.rollback().await?;
+                        return Ok(results);
+                    }
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    async fn update_many(
+        &self,
+        updates: Vec<(i32, OrderChanges, Option<i64>)>,
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<Order, RepositoryError>>, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(updates.len());
+
+        for (id, changes, expected_version) in updates {
+            let mut savepoint = tx.begin().await?;
+            match update_order(&mut savepoint, id, changes, expected_version, actor).await {
+                Ok(order) => {
+                    savepoint.commit().await?;
+                    results.push(Ok(order));
+                }
+                Err(e) => {
+                    savepoint.rollback().await?;
+                    results.push(Err(e));
+                    if mode == BulkMode::AllOrNothing {
+                        tx.rollback().await?;
+                        return Ok(results);
+                    }
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    async fn delete_many(
+        &self,
+        ids: &[i32],
+        mode: BulkMode,
+        actor: &str,
+    ) -> Result<Vec<Result<(), RepositoryError>>, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(ids.len());
+
+        for &id in ids {
+            let mut savepoint = tx.begin().await?;
+            match soft_delete_order(&mut savepoint, id, None, actor).await {
+                Ok(()) => {
+                    savepoint.commit().await?;
+                    results.push(Ok(()));
+                }
+                Err(e) => {
+                    savepoint.rollback().await?;
+                    results.push(Err(e));
+                    if mode == BulkMode::AllOrNothing {
+                        tx.rollback().await?;
+                        return Ok(results);
+                    }
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    async fn restore(&self, id: i32, actor: &str) -> Result<Option<Order>, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = fetch_order(&mut tx, id, true).await?
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+        if current.deleted_at.is_none() {
+            return Ok(None);
+        }
+
+        if stock_hold(current.status) == StockHold::Reserved {
+            reserve_stock(&mut tx, &stock_quantities(&current.items)).await?;
+        }
 
+        let now = Utc::now();
         let result = sqlx::query(
             r#"
-            INSERT INTO orders (customer_name, product_name, quantity, unit_price, total_amount, order_date, status, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            UPDATE orders
+            SET deleted_at = NULL, version = version + 1, updated_at = ?
+            WHERE id = ? AND version = ?
             "#
         )
-        .bind(&request.customer_name)
-        .bind(&request.product_name)
-        .bind(request.quantity)
-        .bind(request.unit_price)
-        .bind(total_amount)
-        .bind(now.to_rfc3339())
-        .bind("Pending")
         .bind(now.to_rfc3339())
-        .bind(now.to_rfc3339())
-        .execute(&self.pool)
+        .bind(id)
+        .bind(current.version)
+        .execute(&mut *tx)
         .await?;
 
-        let id = result.last_insert_rowid() as i32;
-
-        Ok(Order {
-            id,
-            customer_name: request.customer_name,
-            product_name: request.product_name,
-            quantity: request.quantity,
-            unit_price: request.unit_price,
-            total_amount,
-            order_date: now,
-            status: OrderStatus::Pending,
-            created_at: now,
+        if result.rows_affected() == 0 {
+            return Err(version_conflict(&mut tx, id, current.version).await);
+        }
+
+        let order = Order {
+            version: current.version + 1,
             updated_at: now,
-        })
+            deleted_at: None,
+            ..current.clone()
+        };
+
+        let (old_values, new_values) = changed_fields(&snapshot(&current), &snapshot(&order));
+        record_event(&mut tx, id, OrderEventType::Updated, actor, Some(old_values), Some(new_values)).await?;
+        tx.commit().await?;
+
+        Ok(Some(order))
     }
 
-    pub async fn find_all(&self) -> Result<Vec<Order>, RepositoryError> {
-        let rows = sqlx::query_as::<_, Order>(
-            "SELECT id, customer_name, product_name, quantity, unit_price, total_amount, order_date, status, created_at, updated_at FROM orders ORDER BY created_at DESC"
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        let result = sqlx::query("DELETE FROM orders WHERE deleted_at IS NOT NULL AND deleted_at < ?")
+            .bind(deleted_before.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn find_events(&self, order_id: i32) -> Result<Vec<OrderEvent>, RepositoryError> {
+        let events = sqlx::query_as::<_, OrderEvent>(
+            "SELECT id, order_id, event_type, actor, old_values, new_values, created_at FROM order_events WHERE order_id = ? ORDER BY id"
         )
+        .bind(order_id)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows)
+        Ok(events)
     }
 
-    pub async fn find_by_id(&self, id: i32) -> Result<Option<Order>, RepositoryError> {
-        let row = sqlx::query_as::<_, Order>(
-            "SELECT id, customer_name, product_name, quantity, unit_price, total_amount, order_date, status, created_at, updated_at FROM orders WHERE id = ?"
+    async fn find_events_after(&self, after_id: i64, limit: i64) -> Result<Vec<OrderEvent>, RepositoryError> {
+        let events = sqlx::query_as::<_, OrderEvent>(
+            "SELECT id, order_id, event_type, actor, old_values, new_values, created_at FROM order_events WHERE id > ? ORDER BY id LIMIT ?"
         )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    async fn latest_event_id(&self) -> Result<i64, RepositoryError> {
+        let id: Option<i64> = sqlx::query_scalar("SELECT MAX(id) FROM order_events")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(id.unwrap_or(0))
+    }
+}
+
+/// Inserts an order with its line items, reserves its stock and records
+/// the `created` event.
+async fn insert_order(conn: &mut SqliteConnection, new_order: NewOrder, actor: &str) -> Result<Order, RepositoryError> {
+    let total_amount = new_order.total_amount();
+    let (customer_id, customer_name) = order_customer(conn, new_order.customer).await?;
+    let first = &new_order.items[0];
+    let now = Utc::now();
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO orders (customer_id, customer_name, product_name, quantity, unit_price, total_amount, order_date, status, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#
+    )
+    .bind(customer_id)
+    .bind(&customer_name)
+    .bind(&first.product_name)
+    .bind(first.quantity)
+    .bind(amount(first.unit_price))
+    .bind(amount(total_amount))
+    .bind(now.to_rfc3339())
+    .bind("Pending")
+    .bind(now.to_rfc3339())
+    .bind(now.to_rfc3339())
+    .execute(&mut *conn)
+    .await?;
+
+    let id = result.last_insert_rowid() as i32;
+    let items = insert_items(conn, id, &new_order.items).await?;
+    reserve_stock(conn, &stock_quantities(&items)).await?;
+
+    let order = Order {
+        id,
+        customer_id,
+        customer_name,
+        product_name: items[0].product_name.clone(),
+        quantity: items[0].quantity,
+        unit_price: items[0].unit_price,
+        total_amount,
+        order_date: now,
+        status: OrderStatus::Pending,
+        version: 1,
+        created_at: now,
+        updated_at: now,
+        deleted_at: None,
+        items,
+    };
+
+    record_event(conn, id, OrderEventType::Created, actor, None, Some(snapshot(&order))).await?;
+    Ok(order)
+}
+
+/// The id and name to write on an order, creating a newly named customer
+/// on `conn` so that it rolls back with the order.
+async fn order_customer(conn: &mut SqliteConnection, customer: OrderCustomer) -> Result<(i32, String), RepositoryError> {
+    match customer {
+        OrderCustomer::Existing { id, name } => Ok((id, name)),
+        OrderCustomer::New(name) => {
+            let customer = find_or_create_by_name(conn, &name).await?;
+            Ok((customer.id, customer.name))
+        }
+    }
+}
+
+/// Applies `changes` to a live order at `expected_version` (or the version
+/// read here), moving stock and recording the audit event.
+async fn update_order(
+    conn: &mut SqliteConnection,
+    id: i32,
+    changes: OrderChanges,
+    expected_version: Option<i64>,
+    actor: &str,
+) -> Result<Order, RepositoryError> {
+    // First, get the current order
+    let current = fetch_order(conn, id, false).await?
+        .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+    let expected = expected_version.unwrap_or(current.version);
+    if current.version != expected {
+        return Err(RepositoryError::VersionConflict { id, expected, actual: current.version });
+    }
+    let before = snapshot(&current);
+
+    let items = match &changes.items {
+        Some(new_items) => {
+            sqlx::query("DELETE FROM order_items WHERE order_id = ?")
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            insert_items(conn, id, new_items).await?
+        }
+        None => current.items.clone(),
+    };
+
+    let (customer_id, customer_name) = match changes.customer {
+        Some(customer) => order_customer(conn, customer).await?,
+        None => (current.customer_id, current.customer_name.clone()),
+    };
+    let status = changes.status.unwrap_or(current.status);
+    let total_amount: Decimal = items.iter().map(|item| item.line_total).sum();
+    let now = Utc::now();
+
+    adjust_stock(conn, (current.status, &current.items), (status, &items)).await?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE orders 
+        SET customer_id = ?, customer_name = ?, product_name = ?, quantity = ?,
+            unit_price = ?, total_amount = ?, status = ?, version = version + 1, updated_at = ?
+        WHERE id = ? AND version = ? AND deleted_at IS NULL
+        "#
+    )
+    .bind(customer_id)
+    .bind(&customer_name)
+    .bind(&items[0].product_name)
+    .bind(items[0].quantity)
+    .bind(amount(items[0].unit_price))
+    .bind(amount(total_amount))
+    .bind(status.as_str())
+    .bind(now.to_rfc3339())
+    .bind(id)
+    .bind(expected)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(version_conflict(conn, id, expected).await);
+    }
+
+    let order = Order {
+        id,
+        customer_id,
+        customer_name,
+        product_name: items[0].product_name.clone(),
+        quantity: items[0].quantity,
+        unit_price: items[0].unit_price,
+        total_amount,
+        order_date: current.order_date,
+        status,
+        version: expected + 1,
+        created_at: current.created_at,
+        updated_at: now,
+        deleted_at: None,
+        items,
+    };
+
+    let event_type = if order.status != current.status {
+        OrderEventType::StatusChanged
+    } else {
+        OrderEventType::Updated
+    };
+    let (old_values, new_values) = changed_fields(&before, &snapshot(&order));
+    record_event(conn, id, event_type, actor, Some(old_values), Some(new_values)).await?;
+    Ok(order)
+}
+
+/// Soft-deletes a live order at `expected_version` (or the version read
+/// here), releasing any stock it holds.
+async fn soft_delete_order(
+    conn: &mut SqliteConnection,
+    id: i32,
+    expected_version: Option<i64>,
+    actor: &str,
+) -> Result<(), RepositoryError> {
+    let current = fetch_order(conn, id, false).await?
+        .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+    let expected = expected_version.unwrap_or(current.version);
+    if current.version != expected {
+        return Err(RepositoryError::VersionConflict { id, expected, actual: current.version });
+    }
+
+    // Shipped stock is gone; only open orders hand their reservation back
+    if stock_hold(current.status) == StockHold::Reserved {
+        release_stock(conn, &stock_quantities(&current.items)).await?;
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let result = sqlx::query(
+        r#"
+        UPDATE orders
+        SET deleted_at = ?, version = version + 1, updated_at = ?
+        WHERE id = ? AND version = ? AND deleted_at IS NULL
+        "#
+    )
+    .bind(&now)
+    .bind(&now)
+    .bind(id)
+    .bind(expected)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(version_conflict(conn, id, expected).await);
+    }
+
+    record_event(conn, id, OrderEventType::Deleted, actor, Some(snapshot(&current)), None).await?;
+    Ok(())
+}
+
+/// Appends the `WHERE` clause for `query`. Equality filters on customer,
+/// order date and status line up with the `IX_orders_*` indexes.
+fn push_filters(builder: &mut QueryBuilder<'_, Sqlite>, query: &OrderQuery) {
+    let mut separator = " WHERE ";
+
+    if !query.include_deleted() {
+        builder.push(separator).push("deleted_at IS NULL");
+        separator = " AND ";
+    }
+
+    if let Some(status) = &query.status {
+        builder.push(separator).push("status = ").push_bind(status.as_str());
+        separator = " AND ";
+    }
+    if let Some(customer_id) = query.customer_id {
+        builder.push(separator).push("customer_id = ").push_bind(customer_id);
+        separator = " AND ";
+    }
+    if let Some(customer_name) = &query.customer_name {
+        builder.push(separator).push("customer_name = ").push_bind(customer_name.clone());
+        separator = " AND ";
+    }
+    if let Some(product_name) = &query.product_name {
+        builder
+            .push(separator)
+            .push("id IN (SELECT order_id FROM order_items WHERE product_name = ")
+            .push_bind(product_name.clone())
+            .push(")");
+        separator = " AND ";
+    }
+    if let Some(from) = &query.order_date_from {
+        builder.push(separator).push("order_date >= ").push_bind(from.to_rfc3339());
+        separator = " AND ";
+    }
+    if let Some(to) = &query.order_date_to {
+        builder.push(separator).push("order_date <= ").push_bind(to.to_rfc3339());
+        separator = " AND ";
+    }
+    if let Some(min_amount) = query.min_amount {
+        builder.push(separator).push("total_amount >= ").push_bind(amount(min_amount));
+        separator = " AND ";
+    }
+    if let Some(max_amount) = query.max_amount {
+        builder.push(separator).push("total_amount <= ").push_bind(amount(max_amount));
+    }
+}
+
+async fn fetch_order(conn: &mut SqliteConnection, id: i32, include_deleted: bool) -> Result<Option<Order>, RepositoryError> {
+    let deleted_filter = if include_deleted { "" } else { " AND deleted_at IS NULL" };
+    let row = sqlx::query_as::<_, Order>(
+        &format!("SELECT {} FROM orders WHERE id = ?{}", ORDER_COLUMNS, deleted_filter)
+    )
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(order) = row else {
+        return Ok(None);
+    };
+
+    let mut orders = vec![order];
+    attach_items(conn, &mut orders).await?;
+    Ok(orders.pop())
+}
+
+/// Drops expired keys, which frees `key` for reuse by its principal once its
+/// window has passed, then stores it with the order as returned to the client.
+async fn store_idempotency_key(
+    conn: &mut SqliteConnection,
+    idempotency_key: &IdempotencyKey,
+    order: &Order,
+) -> Result<(), RepositoryError> {
+    let now = Utc::now().to_rfc3339();
+
+    sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= ?")
+        .bind(&now)
+        .execute(&mut *conn)
+        .await?;
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO idempotency_keys (principal, key, fingerprint, order_id, response, created_at, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#
+    )
+    .bind(&idempotency_key.principal)
+    .bind(&idempotency_key.key)
+    .bind(&idempotency_key.fingerprint)
+    .bind(order.id)
+    .bind(Json(order))
+    .bind(&now)
+    .bind(idempotency_key.expires_at.to_rfc3339())
+    .execute(&mut *conn)
+    .await;
+
+    match result.map_err(RepositoryError::from) {
+        Ok(_) => Ok(()),
+        Err(e) if e.is_unique_violation() => {
+            Err(RepositoryError::DuplicateIdempotencyKey(idempotency_key.key.clone()))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Builds the error for a conditional write that matched no row because
+/// another writer bumped the version first.
+async fn version_conflict(conn: &mut SqliteConnection, id: i32, expected: i64) -> RepositoryError {
+    let actual = sqlx::query_scalar::<_, i64>("SELECT version FROM orders WHERE id = ?")
         .bind(id)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&mut *conn)
+        .await;
+
+    match actual {
+        Ok(Some(actual)) => RepositoryError::VersionConflict { id, expected, actual },
+        Ok(None) => RepositoryError::Database(sqlx::Error::RowNotFound),
+        Err(e) => RepositoryError::Database(e),
+    }
+}
+
+/// Loads the line items for `orders` with a single query.
+async fn attach_items(conn: &mut SqliteConnection, orders: &mut [Order]) -> Result<(), RepositoryError> {
+    if orders.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = QueryBuilder::<Sqlite>::new(format!(
+        "SELECT {} FROM order_items WHERE order_id IN (",
+        ORDER_ITEM_COLUMNS
+    ));
+    let mut ids = query.separated(", ");
+    for order in orders.iter() {
+        ids.push_bind(order.id);
+    }
+    query.push(") ORDER BY order_id, line_number");
+
+    let items = query
+        .build_query_as::<OrderItem>()
+        .fetch_all(&mut *conn)
         .await?;
 
-        Ok(row)
+    let mut by_order: HashMap<i32, Vec<OrderItem>> = HashMap::new();
+    for item in items {
+        by_order.entry(item.order_id).or_default().push(item);
+    }
+    for order in orders.iter_mut() {
+        order.items = by_order.remove(&order.id).unwrap_or_default();
     }
 
-    pub async fn update(&self, id: i32, request: UpdateOrderRequest) -> Result<Order, RepositoryError> {
-        // First, get the current order
-        let current = self.find_by_id(id).await?
-            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+    Ok(())
+}
 
-        // Build the updated values
-        let customer_name = request.customer_name.unwrap_or(current.customer_name);
-        let product_name = request.product_name.unwrap_or(current.product_name);
-        let quantity = request.quantity.unwrap_or(current.quantity);
-        let unit_price = request.unit_price.unwrap_or(current.unit_price);
-        let status = request.status.unwrap_or(current.status);
-        let total_amount = unit_price * Decimal::from(quantity);
-        let now = Utc::now();
+async fn insert_items(
+    conn: &mut SqliteConnection,
+    order_id: i32,
+    items: &[NewOrderItem],
+) -> Result<Vec<OrderItem>, RepositoryError> {
+    let mut inserted = Vec::with_capacity(items.len());
 
-        let status_str = match status {
-            OrderStatus::Pending => "Pending",
-            OrderStatus::Processing => "Processing",
-            OrderStatus::Shipped => "Shipped",
-            OrderStatus::Delivered => "Delivered",
-            OrderStatus::Cancelled => "Cancelled",
-        };
+    for (index, item) in items.iter().enumerate() {
+        let line_number = index as i32 + 1;
+        let line_total = item.line_total();
 
         sqlx::query(
             r#"
-            UPDATE orders 
-            SET customer_name = ?, product_name = ?, quantity = ?,
-                unit_price = ?, total_amount = ?, status = ?, updated_at = ?
-            WHERE id = ?
+            INSERT INTO order_items (order_id, line_number, product_id, sku, product_name, quantity, unit_price, line_total)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(order_id)
+        .bind(line_number)
+        .bind(item.product_id)
+        .bind(&item.sku)
+        .bind(&item.product_name)
+        .bind(item.quantity)
+        .bind(amount(item.unit_price))
+        .bind(amount(line_total))
+        .execute(&mut *conn)
+        .await?;
+
+        inserted.push(OrderItem {
+            order_id,
+            line_number,
+            product_id: item.product_id,
+            sku: item.sku.clone(),
+            product_name: item.product_name.clone(),
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+            line_total,
+        });
+    }
+
+    Ok(inserted)
+}
+
+/// What an order in a given status does to its products' stock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StockHold {
+    None,
+    Reserved,
+    Consumed,
+}
+
+fn stock_hold(status: OrderStatus) -> StockHold {
+    match status {
+        OrderStatus::Pending | OrderStatus::Processing => StockHold::Reserved,
+        OrderStatus::Shipped | OrderStatus::Delivered => StockHold::Consumed,
+        OrderStatus::Cancelled => StockHold::None,
+    }
+}
+
+/// Total quantity per catalog product. Lines written before the catalog
+/// existed have no product and are not stock-tracked.
+fn stock_quantities(items: &[OrderItem]) -> BTreeMap<i32, i64> {
+    let mut quantities = BTreeMap::new();
+    for item in items {
+        if let Some(product_id) = item.product_id {
+            *quantities.entry(product_id).or_insert(0) += i64::from(item.quantity);
+        }
+    }
+    quantities
+}
+
+/// Moves stock from what the order held before an update to what it holds
+/// after: the old hold is undone and the new one applied, so reservations
+/// follow item edits, cancelling releases and shipping draws down on-hand.
+async fn adjust_stock(
+    conn: &mut SqliteConnection,
+    before: (OrderStatus, &[OrderItem]),
+    after: (OrderStatus, &[OrderItem]),
+) -> Result<(), RepositoryError> {
+    let (before_hold, before_quantities) = (stock_hold(before.0), stock_quantities(before.1));
+    let (after_hold, after_quantities) = (stock_hold(after.0), stock_quantities(after.1));
+
+    if before_hold == after_hold && before_quantities == after_quantities {
+        return Ok(());
+    }
+
+    match before_hold {
+        StockHold::Reserved => release_stock(conn, &before_quantities).await?,
+        StockHold::Consumed => restock(conn, &before_quantities).await?,
+        StockHold::None => {}
+    }
+
+    match after_hold {
+        StockHold::Reserved => reserve_stock(conn, &after_quantities).await?,
+        StockHold::Consumed => consume_stock(conn, &after_quantities).await?,
+        StockHold::None => {}
+    }
+
+    Ok(())
+}
+
+async fn reserve_stock(conn: &mut SqliteConnection, quantities: &BTreeMap<i32, i64>) -> Result<(), RepositoryError> {
+    let now = Utc::now().to_rfc3339();
+
+    for (&product_id, &quantity) in quantities {
+        let result = sqlx::query(
+            r#"
+            UPDATE inventory
+            SET reserved = reserved + ?, updated_at = ?
+            WHERE product_id = ? AND on_hand - reserved >= ?
             "#
         )
-        .bind(&customer_name)
-        .bind(&product_name)
         .bind(quantity)
-        .bind(unit_price)
-        .bind(total_amount)
-        .bind(status_str)
-        .bind(now.to_rfc3339())
-        .bind(id)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(product_id)
+        .bind(quantity)
+        .execute(&mut *conn)
         .await?;
 
-        Ok(Order {
-            id,
-            customer_name,
-            product_name,
-            quantity,
-            unit_price,
-            total_amount,
-            order_date: current.order_date,
-            status,
-            created_at: current.created_at,
-            updated_at: now,
-        })
+        if result.rows_affected() == 0 {
+            return Err(insufficient_stock(conn, product_id, quantity).await);
+        }
     }
 
-    pub async fn delete(&self, id: i32) -> Result<bool, RepositoryError> {
-        let result = sqlx::query("DELETE FROM orders WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
+    Ok(())
+}
+
+async fn release_stock(conn: &mut SqliteConnection, quantities: &BTreeMap<i32, i64>) -> Result<(), RepositoryError> {
+    let now = Utc::now().to_rfc3339();
+
+    for (&product_id, &quantity) in quantities {
+        sqlx::query(
+            r#"
+            UPDATE inventory
+            SET reserved = MAX(reserved - ?, 0), updated_at = ?
+            WHERE product_id = ?
+            "#
+        )
+        .bind(quantity)
+        .bind(&now)
+        .bind(product_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn consume_stock(conn: &mut SqliteConnection, quantities: &BTreeMap<i32, i64>) -> Result<(), RepositoryError> {
+    let now = Utc::now().to_rfc3339();
+
+    for (&product_id, &quantity) in quantities {
+        let result = sqlx::query(
+            r#"
+            UPDATE inventory
+            SET on_hand = on_hand - ?, updated_at = ?
+            WHERE product_id = ? AND on_hand - reserved >= ?
+            "#
+        )
+        .bind(quantity)
+        .bind(&now)
+        .bind(product_id)
+        .bind(quantity)
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(insufficient_stock(conn, product_id, quantity).await);
+        }
+    }
+
+    Ok(())
+}
+
+async fn restock(conn: &mut SqliteConnection, quantities: &BTreeMap<i32, i64>) -> Result<(), RepositoryError> {
+    let now = Utc::now().to_rfc3339();
+
+    for (&product_id, &quantity) in quantities {
+        sqlx::query("UPDATE inventory SET on_hand = on_hand + ?, updated_at = ? WHERE product_id = ?")
+            .bind(quantity)
+            .bind(&now)
+            .bind(product_id)
+            .execute(&mut *conn)
             .await?;
+    }
+
+    Ok(())
+}
+
+async fn insufficient_stock(conn: &mut SqliteConnection, product_id: i32, requested: i64) -> RepositoryError {
+    let available = sqlx::query_scalar::<_, i64>("SELECT on_hand - reserved FROM inventory WHERE product_id = ?")
+        .bind(product_id)
+        .fetch_optional(&mut *conn)
+        .await;
+
+    match available {
+        Ok(available) => RepositoryError::InsufficientStock {
+            product_id,
+            requested,
+            available: available.unwrap_or(0),
+        },
+        Err(e) => RepositoryError::Database(e),
+    }
+}
+
+/// Writes an `order_events` row and queues a delivery for every active
+/// webhook subscribed to its type. Callers pass their open transaction so
+/// the event and its deliveries commit or roll back together with the
+/// change they describe.
+pub(crate) async fn record_event(
+    conn: &mut SqliteConnection,
+    order_id: i32,
+    event_type: OrderEventType,
+    actor: &str,
+    old_values: Option<Value>,
+    new_values: Option<Value>,
+) -> Result<(), RepositoryError> {
+    let mut event = OrderEvent {
+        id: 0,
+        order_id,
+        event_type,
+        actor: actor.to_string(),
+        old_values: old_values.map(Json),
+        new_values: new_values.map(Json),
+        created_at: Utc::now(),
+    };
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO order_events (order_id, event_type, actor, old_values, new_values, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#
+    )
+    .bind(order_id)
+    .bind(event_type)
+    .bind(actor)
+    .bind(&event.old_values)
+    .bind(&event.new_values)
+    .bind(event.created_at.to_rfc3339())
+    .execute(&mut *conn)
+    .await?;
+    event.id = result.last_insert_rowid();
+
+    let payload = serde_json::to_string(&event).unwrap_or_default();
+    sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries (webhook_id, event_id, event_type, order_id, payload, state, attempts, next_attempt_at, created_at)
+        SELECT id, ?, ?, ?, ?, 'pending', 0, ?, ?
+        FROM webhooks
+        WHERE active = 1
+          AND EXISTS (SELECT 1 FROM json_each(webhooks.event_types) WHERE json_each.value = ?)
+        "#
+    )
+    .bind(event.id)
+    .bind(event_type)
+    .bind(order_id)
+    .bind(payload)
+    .bind(event.created_at.to_rfc3339())
+    .bind(event.created_at.to_rfc3339())
+    .bind(event_type)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub(crate) fn snapshot(order: &Order) -> Value {
+    serde_json::to_value(order).unwrap_or(Value::Null)
+}
+
+/// Reduces two order snapshots to just the fields that differ, so update
+/// events record what changed rather than the whole row.
+pub(crate) fn changed_fields(before: &Value, after: &Value) -> (Value, Value) {
+    let mut old_values = Map::new();
+    let mut new_values = Map::new();
+
+    if let (Value::Object(before), Value::Object(after)) = (before, after) {
+        for (field, new_value) in after {
+            if field == "updated_at" || field == "version" {
+                continue;
+            }
+            let old_value = before.get(field).cloned().unwrap_or(Value::Null);
+            if &old_value != new_value {
+                old_values.insert(field.clone(), old_value);
+                new_values.insert(field.clone(), new_value.clone());
+            }
+        }
+    }
+
+    (Value::Object(old_values), Value::Object(new_values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customer_repository::{CustomerRepository, SqliteCustomerRepository};
+    use crate::database::test_pool;
+    use crate::models::{CreateProductRequest, CustomerQuery};
+    use crate::product_repository::{ProductRepository, SqliteProductRepository};
+
+    fn new_order() -> NewOrder {
+        NewOrder {
+            customer: OrderCustomer::New("Acme".to_string()),
+            items: vec![NewOrderItem {
+                product_id: None,
+                sku: None,
+                product_name: "Widget".to_string(),
+                quantity: 2,
+                unit_price: Decimal::new(250, 2),
+            }],
+        }
+    }
+
+    fn idempotency_key(principal: &str) -> IdempotencyKey {
+        IdempotencyKey {
+            principal: principal.to_string(),
+            key: "retry-1".to_string(),
+            fingerprint: "body".to_string(),
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn idempotency_keys_are_unique_per_principal() {
+        let pool = test_pool().await;
+        let repository = SqliteOrderRepository::new(pool.clone());
+
+        let first = repository
+            .create(new_order(), Some(&idempotency_key("api-key:1")), "test")
+            .await
+            .unwrap();
+        let other = repository
+            .create(new_order(), Some(&idempotency_key("api-key:2")), "test")
+            .await
+            .unwrap();
+        assert_ne!(first.id, other.id);
+
+        let error = repository
+            .create(new_order(), Some(&idempotency_key("api-key:1")), "test")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, RepositoryError::DuplicateIdempotencyKey(_)), "{error}");
 
-        Ok(result.rows_affected() > 0)
+        let record = repository
+            .find_idempotency_record("api-key:1", "retry-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.order_id, first.id);
+        assert!(repository.find_idempotency_record("jwt:someone", "retry-1").await.unwrap().is_none());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn bulk_writes_roll_back_or_keep_items_by_mode() {
+        let pool = test_pool().await;
+        let repository = SqliteOrderRepository::new(pool.clone());
+        let first = repository.create(new_order(), None, "test").await.unwrap();
+        let second = repository.create(new_order(), None, "test").await.unwrap();
+        let ids = [first.id, 9999, second.id];
+
+        let results = repository.delete_many(&ids, BulkMode::AllOrNothing, "test").await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(RepositoryError::Database(sqlx::Error::RowNotFound))));
+        assert!(repository.find_by_id(first.id, false).await.unwrap().is_some());
+
+        let results = repository.delete_many(&ids, BulkMode::PerItem, "test").await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok() && results[1].is_err() && results[2].is_ok());
+        assert!(repository.find_by_id(first.id, false).await.unwrap().is_none());
+        assert!(repository.find_by_id(second.id, false).await.unwrap().is_none());
+    }
+
+    async fn widget(pool: &DatabasePool, stock_on_hand: i64) -> NewOrder {
+        let product = SqliteProductRepository::new(pool.clone())
+            .create(CreateProductRequest {
+                sku: "WIDGET".to_string(),
+                name: "Widget".to_string(),
+                list_price: Decimal::new(250, 2),
+                active: None,
+                stock_on_hand: Some(stock_on_hand),
+            })
+            .await
+            .unwrap();
+
+        let mut order = new_order();
+        order.items[0].product_id = Some(product.id);
+        order.items[0].sku = Some(product.sku);
+        order
+    }
+
+    #[tokio::test]
+    async fn an_order_that_fails_on_stock_creates_no_customer() {
+        let pool = test_pool().await;
+        let repository = SqliteOrderRepository::new(pool.clone());
+        let customers = SqliteCustomerRepository::new(pool.clone());
+        let order = widget(&pool, 1).await;
+
+        let error = repository.create(order, None, "test").await.unwrap_err();
+        assert!(matches!(error, RepositoryError::InsufficientStock { .. }), "{error}");
+        assert!(customers.find_all(&CustomerQuery::default()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn an_all_or_nothing_batch_rolls_back_its_new_customers() {
+        let pool = test_pool().await;
+        let repository = SqliteOrderRepository::new(pool.clone());
+        let customers = SqliteCustomerRepository::new(pool.clone());
+        let existing = repository.create(new_order(), None, "test").await.unwrap();
+        let widget = widget(&pool, 2).await;
+
+        let mut orders = vec![widget.clone(), widget];
+        orders[0].customer = OrderCustomer::New("Globex".to_string());
+        orders[1].customer = OrderCustomer::New("Initech".to_string());
+
+        let results = repository.create_many(orders, BulkMode::AllOrNothing, "test").await.unwrap();
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(RepositoryError::InsufficientStock { .. })));
+
+        let names: Vec<String> = customers
+            .find_all(&CustomerQuery::default())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|customer| customer.name)
+            .collect();
+        assert_eq!(names, vec![existing.customer_name]);
+    }
+}
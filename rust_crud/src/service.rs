@@ -1,6 +1,18 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
 use validator::Validate;
-use crate::models::{Order, CreateOrderRequest, UpdateOrderRequest};
+use crate::customer_repository::CustomerRepository;
+use crate::models::{
+    Order, CreateOrderRequest, UpdateOrderRequest, OrderQuery, OrderStatus, OrderEvent, OrderCustomer, NewOrder, VersionMatch,
+    NewOrderItem, OrderChanges, OrderItemRequest, Product, ProductRef, IdempotencyKey,
+    PurgeQuery, PurgeResult, OrderStreamQuery, OrderStreamEvent, OrderCsvLine, OrderCsvRow, MAX_PAGE_LIMIT, BulkMode, BulkCreateRequest, BulkUpdateRequest, BulkDeleteRequest,
+};
+use crate::product_repository::ProductRepository;
 use crate::repository::{OrderRepository, RepositoryError};
 use crate::status_reporter::StatusReporter;
 
@@ -12,24 +24,103 @@ pub enum ServiceError {
     OrderNotFound { id: i32 },
     #[error("Validation error: {0}")]
     Validation(String),
+    #[error("Order {id} cannot move from {from} to {to}")]
+    InvalidStatusTransition { id: i32, from: OrderStatus, to: OrderStatus },
+    #[error("Customer not found with id: {id}")]
+    CustomerNotFound { id: i32 },
+    #[error("A customer named '{name}' already exists")]
+    DuplicateCustomer { name: String },
+    #[error("Customer {id} still has orders")]
+    CustomerHasOrders { id: i32 },
+    #[error("Product not found with id: {id}")]
+    ProductNotFound { id: i32 },
+    #[error("A product with SKU '{sku}' already exists")]
+    DuplicateProduct { sku: String },
+    #[error("Product {id} appears on orders; deactivate it instead")]
+    ProductInUse { id: i32 },
+    #[error("Insufficient stock for product {product_id}: requested {requested}, available {available}")]
+    InsufficientStock { product_id: i32, requested: i64, available: i64 },
+    #[error("Stock on hand {on_hand} for product {product_id} is below the {reserved} units reserved by open orders")]
+    StockBelowReserved { product_id: i32, on_hand: i64, reserved: i64 },
+    #[error("Order {id} is {status}; its items can no longer be changed")]
+    OrderLocked { id: i32, status: OrderStatus },
+    #[error("Order {id} is at version {actual}, which the request did not expect")]
+    PreconditionFailed { id: i32, actual: i64 },
+    #[error("Order {id} was modified concurrently; fetch it again and retry")]
+    ConcurrentModification { id: i32 },
+    #[error("Idempotency key '{key}' was already used with a different request body")]
+    IdempotencyKeyReused { key: String },
+    #[error("Order {id} is not deleted")]
+    OrderNotDeleted { id: i32 },
+    #[error("Not applied: item {index} failed and the batch was rolled back")]
+    BulkRolledBack { index: usize },
+    #[error("Webhook not found with id: {id}")]
+    WebhookNotFound { id: i32 },
+    #[error("Webhook {webhook_id} has no delivery with id {id}")]
+    WebhookDeliveryNotFound { webhook_id: i32, id: i64 },
+    #[error("API key not found with id: {id}")]
+    ApiKeyNotFound { id: i64 },
+    #[error("Export failed: {0}")]
+    Export(String),
     #[error("Status reporting failed: {0}")]
     StatusReporting(String),
 }
 
 pub struct OrderService {
-    repository: Arc<OrderRepository>,
-    status_reporter: Arc<StatusReporter>,
+    repository: Arc<dyn OrderRepository>,
+    customers: Arc<dyn CustomerRepository>,
+    products: Arc<dyn ProductRepository>,
+    status_reporter: Arc<dyn StatusReporter>,
+    allow_price_override: bool,
+    idempotency_window: Duration,
+    deleted_retention: Duration,
+    /// Bumped after every write so order stream subscribers look for new
+    /// events
+    changes: watch::Sender<u64>,
 }
 
+/// Events read per query when catching up an order stream.
+const STREAM_BATCH_SIZE: i64 = 100;
+
+/// Most data rows accepted in one CSV import.
+const MAX_IMPORT_ROWS: usize = 5000;
+
+/// Longest `Idempotency-Key` accepted.
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
 impl OrderService {
-    pub fn new(repository: Arc<OrderRepository>, status_reporter: Arc<StatusReporter>) -> Self {
+    pub fn new(
+        repository: Arc<dyn OrderRepository>,
+        customers: Arc<dyn CustomerRepository>,
+        products: Arc<dyn ProductRepository>,
+        status_reporter: Arc<dyn StatusReporter>,
+        allow_price_override: bool,
+        idempotency_window: Duration,
+        deleted_retention: Duration,
+    ) -> Self {
         Self {
             repository,
+            customers,
+            products,
             status_reporter,
+            allow_price_override,
+            idempotency_window,
+            deleted_retention,
+            changes: watch::channel(0).0,
         }
     }
 
-    pub async fn create_order(&self, request: CreateOrderRequest) -> Result<Order, ServiceError> {
+    /// Creates an order. When the client sends an `Idempotency-Key` that it
+    /// already used for the same body within the window, the original order
+    /// is returned instead and the flag in the result is `true`. Keys are
+    /// scoped to `principal`, the id of the caller's credential.
+    pub async fn create_order(
+        &self,
+        request: CreateOrderRequest,
+        idempotency_key: Option<String>,
+        principal: &str,
+        actor: &str,
+    ) -> Result<(Order, bool), ServiceError> {
         // Validate the request
         if let Err(validation_errors) = request.validate() {
             let error_msg = format!("Validation failed: {:?}", validation_errors);
@@ -39,12 +130,70 @@ impl OrderService {
             return Err(ServiceError::Validation(error_msg));
         }
 
-        match self.repository.create(request).await {
+        let idempotency_key = match idempotency_key {
+            Some(key) => match self.check_idempotency_key(principal, key, &request).await {
+                Ok(IdempotencyCheck::Replay(order)) => {
+                    self.status_reporter
+                        .report_success("create_order", Some(order.id))
+                        .await;
+                    return Ok((order, true));
+                }
+                Ok(IdempotencyCheck::New(idempotency_key)) => Some(idempotency_key),
+                Err(error) => {
+                    self.status_reporter
+                        .report_failure("create_order", &error.to_string(), None)
+                        .await;
+                    return Err(error);
+                }
+            },
+            None => None,
+        };
+
+        let new_order = match self.prepare_order(&request).await {
+            Ok(new_order) => new_order,
+            Err(error) => {
+                self.status_reporter
+                    .report_failure("create_order", &error.to_string(), None)
+                    .await;
+                return Err(error);
+            }
+        };
+
+        match self.repository.create(new_order, idempotency_key.as_ref(), actor).await {
             Ok(order) => {
+                self.publish_changes();
                 self.status_reporter
                     .report_success("create_order", Some(order.id))
                     .await;
-                Ok(order)
+                Ok((order, false))
+            }
+            Err(RepositoryError::DuplicateIdempotencyKey(key)) => {
+                // A concurrent retry with the same key committed first
+                let replay = match self.find_replay(principal, &key, &fingerprint(&request)).await {
+                    Ok(Some(order)) => Ok((order, true)),
+                    Ok(None) => Err(ServiceError::Repository(RepositoryError::DuplicateIdempotencyKey(key))),
+                    Err(error) => Err(error),
+                };
+                match &replay {
+                    Ok((order, _)) => {
+                        self.status_reporter
+                            .report_success("create_order", Some(order.id))
+                            .await;
+                    }
+                    Err(error) => {
+                        self.status_reporter
+                            .report_failure("create_order", &error.to_string(), None)
+                            .await;
+                    }
+                }
+                replay
+            }
+            Err(RepositoryError::InsufficientStock { product_id, requested, available }) => {
+                let error = ServiceError::InsufficientStock { product_id, requested, available };
+                self.status_reporter
+                    .report_failure("create_order", &error.to_string(), None)
+                    .await;
+                Err(error)
             }
             Err(e) => {
                 let error_msg = format!("Failed to create order: {}", e);
@@ -56,13 +205,22 @@ impl OrderService {
         }
     }
 
-    pub async fn get_orders(&self) -> Result<Vec<Order>, ServiceError> {
-        match self.repository.find_all().await {
-            Ok(orders) => {
+    pub async fn get_orders(&self, query: &OrderQuery) -> Result<(Vec<Order>, i64), ServiceError> {
+        // Validate the query
+        if let Err(validation_errors) = query.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("get_orders", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        match self.repository.find_all(query).await {
+            Ok(page) => {
                 self.status_reporter
                     .report_success("get_orders", None)
                     .await;
-                Ok(orders)
+                Ok(page)
             }
             Err(e) => {
                 let error_msg = format!("Failed to get orders: {}", e);
@@ -74,8 +232,8 @@ impl OrderService {
         }
     }
 
-    pub async fn get_order(&self, id: i32) -> Result<Order, ServiceError> {
-        match self.repository.find_by_id(id).await {
+    pub async fn get_order(&self, id: i32, include_deleted: bool) -> Result<Order, ServiceError> {
+        match self.repository.find_by_id(id, include_deleted).await {
             Ok(Some(order)) => {
                 self.status_reporter
                     .report_success("get_order", Some(id))
@@ -99,43 +257,186 @@ impl OrderService {
         }
     }
 
-    pub async fn update_order(&self, id: i32, request: UpdateOrderRequest) -> Result<Order, ServiceError> {
+    /// `if_match` holds the versions from the client's `If-Match` header.
+    pub async fn update_order(
+        &self,
+        id: i32,
+        request: UpdateOrderRequest,
+        if_match: &VersionMatch,
+        actor: &str,
+    ) -> Result<Order, ServiceError> {
+        self.apply_update("update_order", id, request, if_match, actor).await
+    }
+
+    /// Moves an order to `status` on behalf of one of the action endpoints
+    /// (`/process`, `/ship`, `/deliver`, `/cancel`). No endpoint moves an
+    /// order back to `Pending`, so that is reported under its own name.
+    pub async fn change_status(&self, id: i32, status: OrderStatus, actor: &str) -> Result<Order, ServiceError> {
+        let operation = match status {
+            OrderStatus::Pending => "change_status",
+            OrderStatus::Processing => "process_order",
+            OrderStatus::Shipped => "ship_order",
+            OrderStatus::Delivered => "deliver_order",
+            OrderStatus::Cancelled => "cancel_order",
+        };
+
+        let request = UpdateOrderRequest {
+            status: Some(status),
+            ..Default::default()
+        };
+
+        self.apply_update(operation, id, request, &VersionMatch::Any, actor).await
+    }
+
+    /// Replays an earlier create for `key`, or returns the key to store
+    /// with the new order.
+    async fn check_idempotency_key(
+        &self,
+        principal: &str,
+        key: String,
+        request: &CreateOrderRequest,
+    ) -> Result<IdempotencyCheck, ServiceError> {
+        if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+            return Err(ServiceError::Validation(format!(
+                "Idempotency-Key must be between 1 and {} characters",
+                MAX_IDEMPOTENCY_KEY_LENGTH
+            )));
+        }
+
+        let fingerprint = fingerprint(request);
+        if let Some(order) = self.find_replay(principal, &key, &fingerprint).await? {
+            return Ok(IdempotencyCheck::Replay(order));
+        }
+
+        let window = chrono::Duration::from_std(self.idempotency_window)
+            .unwrap_or_else(|_| chrono::Duration::days(1));
+
+        Ok(IdempotencyCheck::New(IdempotencyKey {
+            principal: principal.to_string(),
+            key,
+            fingerprint,
+            expires_at: Utc::now() + window,
+        }))
+    }
+
+    /// Looks up the order `principal` stored for `key`. The same key with a
+    /// different body is a client bug rather than a retry.
+    async fn find_replay(&self, principal: &str, key: &str, fingerprint: &str) -> Result<Option<Order>, ServiceError> {
+        match self.repository.find_idempotency_record(principal, key).await? {
+            Some(record) if record.fingerprint == fingerprint => Ok(Some(record.response.0)),
+            Some(_) => Err(ServiceError::IdempotencyKeyReused { key: key.to_string() }),
+            None => Ok(None),
+        }
+    }
+
+    async fn apply_update(
+        &self,
+        operation: &str,
+        id: i32,
+        request: UpdateOrderRequest,
+        if_match: &VersionMatch,
+        actor: &str,
+    ) -> Result<Order, ServiceError> {
         // Validate the request
         if let Err(validation_errors) = request.validate() {
             let error_msg = format!("Validation failed: {:?}", validation_errors);
             self.status_reporter
-                .report_failure("update_order", &error_msg, Some(id))
+                .report_failure(operation, &error_msg, Some(id))
                 .await;
             return Err(ServiceError::Validation(error_msg));
         }
 
-        match self.repository.update(id, request).await {
+        let current = match self.repository.find_by_id(id, false).await {
+            Ok(Some(order)) => order,
+            Ok(None) => {
+                let error_msg = format!("Order not found with id: {}", id);
+                self.status_reporter
+                    .report_failure(operation, &error_msg, Some(id))
+                    .await;
+                return Err(ServiceError::OrderNotFound { id });
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to update order {}: {}", id, e);
+                self.status_reporter
+                    .report_failure(operation, &error_msg, Some(id))
+                    .await;
+                return Err(ServiceError::Repository(e));
+            }
+        };
+
+        if !if_match.matches(current.version) {
+            let error = ServiceError::PreconditionFailed { id, actual: current.version };
+            self.status_reporter
+                .report_failure(operation, &error.to_string(), Some(id))
+                .await;
+            return Err(error);
+        }
+
+        let changes = match self.prepare_changes(&current, request).await {
+            Ok(changes) => changes,
+            Err(error) => {
+                self.status_reporter
+                    .report_failure(operation, &error.to_string(), Some(id))
+                    .await;
+                return Err(error);
+            }
+        };
+
+        // The changes were worked out against `current`, so only write them
+        // if nobody else has updated the order since it was read
+        match self.repository.update(id, changes, Some(current.version), actor).await {
             Ok(order) => {
+                self.publish_changes();
                 self.status_reporter
-                    .report_success("update_order", Some(id))
+                    .report_success(operation, Some(id))
                     .await;
                 Ok(order)
             }
             Err(RepositoryError::Database(sqlx::Error::RowNotFound)) => {
                 let error_msg = format!("Order not found with id: {}", id);
                 self.status_reporter
-                    .report_failure("update_order", &error_msg, Some(id))
+                    .report_failure(operation, &error_msg, Some(id))
                     .await;
                 Err(ServiceError::OrderNotFound { id })
             }
+            Err(RepositoryError::InsufficientStock { product_id, requested, available }) => {
+                let error = ServiceError::InsufficientStock { product_id, requested, available };
+                self.status_reporter
+                    .report_failure(operation, &error.to_string(), Some(id))
+                    .await;
+                Err(error)
+            }
+            Err(RepositoryError::VersionConflict { actual, .. }) => {
+                let error = version_conflict_error(id, if_match != &VersionMatch::Any, actual);
+                self.status_reporter
+                    .report_failure(operation, &error.to_string(), Some(id))
+                    .await;
+                Err(error)
+            }
             Err(e) => {
                 let error_msg = format!("Failed to update order {}: {}", id, e);
                 self.status_reporter
-                    .report_failure("update_order", &error_msg, Some(id))
+                    .report_failure(operation, &error_msg, Some(id))
                     .await;
                 Err(ServiceError::Repository(e))
             }
         }
     }
 
-    pub async fn delete_order(&self, id: i32) -> Result<(), ServiceError> {
-        match self.repository.delete(id).await {
+    pub async fn delete_order(&self, id: i32, if_match: &VersionMatch, actor: &str) -> Result<(), ServiceError> {
+        let expected_version = match self.expected_version(id, if_match).await {
+            Ok(version) => version,
+            Err(error) => {
+                self.status_reporter
+                    .report_failure("delete_order", &error.to_string(), Some(id))
+                    .await;
+                return Err(error);
+            }
+        };
+
+        match self.repository.delete(id, expected_version, actor).await {
             Ok(true) => {
+                self.publish_changes();
                 self.status_reporter
                     .report_success("delete_order", Some(id))
                     .await;
@@ -148,6 +449,13 @@ impl OrderService {
                     .await;
                 Err(ServiceError::OrderNotFound { id })
             }
+            Err(RepositoryError::VersionConflict { actual, .. }) => {
+                let error = version_conflict_error(id, expected_version.is_some(), actual);
+                self.status_reporter
+                    .report_failure("delete_order", &error.to_string(), Some(id))
+                    .await;
+                Err(error)
+            }
             Err(e) => {
                 let error_msg = format!("Failed to delete order {}: {}", id, e);
                 self.status_reporter
@@ -157,4 +465,1218 @@ impl OrderService {
             }
         }
     }
-}
\ No newline at end of file
+
+    /// The current version of the order if `if_match` lists it, for the
+    /// repository to check again as it writes. `None` when any version will do.
+    async fn expected_version(&self, id: i32, if_match: &VersionMatch) -> Result<Option<i64>, ServiceError> {
+        if *if_match == VersionMatch::Any {
+            return Ok(None);
+        }
+
+        let current = self.repository.find_by_id(id, false).await?
+            .ok_or(ServiceError::OrderNotFound { id })?;
+        if !if_match.matches(current.version) {
+            return Err(ServiceError::PreconditionFailed { id, actual: current.version });
+        }
+        Ok(Some(current.version))
+    }
+
+    pub async fn restore_order(&self, id: i32, actor: &str) -> Result<Order, ServiceError> {
+        match self.repository.restore(id, actor).await {
+            Ok(Some(order)) => {
+                self.publish_changes();
+                self.status_reporter
+                    .report_success("restore_order", Some(id))
+                    .await;
+                Ok(order)
+            }
+            Ok(None) => {
+                let error = ServiceError::OrderNotDeleted { id };
+                self.status_reporter
+                    .report_failure("restore_order", &error.to_string(), Some(id))
+                    .await;
+                Err(error)
+            }
+            Err(RepositoryError::Database(sqlx::Error::RowNotFound)) => {
+                let error_msg = format!("Order not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("restore_order", &error_msg, Some(id))
+                    .await;
+                Err(ServiceError::OrderNotFound { id })
+            }
+            Err(RepositoryError::InsufficientStock { product_id, requested, available }) => {
+                let error = ServiceError::InsufficientStock { product_id, requested, available };
+                self.status_reporter
+                    .report_failure("restore_order", &error.to_string(), Some(id))
+                    .await;
+                Err(error)
+            }
+            Err(RepositoryError::VersionConflict { .. }) => {
+                let error = ServiceError::ConcurrentModification { id };
+                self.status_reporter
+                    .report_failure("restore_order", &error.to_string(), Some(id))
+                    .await;
+                Err(error)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to restore order {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("restore_order", &error_msg, Some(id))
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// Creates many orders in one transaction. Each item is validated and
+    /// priced like a single create, but only one status report is sent for
+    /// the whole batch.
+    pub async fn bulk_create_orders(
+        &self,
+        request: BulkCreateRequest,
+        actor: &str,
+    ) -> Result<BulkOutcome<Order>, ServiceError> {
+        let operation = "bulk_create_orders";
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure(operation, &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let mut prepared = Vec::with_capacity(request.orders.len());
+        let mut new_orders = Vec::new();
+        for order in &request.orders {
+            match self.prepare_create(order).await {
+                Ok(new_order) => {
+                    new_orders.push(new_order);
+                    prepared.push(None);
+                }
+                Err(error) => prepared.push(Some(error)),
+            }
+        }
+
+        let mut written = Vec::new();
+        if should_write(request.mode, &prepared) {
+            match self.repository.create_many(new_orders, request.mode, actor).await {
+                Ok(results) => {
+                    written = results
+                        .into_iter()
+                        .map(|result| result.map_err(|e| write_error(None, None, e)))
+                        .collect();
+                }
+                Err(e) => return Err(self.report_batch_error(operation, e).await),
+            }
+        }
+
+        let ids = vec![None; prepared.len()];
+        let mut outcome = BulkOutcome::assemble(request.mode, ids, prepared, written);
+        for item in &mut outcome.items {
+            if let Ok(order) = &item.result {
+                item.id = Some(order.id);
+            }
+        }
+
+        self.report_batch(operation, &outcome).await;
+        Ok(outcome)
+    }
+
+    /// Applies many updates in one transaction. An item's `version` plays
+    /// the part of `If-Match` on a single update.
+    pub async fn bulk_update_orders(
+        &self,
+        request: BulkUpdateRequest,
+        actor: &str,
+    ) -> Result<BulkOutcome<Order>, ServiceError> {
+        let operation = "bulk_update_orders";
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure(operation, &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let ids: Vec<Option<i32>> = request.updates.iter().map(|update| Some(update.id)).collect();
+        let mut prepared = Vec::with_capacity(request.updates.len());
+        let mut updates = Vec::new();
+        let mut if_matches = Vec::new();
+        for update in request.updates {
+            match self.prepare_update(update.id, update.changes, update.version).await {
+                Ok((changes, version)) => {
+                    updates.push((update.id, changes, Some(version)));
+                    if_matches.push((update.id, update.version));
+                    prepared.push(None);
+                }
+                Err(error) => prepared.push(Some(error)),
+            }
+        }
+
+        let mut written = Vec::new();
+        if should_write(request.mode, &prepared) {
+            match self.repository.update_many(updates, request.mode, actor).await {
+                Ok(results) => {
+                    written = results
+                        .into_iter()
+                        .zip(if_matches)
+                        .map(|(result, (id, if_match))| result.map_err(|e| write_error(Some(id), if_match, e)))
+                        .collect();
+                }
+                Err(e) => return Err(self.report_batch_error(operation, e).await),
+            }
+        }
+
+        let outcome = BulkOutcome::assemble(request.mode, ids, prepared, written);
+        self.report_batch(operation, &outcome).await;
+        Ok(outcome)
+    }
+
+    /// Soft-deletes many orders in one transaction.
+    pub async fn bulk_delete_orders(
+        &self,
+        request: BulkDeleteRequest,
+        actor: &str,
+    ) -> Result<BulkOutcome<()>, ServiceError> {
+        let operation = "bulk_delete_orders";
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure(operation, &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let written = match self.repository.delete_many(&request.ids, request.mode, actor).await {
+            Ok(results) => results
+                .into_iter()
+                .zip(&request.ids)
+                .map(|(result, &id)| result.map_err(|e| write_error(Some(id), None, e)))
+                .collect(),
+            Err(e) => return Err(self.report_batch_error(operation, e).await),
+        };
+
+        let ids = request.ids.iter().copied().map(Some).collect();
+        let prepared = (0..request.ids.len()).map(|_| None).collect();
+        let outcome = BulkOutcome::assemble(request.mode, ids, prepared, written);
+
+        self.report_batch(operation, &outcome).await;
+        Ok(outcome)
+    }
+
+    /// Writes every order matching the list filters as CSV, one row per line
+    /// item. `limit` and `offset` are ignored: the export is never paged.
+    pub async fn export_orders(&self, query: &OrderQuery) -> Result<String, ServiceError> {
+        // Validate the query
+        if let Err(validation_errors) = query.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("export_orders", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let mut page = OrderQuery {
+            limit: Some(MAX_PAGE_LIMIT),
+            ..query.clone()
+        }
+        .with_offset(0);
+
+        loop {
+            let orders = match self.repository.find_all(&page).await {
+                Ok((orders, _)) => orders,
+                Err(e) => {
+                    let error_msg = format!("Failed to export orders: {}", e);
+                    self.status_reporter
+                        .report_failure("export_orders", &error_msg, None)
+                        .await;
+                    return Err(ServiceError::Repository(e));
+                }
+            };
+
+            for order in &orders {
+                for item in &order.items {
+                    if let Err(e) = writer.serialize(OrderCsvLine::new(order, item)) {
+                        return Err(self.report_export_error(e.to_string()).await);
+                    }
+                }
+            }
+
+            if (orders.len() as i64) < page.limit() {
+                break;
+            }
+            page = page.with_offset(page.offset() + page.limit());
+        }
+
+        let csv = match writer.into_inner() {
+            Ok(bytes) => String::from_utf8(bytes).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match csv {
+            Ok(csv) => {
+                self.status_reporter
+                    .report_success("export_orders", None)
+                    .await;
+                Ok(csv)
+            }
+            Err(error_msg) => Err(self.report_export_error(error_msg).await),
+        }
+    }
+
+    async fn report_export_error(&self, error_msg: String) -> ServiceError {
+        self.status_reporter
+            .report_failure("export_orders", &error_msg, None)
+            .await;
+        ServiceError::Export(error_msg)
+    }
+
+    /// Creates one single-item order per CSV data row. Every row is checked
+    /// with the `CreateOrderRequest` rules and a bad row is reported without
+    /// stopping the rest; the good rows are written in one transaction.
+    pub async fn import_orders(&self, data: &str, actor: &str) -> Result<ImportOutcome, ServiceError> {
+        let operation = "import_orders";
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data.as_bytes());
+
+        let headers = match reader.headers() {
+            Ok(headers) => headers.clone(),
+            Err(e) => {
+                let error = ServiceError::Validation(format!("Invalid CSV header: {}", e));
+                self.status_reporter
+                    .report_failure(operation, &error.to_string(), None)
+                    .await;
+                return Err(error);
+            }
+        };
+
+        let mut lines = Vec::new();
+        let mut prepared = Vec::new();
+        let mut new_orders = Vec::new();
+        for (index, record) in reader.records().enumerate() {
+            if index == MAX_IMPORT_ROWS {
+                let error = ServiceError::Validation(format!("CSV has more than {} rows", MAX_IMPORT_ROWS));
+                self.status_reporter
+                    .report_failure(operation, &error.to_string(), None)
+                    .await;
+                return Err(error);
+            }
+
+            // The header is line 1, so the first data row is line 2 unless
+            // quoted fields span several lines
+            let fallback_line = index as u64 + 2;
+            let (line, row) = match record {
+                Ok(record) => (
+                    record.position().map_or(fallback_line, |position| position.line()),
+                    record.deserialize::<OrderCsvRow>(Some(&headers)),
+                ),
+                Err(e) => (e.position().map_or(fallback_line, |position| position.line()), Err(e)),
+            };
+            lines.push(line);
+
+            let request = match row {
+                Ok(row) => CreateOrderRequest::from(row),
+                Err(e) => {
+                    prepared.push(Some(ServiceError::Validation(format!("Invalid row: {}", e))));
+                    continue;
+                }
+            };
+
+            match self.prepare_create(&request).await {
+                Ok(new_order) => {
+                    new_orders.push(new_order);
+                    prepared.push(None);
+                }
+                Err(error) => prepared.push(Some(error)),
+            }
+        }
+
+        if prepared.is_empty() {
+            let error = ServiceError::Validation("CSV has no data rows".to_string());
+            self.status_reporter
+                .report_failure(operation, &error.to_string(), None)
+                .await;
+            return Err(error);
+        }
+
+        let mut written = Vec::new();
+        if should_write(BulkMode::PerItem, &prepared) {
+            match self.repository.create_many(new_orders, BulkMode::PerItem, actor).await {
+                Ok(results) => {
+                    written = results
+                        .into_iter()
+                        .map(|result| result.map_err(|e| write_error(None, None, e)))
+                        .collect();
+                }
+                Err(e) => return Err(self.report_batch_error(operation, e).await),
+            }
+        }
+
+        let ids = vec![None; prepared.len()];
+        let mut orders = BulkOutcome::assemble(BulkMode::PerItem, ids, prepared, written);
+        for item in &mut orders.items {
+            if let Ok(order) = &item.result {
+                item.id = Some(order.id);
+            }
+        }
+
+        self.report_batch(operation, &orders).await;
+        Ok(ImportOutcome { lines, orders })
+    }
+
+    /// Sends the single status report for a bulk operation, and wakes order
+    /// stream subscribers if anything was written.
+    async fn report_batch<T>(&self, operation: &str, outcome: &BulkOutcome<T>) {
+        let total = outcome.items.len();
+        let failed = outcome.failed();
+
+        if outcome.committed && failed < total {
+            self.publish_changes();
+        }
+
+        if failed == 0 {
+            let details = format!("{} of {} items succeeded", total, total);
+            self.status_reporter
+                .report_status(operation, true, Some(details), None)
+                .await;
+        } else {
+            let rolled_back = if outcome.committed { "" } else { "; nothing was committed" };
+            let details = format!("{} of {} items failed{}", failed, total, rolled_back);
+            self.status_reporter
+                .report_status(operation, false, Some(details), None)
+                .await;
+        }
+    }
+
+    async fn report_batch_error(&self, operation: &str, e: RepositoryError) -> ServiceError {
+        let error_msg = format!("Failed to run {}: {}", operation, e);
+        self.status_reporter
+            .report_failure(operation, &error_msg, None)
+            .await;
+        ServiceError::Repository(e)
+    }
+
+    /// Permanently removes orders that were soft-deleted more than
+    /// `older_than_days` ago, or the configured retention period if `None`.
+    pub async fn purge_deleted_orders(&self, query: &PurgeQuery) -> Result<PurgeResult, ServiceError> {
+        // Validate the query
+        if let Err(validation_errors) = query.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("purge_orders", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        let retention = match query.older_than_days {
+            Some(days) => chrono::Duration::days(days),
+            None => chrono::Duration::from_std(self.deleted_retention)
+                .unwrap_or_else(|_| chrono::Duration::days(30)),
+        };
+        let deleted_before = Utc::now() - retention;
+
+        match self.repository.purge_deleted(deleted_before).await {
+            Ok(purged) => {
+                tracing::info!("Purged {} orders deleted before {}", purged, deleted_before);
+                self.status_reporter
+                    .report_success("purge_orders", None)
+                    .await;
+                Ok(PurgeResult { purged, deleted_before })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to purge deleted orders: {}", e);
+                self.status_reporter
+                    .report_failure("purge_orders", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// Starts an order stream: validates the filters and returns the event
+    /// id to stream after. That is `last_event_id` when resuming, otherwise
+    /// the newest event so only new changes are sent.
+    pub async fn open_order_stream(
+        &self,
+        query: &OrderStreamQuery,
+        last_event_id: Option<i64>,
+    ) -> Result<(i64, watch::Receiver<u64>), ServiceError> {
+        // Validate the query
+        if let Err(validation_errors) = query.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("stream_orders", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        // Subscribe before reading the cursor so no write can slip between
+        let changes = self.changes.subscribe();
+
+        let cursor = match last_event_id.or(query.last_event_id) {
+            Some(last_event_id) => Ok(last_event_id),
+            None => self.repository.latest_event_id().await,
+        };
+
+        match cursor {
+            Ok(cursor) => {
+                self.status_reporter
+                    .report_success("stream_orders", None)
+                    .await;
+                Ok((cursor, changes))
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to open order stream: {}", e);
+                self.status_reporter
+                    .report_failure("stream_orders", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// Reads the next batch of events after `cursor` and keeps those that
+    /// match the stream's filters. The returned cursor moves past
+    /// filtered-out events too. Not status-reported: it runs on every change
+    /// for every open stream.
+    pub async fn next_stream_events(
+        &self,
+        cursor: i64,
+        query: &OrderStreamQuery,
+    ) -> Result<StreamBatch, ServiceError> {
+        let events = self.repository.find_events_after(cursor, STREAM_BATCH_SIZE).await?;
+        let more = events.len() as i64 >= STREAM_BATCH_SIZE;
+        let next_cursor = events.last().map_or(cursor, |event| event.id);
+
+        let mut orders: HashMap<i32, Option<Order>> = HashMap::new();
+        let mut matched = Vec::new();
+        for event in events {
+            let order = match orders.get(&event.order_id) {
+                Some(order) => order.clone(),
+                None => {
+                    let order = self.repository.find_by_id(event.order_id, true).await?;
+                    orders.insert(event.order_id, order.clone());
+                    order
+                }
+            };
+
+            if stream_matches(query, &event, order.as_ref()) {
+                matched.push(OrderStreamEvent { event, order });
+            }
+        }
+
+        Ok(StreamBatch { events: matched, cursor: next_cursor, more })
+    }
+
+    fn publish_changes(&self) {
+        self.changes.send_modify(|generation| *generation = generation.wrapping_add(1));
+    }
+
+    pub async fn get_order_history(&self, id: i32) -> Result<Vec<OrderEvent>, ServiceError> {
+        let events = match self.repository.find_events(id).await {
+            Ok(events) => events,
+            Err(e) => {
+                let error_msg = format!("Failed to get history for order {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("get_order_history", &error_msg, Some(id))
+                    .await;
+                return Err(ServiceError::Repository(e));
+            }
+        };
+
+        // Orders created before the audit trail existed have no events yet
+        if events.is_empty() {
+            match self.repository.find_by_id(id, true).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    let error_msg = format!("Order not found with id: {}", id);
+                    self.status_reporter
+                        .report_failure("get_order_history", &error_msg, Some(id))
+                        .await;
+                    return Err(ServiceError::OrderNotFound { id });
+                }
+                Err(e) => {
+                    let error_msg = format!("Failed to get history for order {}: {}", id, e);
+                    self.status_reporter
+                        .report_failure("get_order_history", &error_msg, Some(id))
+                        .await;
+                    return Err(ServiceError::Repository(e));
+                }
+            }
+        }
+
+        self.status_reporter
+            .report_success("get_order_history", Some(id))
+            .await;
+        Ok(events)
+    }
+
+    /// Validates and prepares one item of a bulk create, without reporting.
+    async fn prepare_create(&self, request: &CreateOrderRequest) -> Result<NewOrder, ServiceError> {
+        if let Err(validation_errors) = request.validate() {
+            return Err(ServiceError::Validation(format!("Validation failed: {:?}", validation_errors)));
+        }
+
+        self.prepare_order(request).await
+    }
+
+    /// Validates and prepares one item of a bulk update, without reporting.
+    /// Returns the changes and the version they were worked out against.
+    async fn prepare_update(
+        &self,
+        id: i32,
+        request: UpdateOrderRequest,
+        if_match: Option<i64>,
+    ) -> Result<(OrderChanges, i64), ServiceError> {
+        if let Err(validation_errors) = request.validate() {
+            return Err(ServiceError::Validation(format!("Validation failed: {:?}", validation_errors)));
+        }
+
+        let current = self.repository.find_by_id(id, false).await?
+            .ok_or(ServiceError::OrderNotFound { id })?;
+        if if_match.is_some_and(|expected| expected != current.version) {
+            return Err(ServiceError::PreconditionFailed { id, actual: current.version });
+        }
+
+        let changes = self.prepare_changes(&current, request).await?;
+        Ok((changes, current.version))
+    }
+
+    /// Resolves the customer and prices every line of a validated create request.
+    async fn prepare_order(&self, request: &CreateOrderRequest) -> Result<NewOrder, ServiceError> {
+        let customer = self.resolve_customer(request.customer_id, request.customer_name.as_deref()).await?;
+
+        let mut items = Vec::new();
+        for line in request.line_items() {
+            items.push(self.price_item(&line).await?);
+        }
+
+        Ok(NewOrder { customer, items })
+    }
+
+    /// Checks a validated update against the current order and resolves it
+    /// into the changes to write.
+    async fn prepare_changes(&self, current: &Order, request: UpdateOrderRequest) -> Result<OrderChanges, ServiceError> {
+        let mut changes = OrderChanges {
+            status: request.status,
+            ..Default::default()
+        };
+
+        // Enforce the status lifecycle before touching the row
+        if let Some(next) = request.status {
+            if !current.status.can_transition_to(next) {
+                return Err(ServiceError::InvalidStatusTransition {
+                    id: current.id,
+                    from: current.status,
+                    to: next,
+                });
+            }
+        }
+
+        // Shipped stock has left the building, so only open orders take item edits
+        let editable = matches!(current.status, OrderStatus::Pending | OrderStatus::Processing);
+        if !editable && (request.items.is_some() || request.has_single_item_fields()) {
+            return Err(ServiceError::OrderLocked {
+                id: current.id,
+                status: current.status,
+            });
+        }
+
+        // Point the order at the resolved customer and its canonical name
+        if request.customer_id.is_some() || request.customer_name.is_some() {
+            changes.customer = Some(self.resolve_customer(request.customer_id, request.customer_name.as_deref()).await?);
+        }
+
+        if let Some(lines) = &request.items {
+            let mut items = Vec::new();
+            for line in lines {
+                items.push(self.price_item(line).await?);
+            }
+            changes.items = Some(items);
+        } else if request.has_single_item_fields() {
+            let mut items: Vec<NewOrderItem> = current.items.iter().map(NewOrderItem::from).collect();
+            if let Some(first) = items.first_mut() {
+                let quantity = request.quantity.unwrap_or(first.quantity);
+
+                if request.sku.is_some() || request.product_name.is_some() {
+                    // A different product: price it from the catalog afresh
+                    *first = self.price_item(&OrderItemRequest {
+                        sku: request.sku.clone(),
+                        product_name: request.product_name.clone(),
+                        quantity,
+                        unit_price: request.unit_price,
+                    }).await?;
+                } else if let Some(unit_price) = request.unit_price {
+                    // Same product at a new price: the catalog still has the final say
+                    match first.sku.clone() {
+                        Some(sku) => {
+                            *first = self.price_item(&OrderItemRequest {
+                                sku: Some(sku),
+                                product_name: None,
+                                quantity,
+                                unit_price: Some(unit_price),
+                            }).await?;
+                        }
+                        None if self.allow_price_override => {
+                            first.quantity = quantity;
+                            first.unit_price = unit_price;
+                        }
+                        None => {
+                            return Err(ServiceError::Validation(
+                                "Price overrides are not permitted".to_string(),
+                            ));
+                        }
+                    }
+                } else {
+                    // Quantity only: keep the price the line was sold at
+                    first.quantity = quantity;
+                }
+            }
+            changes.items = Some(items);
+        }
+
+        Ok(changes)
+    }
+
+    /// Prices a line from the catalog. A client-supplied `unit_price` that
+    /// differs from the list price is only accepted when overrides are enabled.
+    async fn price_item(&self, line: &OrderItemRequest) -> Result<NewOrderItem, ServiceError> {
+        let product = self.find_product(&line.product_ref()).await?;
+
+        if !product.active {
+            return Err(ServiceError::Validation(format!(
+                "Product with SKU '{}' is not available",
+                product.sku
+            )));
+        }
+
+        let unit_price = match line.unit_price {
+            Some(price) if price != product.list_price => {
+                if !self.allow_price_override {
+                    return Err(ServiceError::Validation(format!(
+                        "Unit price {} does not match the catalog price {} for SKU '{}'",
+                        price, product.list_price, product.sku
+                    )));
+                }
+                price
+            }
+            _ => product.list_price,
+        };
+
+        Ok(NewOrderItem {
+            product_id: Some(product.id),
+            sku: Some(product.sku),
+            product_name: product.name,
+            quantity: line.quantity,
+            unit_price,
+        })
+    }
+
+    async fn find_product(&self, reference: &ProductRef) -> Result<Product, ServiceError> {
+        match reference {
+            ProductRef::Sku(sku) => self.products.find_by_sku(sku).await?
+                .ok_or_else(|| ServiceError::Validation(format!("Unknown {}", reference))),
+            ProductRef::Name(name) => {
+                let mut matches = self.products.find_by_name(name).await?;
+                match matches.len() {
+                    0 => Err(ServiceError::Validation(format!("Unknown {}", reference))),
+                    1 => Ok(matches.remove(0)),
+                    _ => Err(ServiceError::Validation(format!(
+                        "{} matches several SKUs; order it by sku instead",
+                        reference
+                    ))),
+                }
+            }
+        }
+    }
+
+    /// Finds the customer an order refers to. An unknown id is a validation
+    /// error; an unknown name is left for the repository to create along
+    /// with the order.
+    async fn resolve_customer(&self, id: Option<i32>, name: Option<&str>) -> Result<OrderCustomer, ServiceError> {
+        match (id, name) {
+            (Some(id), _) => self.customers.find_by_id(id).await?
+                .map(OrderCustomer::from)
+                .ok_or_else(|| ServiceError::Validation(format!("Customer with id {} does not exist", id))),
+            (None, Some(name)) => Ok(match self.customers.find_by_name(name).await? {
+                Some(customer) => customer.into(),
+                None => OrderCustomer::New(name.to_string()),
+            }),
+            (None, None) => Err(ServiceError::Validation("A customer is required".to_string())),
+        }
+    }
+}
+
+/// Per-item results of a bulk operation, in request order.
+pub struct BulkOutcome<T> {
+    pub mode: BulkMode,
+    /// Whether anything was written
+    pub committed: bool,
+    pub items: Vec<BulkItem<T>>,
+}
+
+pub struct BulkItem<T> {
+    pub id: Option<i32>,
+    pub result: Result<T, ServiceError>,
+}
+
+impl<T> BulkOutcome<T> {
+    /// Lines results up with the request. `prepared` has an error for each
+    /// item that failed before reaching the repository and `None` for the
+    /// rest; `written` holds the repository results for the `None` entries
+    /// and stops early if an all-or-nothing batch was rolled back. In that
+    /// mode every item that did not fail itself is marked as rolled back.
+    fn assemble(
+        mode: BulkMode,
+        ids: Vec<Option<i32>>,
+        prepared: Vec<Option<ServiceError>>,
+        written: Vec<Result<T, ServiceError>>,
+    ) -> Self {
+        let mut written = written.into_iter();
+        let mut results: Vec<Option<Result<T, ServiceError>>> = prepared
+            .into_iter()
+            .map(|failure| match failure {
+                Some(error) => Some(Err(error)),
+                None => written.next(),
+            })
+            .collect();
+
+        let first_failure = results.iter().position(|result| matches!(result, Some(Err(_))));
+        if let (BulkMode::AllOrNothing, Some(index)) = (mode, first_failure) {
+            for result in results.iter_mut().filter(|result| !matches!(result, Some(Err(_)))) {
+                *result = Some(Err(ServiceError::BulkRolledBack { index }));
+            }
+        }
+
+        let committed = results.iter().any(|result| matches!(result, Some(Ok(_))));
+        let items = ids
+            .into_iter()
+            .zip(results)
+            .map(|(id, result)| BulkItem {
+                id,
+                result: result.unwrap_or(Err(ServiceError::BulkRolledBack { index: first_failure.unwrap_or(0) })),
+            })
+            .collect();
+
+        Self { mode, committed, items }
+    }
+
+    pub fn failed(&self) -> usize {
+        self.items.iter().filter(|item| item.result.is_err()).count()
+    }
+}
+
+/// One read of an order stream. `more` is set when the batch was full and
+/// the caller should read again before waiting.
+pub struct StreamBatch {
+    pub events: Vec<OrderStreamEvent>,
+    pub cursor: i64,
+    pub more: bool,
+}
+
+/// Results of a CSV import; `lines[i]` is the file line of `orders.items[i]`.
+pub struct ImportOutcome {
+    pub lines: Vec<u64>,
+    pub orders: BulkOutcome<Order>,
+}
+
+/// An all-or-nothing batch with an item that already failed validation is
+/// not sent to the repository at all.
+fn should_write(mode: BulkMode, prepared: &[Option<ServiceError>]) -> bool {
+    let failed = prepared.iter().filter(|failure| failure.is_some()).count();
+    match mode {
+        BulkMode::AllOrNothing => failed == 0,
+        BulkMode::PerItem => failed < prepared.len(),
+    }
+}
+
+/// Maps a repository failure for one item of a bulk write.
+fn write_error(id: Option<i32>, if_match: Option<i64>, e: RepositoryError) -> ServiceError {
+    match (id, e) {
+        (Some(id), RepositoryError::Database(sqlx::Error::RowNotFound)) => ServiceError::OrderNotFound { id },
+        (_, RepositoryError::InsufficientStock { product_id, requested, available }) => {
+            ServiceError::InsufficientStock { product_id, requested, available }
+        }
+        (Some(id), RepositoryError::VersionConflict { actual, .. }) => {
+            version_conflict_error(id, if_match.is_some(), actual)
+        }
+        (_, e) => ServiceError::Repository(e),
+    }
+}
+
+enum IdempotencyCheck {
+    Replay(Order),
+    New(IdempotencyKey),
+}
+
+/// Whether a stream event passes the status and customer filters, judged
+/// on the order as it is now and on the event's before and after values.
+fn stream_matches(query: &OrderStreamQuery, event: &OrderEvent, order: Option<&Order>) -> bool {
+    let in_event = |field: &str, expected: Value| {
+        [&event.old_values, &event.new_values]
+            .into_iter()
+            .flatten()
+            .any(|values| values.0.get(field) == Some(&expected))
+    };
+
+    query.status.is_none_or(|status| {
+        order.is_some_and(|order| order.status == status) || in_event("status", serde_json::json!(status))
+    }) && query.customer_id.is_none_or(|customer_id| {
+        order.is_some_and(|order| order.customer_id == customer_id)
+            || in_event("customer_id", serde_json::json!(customer_id))
+    })
+}
+
+/// SHA-256 of the request as re-serialised, so formatting and key order in
+/// the client's JSON do not matter.
+fn fingerprint(request: &CreateOrderRequest) -> String {
+    let body = serde_json::to_vec(request).unwrap_or_default();
+    hex::encode(Sha256::digest(&body))
+}
+
+/// A lost race is a failed precondition when the client asked for specific
+/// versions with `If-Match`, and a plain conflict when it did not.
+fn version_conflict_error(id: i32, preconditioned: bool, actual: i64) -> ServiceError {
+    if preconditioned {
+        ServiceError::PreconditionFailed { id, actual }
+    } else {
+        ServiceError::ConcurrentModification { id }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use crate::memory_repository::{
+        InMemoryCustomerRepository, InMemoryOrderRepository, InMemoryProductRepository, InMemoryStatusReporter,
+    };
+    use crate::models::CreateProductRequest;
+
+    struct Fixture {
+        service: OrderService,
+        reporter: Arc<InMemoryStatusReporter>,
+    }
+
+    /// An order service over in-memory storage with two products in the
+    /// catalog: `WIDGET` at 2.50 and `GADGET` at 10.00.
+    async fn fixture(allow_price_override: bool) -> Fixture {
+        let products = Arc::new(InMemoryProductRepository::new());
+        for (sku, name, price) in [("WIDGET", "Widget", Decimal::new(250, 2)), ("GADGET", "Gadget", Decimal::new(1000, 2))] {
+            products
+                .create(CreateProductRequest {
+                    sku: sku.to_string(),
+                    name: name.to_string(),
+                    list_price: price,
+                    active: None,
+                    stock_on_hand: Some(100),
+                })
+                .await
+                .unwrap();
+        }
+
+        let reporter = Arc::new(InMemoryStatusReporter::new());
+        let customers = Arc::new(InMemoryCustomerRepository::new());
+        let service = OrderService::new(
+            Arc::new(InMemoryOrderRepository::with_customers(&customers)),
+            customers,
+            products,
+            reporter.clone(),
+            allow_price_override,
+            Duration::from_secs(3600),
+            Duration::from_secs(3600),
+        );
+
+        Fixture { service, reporter }
+    }
+
+    fn order_request(customer: &str, sku: &str, quantity: i32) -> CreateOrderRequest {
+        CreateOrderRequest {
+            customer_id: None,
+            customer_name: Some(customer.to_string()),
+            sku: Some(sku.to_string()),
+            product_name: None,
+            quantity: Some(quantity),
+            unit_price: None,
+            items: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn create_order_prices_lines_from_the_catalog() {
+        let Fixture { service, reporter } = fixture(false).await;
+
+        let (order, replayed) = service
+            .create_order(order_request("Acme", "WIDGET", 4), None, "client", "test")
+            .await
+            .unwrap();
+
+        assert!(!replayed);
+        assert_eq!(order.status, OrderStatus::Pending);
+        assert_eq!(order.customer_name, "Acme");
+        assert_eq!(order.unit_price, Decimal::new(250, 2));
+        assert_eq!(order.total_amount, Decimal::new(1000, 2));
+        assert_eq!(reporter.outcomes(), vec![("create_order".to_string(), true)]);
+    }
+
+    #[tokio::test]
+    async fn create_order_rejects_a_price_override_unless_enabled() {
+        let mut request = order_request("Acme", "WIDGET", 1);
+        request.unit_price = Some(Decimal::new(100, 2));
+
+        let Fixture { service, reporter } = fixture(false).await;
+        let error = service.create_order(request, None, "client", "test").await.unwrap_err();
+        assert!(matches!(error, ServiceError::Validation(_)), "{error}");
+        assert_eq!(reporter.outcomes(), vec![("create_order".to_string(), false)]);
+
+        let mut request = order_request("Acme", "WIDGET", 1);
+        request.unit_price = Some(Decimal::new(100, 2));
+
+        let Fixture { service, .. } = fixture(true).await;
+        let (order, _) = service.create_order(request, None, "client", "test").await.unwrap();
+        assert_eq!(order.unit_price, Decimal::new(100, 2));
+    }
+
+    #[tokio::test]
+    async fn create_order_rejects_unknown_products_and_customers() {
+        let Fixture { service, .. } = fixture(false).await;
+
+        let error = service
+            .create_order(order_request("Acme", "NOPE", 1), None, "client", "test")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ServiceError::Validation(_)), "{error}");
+
+        let mut request = order_request("Acme", "WIDGET", 1);
+        request.customer_name = None;
+        request.customer_id = Some(42);
+        let error = service.create_order(request, None, "client", "test").await.unwrap_err();
+        assert!(matches!(error, ServiceError::Validation(_)), "{error}");
+    }
+
+    #[tokio::test]
+    async fn status_changes_follow_the_lifecycle() {
+        let Fixture { service, .. } = fixture(false).await;
+        let (order, _) = service
+            .create_order(order_request("Acme", "WIDGET", 1), None, "client", "test")
+            .await
+            .unwrap();
+
+        for status in [OrderStatus::Processing, OrderStatus::Shipped, OrderStatus::Delivered] {
+            let updated = service.change_status(order.id, status, "test").await.unwrap();
+            assert_eq!(updated.status, status);
+        }
+
+        let error = service
+            .change_status(order.id, OrderStatus::Cancelled, "test")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                ServiceError::InvalidStatusTransition { from: OrderStatus::Delivered, to: OrderStatus::Cancelled, .. }
+            ),
+            "{error}"
+        );
+
+        let history = service.get_order_history(order.id).await.unwrap();
+        assert_eq!(history.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn pending_orders_cannot_skip_to_shipped() {
+        let Fixture { service, .. } = fixture(false).await;
+        let (order, _) = service
+            .create_order(order_request("Acme", "WIDGET", 1), None, "client", "test")
+            .await
+            .unwrap();
+
+        let error = service
+            .change_status(order.id, OrderStatus::Shipped, "test")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ServiceError::InvalidStatusTransition { .. }), "{error}");
+        assert_eq!(service.get_order(order.id, false).await.unwrap().status, OrderStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn shipped_orders_lock_their_items() {
+        let Fixture { service, .. } = fixture(false).await;
+        let (order, _) = service
+            .create_order(order_request("Acme", "WIDGET", 1), None, "client", "test")
+            .await
+            .unwrap();
+        service.change_status(order.id, OrderStatus::Processing, "test").await.unwrap();
+        service.change_status(order.id, OrderStatus::Shipped, "test").await.unwrap();
+
+        let request = UpdateOrderRequest {
+            quantity: Some(5),
+            ..Default::default()
+        };
+        let error = service.update_order(order.id, request, &VersionMatch::Any, "test").await.unwrap_err();
+        assert!(matches!(error, ServiceError::OrderLocked { status: OrderStatus::Shipped, .. }), "{error}");
+    }
+
+    #[tokio::test]
+    async fn stale_if_match_fails_the_precondition() {
+        let Fixture { service, .. } = fixture(false).await;
+        let (order, _) = service
+            .create_order(order_request("Acme", "WIDGET", 1), None, "client", "test")
+            .await
+            .unwrap();
+
+        let request = UpdateOrderRequest {
+            quantity: Some(2),
+            ..Default::default()
+        };
+        let updated = service
+            .update_order(order.id, request, &VersionMatch::OneOf(vec![order.version]), "test")
+            .await
+            .unwrap();
+        assert_eq!(updated.version, order.version + 1);
+
+        let request = UpdateOrderRequest {
+            quantity: Some(3),
+            ..Default::default()
+        };
+        let error = service
+            .update_order(order.id, request, &VersionMatch::OneOf(vec![order.version]), "test")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ServiceError::PreconditionFailed { .. }), "{error}");
+    }
+
+    #[tokio::test]
+    async fn if_match_lists_any_acceptable_version() {
+        let Fixture { service, .. } = fixture(false).await;
+        let (order, _) = service
+            .create_order(order_request("Acme", "WIDGET", 1), None, "client", "test")
+            .await
+            .unwrap();
+
+        let request = UpdateOrderRequest {
+            quantity: Some(2),
+            ..Default::default()
+        };
+        let updated = service
+            .update_order(order.id, request, &VersionMatch::OneOf(vec![order.version + 5, order.version]), "test")
+            .await
+            .unwrap();
+
+        // Only weak tags were sent, so no version matches
+        let error = service
+            .delete_order(order.id, &VersionMatch::OneOf(Vec::new()), "test")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ServiceError::PreconditionFailed { .. }), "{error}");
+
+        let error = service
+            .delete_order(order.id, &VersionMatch::OneOf(vec![order.version]), "test")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ServiceError::PreconditionFailed { .. }), "{error}");
+
+        service
+            .delete_order(order.id, &VersionMatch::OneOf(vec![updated.version]), "test")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn idempotency_key_replays_the_same_body_only() {
+        let Fixture { service, .. } = fixture(false).await;
+        let key = Some("retry-1".to_string());
+
+        let (first, replayed) = service
+            .create_order(order_request("Acme", "WIDGET", 1), key.clone(), "client", "test")
+            .await
+            .unwrap();
+        assert!(!replayed);
+
+        let (second, replayed) = service
+            .create_order(order_request("Acme", "WIDGET", 1), key.clone(), "client", "test")
+            .await
+            .unwrap();
+        assert!(replayed);
+        assert_eq!(second.id, first.id);
+
+        let error = service
+            .create_order(order_request("Acme", "WIDGET", 2), key, "client", "test")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ServiceError::IdempotencyKeyReused { .. }), "{error}");
+
+        let (_, total) = service.get_orders(&OrderQuery::default()).await.unwrap();
+        assert_eq!(total, 1);
+    }
+
+    #[tokio::test]
+    async fn idempotency_keys_are_scoped_to_the_principal() {
+        let Fixture { service, .. } = fixture(false).await;
+        let key = Some("retry-1".to_string());
+
+        let (first, _) = service
+            .create_order(order_request("Acme", "WIDGET", 1), key.clone(), "api-key:1", "test")
+            .await
+            .unwrap();
+        let (second, replayed) = service
+            .create_order(order_request("Acme", "WIDGET", 1), key, "api-key:2", "test")
+            .await
+            .unwrap();
+
+        assert!(!replayed);
+        assert_ne!(second.id, first.id);
+    }
+
+    #[tokio::test]
+    async fn bulk_create_rolls_back_or_keeps_items_by_mode() {
+        let orders = || {
+            vec![
+                order_request("Acme", "WIDGET", 1),
+                order_request("Acme", "NOPE", 1),
+                order_request("Acme", "GADGET", 1),
+            ]
+        };
+
+        let Fixture { service, .. } = fixture(false).await;
+        let outcome = service
+            .bulk_create_orders(BulkCreateRequest { mode: BulkMode::AllOrNothing, orders: orders() }, "test")
+            .await
+            .unwrap();
+        assert!(!outcome.committed);
+        assert_eq!(outcome.failed(), 3);
+        assert!(matches!(outcome.items[0].result, Err(ServiceError::BulkRolledBack { index: 1 })));
+        assert!(matches!(outcome.items[1].result, Err(ServiceError::Validation(_))));
+        let (_, total) = service.get_orders(&OrderQuery::default()).await.unwrap();
+        assert_eq!(total, 0);
+
+        let outcome = service
+            .bulk_create_orders(BulkCreateRequest { mode: BulkMode::PerItem, orders: orders() }, "test")
+            .await
+            .unwrap();
+        assert!(outcome.committed);
+        assert_eq!(outcome.failed(), 1);
+        let (_, total) = service.get_orders(&OrderQuery::default()).await.unwrap();
+        assert_eq!(total, 2);
+    }
+
+    #[tokio::test]
+    async fn export_keeps_formula_like_text_as_text() {
+        let Fixture { service, .. } = fixture(false).await;
+        service
+            .create_order(order_request("=HYPERLINK(\"http://x\")", "WIDGET", 1), None, "client", "test")
+            .await
+            .unwrap();
+        service
+            .create_order(order_request("Acme", "GADGET", 1), None, "client", "test")
+            .await
+            .unwrap();
+
+        let csv = service.export_orders(&OrderQuery::default()).await.unwrap();
+
+        assert!(csv.contains("'=HYPERLINK"), "{csv}");
+        assert!(csv.contains(",Acme,"), "{csv}");
+    }
+}
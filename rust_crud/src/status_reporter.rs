@@ -1,30 +1,84 @@
-use chrono::Utc;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use reqwest::Client;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use crate::models::StatusReport;
+use tokio::sync::{watch, Notify};
+use crate::metrics::Metrics;
+use crate::models::{OutboxEntry, StatusReport};
+use crate::outbox_repository::OutboxRepository;
+use crate::repository::RepositoryError;
 
-pub struct StatusReporter {
-    client: Client,
-    endpoint: String,
+/// Reports fetched per pass of the dispatcher.
+const DISPATCH_BATCH_SIZE: i64 = 100;
+
+/// How often the dispatcher deletes delivered reports past their retention.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How recent deliveries to the status endpoint went.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DeliveryStats {
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+    /// Failed attempts since the last successful delivery
+    pub consecutive_failures: u32,
 }
 
-impl StatusReporter {
-    pub fn new(endpoint: String, timeout: Duration) -> Self {
-        let client = Client::builder()
-            .timeout(timeout)
-            .build()
-            .expect("Failed to create HTTP client");
+/// Where services report the outcome of every operation.
+/// `OutboxStatusReporter` is the production implementation.
+#[async_trait]
+pub trait StatusReporter: Send + Sync {
+    async fn report_status(
+        &self,
+        operation: &str,
+        success: bool,
+        details: Option<String>,
+        order_id: Option<i32>,
+    );
+
+    async fn report_success(&self, operation: &str, order_id: Option<i32>) {
+        self.report_status(operation, true, None, order_id).await;
+    }
+
+    async fn report_failure(&self, operation: &str, error: &str, order_id: Option<i32>) {
+        self.report_status(operation, false, Some(error.to_string()), order_id).await;
+    }
+}
+
+/// Writes status reports to the outbox; `StatusDispatcher` delivers them.
+pub struct OutboxStatusReporter {
+    outbox: Arc<OutboxRepository>,
+    wakeup: Arc<Notify>,
+    metrics: Arc<Metrics>,
+    delivery: Arc<Mutex<DeliveryStats>>,
+}
 
-        Self { client, endpoint }
+impl OutboxStatusReporter {
+    pub fn new(outbox: Arc<OutboxRepository>, metrics: Arc<Metrics>) -> Self {
+        Self {
+            outbox,
+            wakeup: Arc::new(Notify::new()),
+            metrics,
+            delivery: Arc::new(Mutex::new(DeliveryStats::default())),
+        }
     }
 
-    pub async fn report_status(
+    pub fn delivery_stats(&self) -> DeliveryStats {
+        *self.delivery.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl StatusReporter for OutboxStatusReporter {
+    async fn report_status(
         &self,
         operation: &str,
         success: bool,
         details: Option<String>,
         order_id: Option<i32>,
     ) {
+        self.metrics.record_operation(operation, success);
+
         let report = StatusReport {
             operation: operation.to_string(),
             success,
@@ -33,35 +87,181 @@ impl StatusReporter {
             order_id,
         };
 
-        // Send status report in a non-blocking way
-        let client = self.client.clone();
-        let endpoint = self.endpoint.clone();
-        
-        tokio::spawn(async move {
-            match client.post(&endpoint).json(&report).send().await {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        tracing::debug!("Status report sent successfully for operation: {}", operation);
-                    } else {
-                        tracing::warn!(
-                            "Status report failed with status {}: {}",
-                            response.status(),
-                            operation
-                        );
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to send status report for operation {}: {}", operation, e);
-                }
+        // The operation being reported has already happened, so a failure
+        // to queue its report is logged rather than returned
+        match self.outbox.enqueue(&report).await {
+            Ok(_) => self.wakeup.notify_one(),
+            Err(e) => {
+                tracing::error!("Failed to queue status report for operation {}: {}", operation, e);
             }
-        });
+        }
     }
+}
 
-    pub async fn report_success(&self, operation: &str, order_id: Option<i32>) {
-        self.report_status(operation, true, None, order_id).await;
+/// How often and how long a delivery is retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// How often the outbox is checked for retries that have come due
+    pub poll_interval: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before the next attempt after `attempts` failures: doubles from
+    /// `base_delay` up to `max_delay`.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
     }
+}
 
-    pub async fn report_failure(&self, operation: &str, error: &str, order_id: Option<i32>) {
-        self.report_status(operation, false, Some(error.to_string()), order_id).await;
+/// Background task that posts queued reports to the status endpoint.
+pub struct StatusDispatcher {
+    client: Client,
+    endpoint: String,
+    outbox: Arc<OutboxRepository>,
+    wakeup: Arc<Notify>,
+    metrics: Arc<Metrics>,
+    delivery: Arc<Mutex<DeliveryStats>>,
+    retry: RetryPolicy,
+    /// How long delivered reports are kept
+    retention: Duration,
+}
+
+impl StatusDispatcher {
+    pub fn new(
+        reporter: &OutboxStatusReporter,
+        endpoint: String,
+        timeout: Duration,
+        retry: RetryPolicy,
+        retention: Duration,
+    ) -> Self {
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            endpoint,
+            outbox: reporter.outbox.clone(),
+            wakeup: reporter.wakeup.clone(),
+            metrics: reporter.metrics.clone(),
+            delivery: reporter.delivery.clone(),
+            retry,
+            retention,
+        }
+    }
+
+    /// Runs until `stop` turns true, then makes a last pass over what is
+    /// due and exits.
+    pub fn spawn(self, stop: watch::Receiver<bool>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(self.run(stop))
+    }
+
+    /// Delivers due reports whenever one is queued, and at least every
+    /// `poll_interval` so that retries go out once their backoff has passed.
+    /// Delivered reports are purged every `PURGE_INTERVAL`.
+    async fn run(self, mut stop: watch::Receiver<bool>) {
+        let mut stopping = false;
+        let mut purged_at: Option<tokio::time::Instant> = None;
+        loop {
+            if let Err(e) = self.dispatch_due().await {
+                tracing::error!("Status dispatcher failed to read the outbox: {}", e);
+            }
+
+            if purged_at.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL) {
+                self.purge_delivered().await;
+                purged_at = Some(tokio::time::Instant::now());
+            }
+
+            if stopping {
+                tracing::info!("Status dispatcher stopped");
+                return;
+            }
+
+            stopping = tokio::select! {
+                _ = self.wakeup.notified() => false,
+                _ = tokio::time::sleep(self.retry.poll_interval) => false,
+                _ = stop.wait_for(|stop| *stop) => true,
+            };
+        }
     }
-}
\ No newline at end of file
+
+    async fn dispatch_due(&self) -> Result<(), RepositoryError> {
+        loop {
+            let due = self.outbox.find_due(Utc::now(), DISPATCH_BATCH_SIZE).await?;
+            let fetched = due.len() as i64;
+
+            for entry in due {
+                self.dispatch(entry).await?;
+            }
+
+            if fetched < DISPATCH_BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn purge_delivered(&self) {
+        let retention = chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        let delivered_before = Utc::now().checked_sub_signed(retention).unwrap_or(DateTime::<Utc>::MIN_UTC);
+        match self.outbox.purge_delivered(delivered_before).await {
+            Ok(0) => {}
+            Ok(purged) => tracing::info!("Purged {} delivered status reports", purged),
+            Err(e) => tracing::error!("Failed to purge delivered status reports: {}", e),
+        }
+    }
+
+    async fn dispatch(&self, entry: OutboxEntry) -> Result<(), RepositoryError> {
+        let error = match self.client.post(&self.endpoint).json(&entry.report()).send().await {
+            Ok(response) if response.status().is_success() => {
+                tracing::debug!("Status report sent successfully for operation: {}", entry.operation);
+                self.metrics.record_status_report_sent();
+                self.record_delivery(true);
+                return self.outbox.mark_delivered(entry.id).await;
+            }
+            Ok(response) => format!("Status endpoint responded with {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+
+        let attempts = entry.attempts as u32 + 1;
+        let dead_lettered = attempts >= self.retry.max_attempts;
+        self.metrics.record_status_report_failed(dead_lettered);
+        self.record_delivery(false);
+        if dead_lettered {
+            tracing::error!(
+                "Status report {} for operation {} failed after {} attempts and was dead-lettered: {}",
+                entry.id,
+                entry.operation,
+                attempts,
+                error
+            );
+            return self.outbox.mark_failed(entry.id, &error, None).await;
+        }
+
+        let delay = self.retry.backoff(attempts);
+        tracing::warn!(
+            "Status report {} for operation {} failed (attempt {}), retrying in {:?}: {}",
+            entry.id,
+            entry.operation,
+            attempts,
+            delay,
+            error
+        );
+        self.outbox.mark_failed(entry.id, &error, Some(Utc::now() + delay)).await
+    }
+
+    fn record_delivery(&self, success: bool) {
+        let mut stats = self.delivery.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if success {
+            stats.last_success_at = Some(Utc::now());
+            stats.consecutive_failures = 0;
+        } else {
+            stats.last_failure_at = Some(Utc::now());
+            stats.consecutive_failures += 1;
+        }
+    }
+}
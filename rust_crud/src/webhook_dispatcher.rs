@@ -0,0 +1,315 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use crate::models::PendingDelivery;
+use crate::repository::RepositoryError;
+use crate::status_reporter::RetryPolicy;
+use crate::webhook_repository::WebhookRepository;
+
+/// Deliveries fetched per pass of the dispatcher.
+const DISPATCH_BATCH_SIZE: i64 = 100;
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Background task that posts queued webhook deliveries to subscribers.
+pub struct WebhookDispatcher {
+    client: Client,
+    repository: Arc<WebhookRepository>,
+    wakeup: Arc<Notify>,
+    retry: RetryPolicy,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        repository: Arc<WebhookRepository>,
+        wakeup: Arc<Notify>,
+        timeout: Duration,
+        retry: RetryPolicy,
+    ) -> Self {
+        let client = Client::builder()
+            .timeout(timeout)
+            .redirect(Policy::none())
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            repository,
+            wakeup,
+            retry,
+        }
+    }
+
+    /// Runs until `stop` turns true, then makes a last pass over what is
+    /// due and exits.
+    pub fn spawn(self, stop: watch::Receiver<bool>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(self.run(stop))
+    }
+
+    /// Order writes queue deliveries inside their own transactions, so new
+    /// work is picked up every `poll_interval`; manual redeliveries wake the
+    /// dispatcher straight away.
+    async fn run(self, mut stop: watch::Receiver<bool>) {
+        let mut stopping = false;
+        loop {
+            if let Err(e) = self.dispatch_due().await {
+                tracing::error!("Webhook dispatcher failed to read deliveries: {}", e);
+            }
+
+            if stopping {
+                tracing::info!("Webhook dispatcher stopped");
+                return;
+            }
+
+            stopping = tokio::select! {
+                _ = self.wakeup.notified() => false,
+                _ = tokio::time::sleep(self.retry.poll_interval) => false,
+                _ = stop.wait_for(|stop| *stop) => true,
+            };
+        }
+    }
+
+    async fn dispatch_due(&self) -> Result<(), RepositoryError> {
+        loop {
+            let due = self.repository.find_due(Utc::now(), DISPATCH_BATCH_SIZE).await?;
+            let fetched = due.len() as i64;
+
+            for delivery in due {
+                self.dispatch(delivery).await?;
+            }
+
+            if fetched < DISPATCH_BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn dispatch(&self, delivery: PendingDelivery) -> Result<(), RepositoryError> {
+        let response = match check_literal_host(&delivery.url) {
+            Ok(()) => {
+                let timestamp = Utc::now().timestamp();
+                self.client
+                    .post(&delivery.url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(SIGNATURE_HEADER, sign(&delivery.secret, timestamp, &delivery.payload))
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(EVENT_HEADER, delivery.event_type.as_str())
+                    .header(DELIVERY_HEADER, delivery.id.to_string())
+                    .body(delivery.payload.clone())
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            Err(error) => Err(error),
+        };
+
+        let (response_status, error) = match response {
+            Ok(response) if response.status().is_success() => {
+                tracing::debug!("Webhook delivery {} sent to webhook {}", delivery.id, delivery.webhook_id);
+                return self.repository.mark_delivered(delivery.id, response.status().as_u16()).await;
+            }
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                format!("Subscriber responded with {}", response.status()),
+            ),
+            Err(error) => (None, error),
+        };
+
+        let attempts = delivery.attempts as u32 + 1;
+        if attempts >= self.retry.max_attempts {
+            tracing::warn!(
+                "Webhook delivery {} to webhook {} failed after {} attempts: {}",
+                delivery.id,
+                delivery.webhook_id,
+                attempts,
+                error
+            );
+            return self.repository.mark_failed(delivery.id, response_status, &error, None).await;
+        }
+
+        let delay = self.retry.backoff(attempts);
+        tracing::debug!(
+            "Webhook delivery {} to webhook {} failed (attempt {}), retrying in {:?}: {}",
+            delivery.id,
+            delivery.webhook_id,
+            attempts,
+            delay,
+            error
+        );
+        self.repository
+            .mark_failed(delivery.id, response_status, &error, Some(Utc::now() + delay))
+            .await
+    }
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `<timestamp>.<body>` keyed
+/// with the webhook's secret, where the timestamp is the Unix time sent in
+/// `X-Webhook-Timestamp`. Subscribers recompute it over the header and the
+/// raw request body, and reject timestamps too far from their own clock so
+/// that a captured request cannot be replayed.
+fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Resolves subscriber hosts, refusing any that point inside our network so
+/// that a webhook URL cannot be used to reach internal services or the cloud
+/// metadata endpoint. Checked on every connection, so a host re-pointed after
+/// registration is caught too.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolve_public(&host).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+async fn resolve_public(host: &str) -> Result<Vec<SocketAddr>, Box<dyn std::error::Error + Send + Sync>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        return Err(format!("{} resolves to non-public address {}", host, addr.ip()).into());
+    }
+    Ok(addrs)
+}
+
+/// URLs with an IP address for a host never reach the resolver, so they are
+/// checked here instead.
+fn check_literal_host(url: &str) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+    let host = url.host_str().unwrap_or_default();
+    let ip = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => return Ok(()),
+    };
+    if is_public(ip) {
+        Ok(())
+    } else {
+        Err(format!("Webhook URL points to non-public address {}", ip))
+    }
+}
+
+/// False for loopback, private, link-local, shared, multicast and other
+/// special-purpose ranges.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space (carrier-grade NAT)
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        // Benchmarking
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local
+        || (first & 0xfe00) == 0xfc00
+        // Link-local
+        || (first & 0xffc0) == 0xfe80
+        // Documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let signature = sign("0123456789abcdef", 1_700_000_000, r#"{"order_id":1}"#);
+
+        let mut mac = HmacSha256::new_from_slice(b"0123456789abcdef").unwrap();
+        mac.update(br#"1700000000.{"order_id":1}"#);
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        assert_eq!(signature, expected);
+        assert_ne!(signature, sign("0123456789abcdef", 1_700_000_001, r#"{"order_id":1}"#));
+        assert_ne!(signature, sign("0123456789abcdef", 1_700_000_000, r#"{"order_id":2}"#));
+        assert_ne!(signature, sign("fedcba9876543210", 1_700_000_000, r#"{"order_id":1}"#));
+    }
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should not be public", ip);
+        }
+
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[test]
+    fn literal_internal_hosts_are_refused() {
+        assert!(check_literal_host("http://169.254.169.254/latest/meta-data").is_err());
+        assert!(check_literal_host("http://127.0.0.1:8080/hook").is_err());
+        assert!(check_literal_host("http://[::1]/hook").is_err());
+        assert!(check_literal_host("https://93.184.216.34/hook").is_ok());
+        assert!(check_literal_host("https://partner.example.com/hook").is_ok());
+    }
+
+    #[tokio::test]
+    async fn hosts_resolving_to_loopback_are_refused() {
+        assert!(resolve_public("localhost").await.is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::{QueryBuilder, Sqlite};
+use crate::database::DatabasePool;
+use crate::models::{
+    Webhook, CreateWebhookRequest, UpdateWebhookRequest, WebhookDelivery, WebhookDeliveryQuery, PendingDelivery,
+};
+use crate::repository::RepositoryError;
+
+const WEBHOOK_COLUMNS: &str = "id, url, secret, event_types, active, created_at, updated_at";
+const DELIVERY_COLUMNS: &str = "id, webhook_id, event_id, event_type, order_id, payload, state, attempts, next_attempt_at, response_status, last_error, redelivery_of, created_at, delivered_at";
+
+pub struct WebhookRepository {
+    pool: DatabasePool,
+}
+
+impl WebhookRepository {
+    pub fn new(pool: DatabasePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(&self, request: CreateWebhookRequest) -> Result<Webhook, RepositoryError> {
+        let active = request.active.unwrap_or(true);
+        let now = Utc::now();
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO webhooks (url, secret, event_types, active, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&request.url)
+        .bind(&request.secret)
+        .bind(Json(&request.event_types))
+        .bind(active)
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Webhook {
+            id: result.last_insert_rowid() as i32,
+            url: request.url,
+            secret: request.secret,
+            event_types: Json(request.event_types),
+            active,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<Webhook>, RepositoryError> {
+        let webhooks = sqlx::query_as::<_, Webhook>(
+            &format!("SELECT {} FROM webhooks ORDER BY id", WEBHOOK_COLUMNS)
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<Webhook>, RepositoryError> {
+        let webhook = sqlx::query_as::<_, Webhook>(
+            &format!("SELECT {} FROM webhooks WHERE id = ?", WEBHOOK_COLUMNS)
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    pub async fn update(&self, id: i32, request: UpdateWebhookRequest) -> Result<Webhook, RepositoryError> {
+        let current = self.find_by_id(id).await?
+            .ok_or_else(|| RepositoryError::Database(sqlx::Error::RowNotFound))?;
+
+        let webhook = Webhook {
+            url: request.url.unwrap_or(current.url),
+            secret: request.secret.unwrap_or(current.secret),
+            event_types: request.event_types.map(Json).unwrap_or(current.event_types),
+            active: request.active.unwrap_or(current.active),
+            updated_at: Utc::now(),
+            ..current
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE webhooks
+            SET url = ?, secret = ?, event_types = ?, active = ?, updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(&webhook.event_types)
+        .bind(webhook.active)
+        .bind(webhook.updated_at.to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    /// Deleting a webhook also drops its delivery log.
+    pub async fn delete(&self, id: i32) -> Result<bool, RepositoryError> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns the delivery log for a webhook, newest first.
+    pub async fn find_deliveries(
+        &self,
+        webhook_id: i32,
+        query: &WebhookDeliveryQuery,
+    ) -> Result<Vec<WebhookDelivery>, RepositoryError> {
+        let mut select = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {} FROM webhook_deliveries WHERE webhook_id = ",
+            DELIVERY_COLUMNS
+        ));
+        select.push_bind(webhook_id);
+        if let Some(state) = query.state {
+            select.push(" AND state = ").push_bind(state);
+        }
+        select.push(" ORDER BY id DESC LIMIT ").push_bind(query.limit());
+        select.push(" OFFSET ").push_bind(query.offset());
+
+        let deliveries = select
+            .build_query_as::<WebhookDelivery>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Queues a fresh copy of a logged delivery, with the same payload.
+    /// Returns `None` when the webhook has no such delivery.
+    pub async fn redeliver(&self, webhook_id: i32, delivery_id: i64) -> Result<Option<WebhookDelivery>, RepositoryError> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event_id, event_type, order_id, payload, state, attempts, next_attempt_at, redelivery_of, created_at)
+            SELECT webhook_id, event_id, event_type, order_id, payload, 'pending', 0, ?, id, ?
+            FROM webhook_deliveries
+            WHERE id = ? AND webhook_id = ?
+            "#
+        )
+        .bind(&now)
+        .bind(&now)
+        .bind(delivery_id)
+        .bind(webhook_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let delivery = sqlx::query_as::<_, WebhookDelivery>(
+            &format!("SELECT {} FROM webhook_deliveries WHERE id = ?", DELIVERY_COLUMNS)
+        )
+        .bind(result.last_insert_rowid())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    /// Pending deliveries that are due, oldest first. Deliveries for
+    /// inactive webhooks wait until the webhook is reactivated.
+    pub async fn find_due(&self, now: DateTime<Utc>, limit: i64) -> Result<Vec<PendingDelivery>, RepositoryError> {
+        let deliveries = sqlx::query_as::<_, PendingDelivery>(
+            r#"
+            SELECT d.id, d.webhook_id, w.url, w.secret, d.event_type, d.payload, d.attempts
+            FROM webhook_deliveries d
+            JOIN webhooks w ON w.id = d.webhook_id
+            WHERE d.state = 'pending' AND d.next_attempt_at <= ? AND w.active = 1
+            ORDER BY d.id
+            LIMIT ?
+            "#
+        )
+        .bind(now.to_rfc3339())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    pub async fn mark_delivered(&self, id: i64, response_status: u16) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET state = 'delivered', attempts = attempts + 1, response_status = ?, last_error = NULL, delivered_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(i64::from(response_status))
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records a failed attempt. With `next_attempt_at` the delivery is
+    /// retried then; without it the delivery is marked failed.
+    pub async fn mark_failed(
+        &self,
+        id: i64,
+        response_status: Option<u16>,
+        error: &str,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> Result<(), RepositoryError> {
+        let state = if next_attempt_at.is_some() { "pending" } else { "failed" };
+
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET state = ?, attempts = attempts + 1, response_status = ?, last_error = ?,
+                next_attempt_at = COALESCE(?, next_attempt_at)
+            WHERE id = ?
+            "#
+        )
+        .bind(state)
+        .bind(response_status.map(i64::from))
+        .bind(error)
+        .bind(next_attempt_at.map(|at| at.to_rfc3339()))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+use tokio::sync::Notify;
+use validator::Validate;
+use crate::models::{Webhook, CreateWebhookRequest, UpdateWebhookRequest, WebhookDelivery, WebhookDeliveryQuery};
+use crate::repository::RepositoryError;
+use crate::service::ServiceError;
+use crate::status_reporter::StatusReporter;
+use crate::webhook_repository::WebhookRepository;
+
+pub struct WebhookService {
+    repository: Arc<WebhookRepository>,
+    status_reporter: Arc<dyn StatusReporter>,
+    dispatcher_wakeup: Arc<Notify>,
+}
+
+impl WebhookService {
+    pub fn new(
+        repository: Arc<WebhookRepository>,
+        status_reporter: Arc<dyn StatusReporter>,
+        dispatcher_wakeup: Arc<Notify>,
+    ) -> Self {
+        Self {
+            repository,
+            status_reporter,
+            dispatcher_wakeup,
+        }
+    }
+
+    pub async fn create_webhook(&self, request: CreateWebhookRequest) -> Result<Webhook, ServiceError> {
+        // Validate the request
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("create_webhook", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        match self.repository.create(request).await {
+            Ok(webhook) => {
+                self.status_reporter
+                    .report_success("create_webhook", None)
+                    .await;
+                Ok(webhook)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to create webhook: {}", e);
+                self.status_reporter
+                    .report_failure("create_webhook", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_webhooks(&self) -> Result<Vec<Webhook>, ServiceError> {
+        match self.repository.find_all().await {
+            Ok(webhooks) => {
+                self.status_reporter
+                    .report_success("get_webhooks", None)
+                    .await;
+                Ok(webhooks)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get webhooks: {}", e);
+                self.status_reporter
+                    .report_failure("get_webhooks", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_webhook(&self, id: i32) -> Result<Webhook, ServiceError> {
+        match self.repository.find_by_id(id).await {
+            Ok(Some(webhook)) => {
+                self.status_reporter
+                    .report_success("get_webhook", None)
+                    .await;
+                Ok(webhook)
+            }
+            Ok(None) => {
+                let error_msg = format!("Webhook not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("get_webhook", &error_msg, None)
+                    .await;
+                Err(ServiceError::WebhookNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get webhook {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("get_webhook", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn update_webhook(&self, id: i32, request: UpdateWebhookRequest) -> Result<Webhook, ServiceError> {
+        // Validate the request
+        if let Err(validation_errors) = request.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("update_webhook", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        match self.repository.update(id, request).await {
+            Ok(webhook) => {
+                self.status_reporter
+                    .report_success("update_webhook", None)
+                    .await;
+                // Reactivating a webhook releases deliveries that were held
+                self.dispatcher_wakeup.notify_one();
+                Ok(webhook)
+            }
+            Err(RepositoryError::Database(sqlx::Error::RowNotFound)) => {
+                let error_msg = format!("Webhook not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("update_webhook", &error_msg, None)
+                    .await;
+                Err(ServiceError::WebhookNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to update webhook {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("update_webhook", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn delete_webhook(&self, id: i32) -> Result<(), ServiceError> {
+        match self.repository.delete(id).await {
+            Ok(true) => {
+                self.status_reporter
+                    .report_success("delete_webhook", None)
+                    .await;
+                Ok(())
+            }
+            Ok(false) => {
+                let error_msg = format!("Webhook not found with id: {}", id);
+                self.status_reporter
+                    .report_failure("delete_webhook", &error_msg, None)
+                    .await;
+                Err(ServiceError::WebhookNotFound { id })
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to delete webhook {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("delete_webhook", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    pub async fn get_webhook_deliveries(
+        &self,
+        id: i32,
+        query: &WebhookDeliveryQuery,
+    ) -> Result<Vec<WebhookDelivery>, ServiceError> {
+        // Validate the query
+        if let Err(validation_errors) = query.validate() {
+            let error_msg = format!("Validation failed: {:?}", validation_errors);
+            self.status_reporter
+                .report_failure("get_webhook_deliveries", &error_msg, None)
+                .await;
+            return Err(ServiceError::Validation(error_msg));
+        }
+
+        // Check the webhook exists so an unknown id is a 404, not an empty log
+        self.get_webhook(id).await?;
+
+        match self.repository.find_deliveries(id, query).await {
+            Ok(deliveries) => {
+                self.status_reporter
+                    .report_success("get_webhook_deliveries", None)
+                    .await;
+                Ok(deliveries)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get deliveries for webhook {}: {}", id, e);
+                self.status_reporter
+                    .report_failure("get_webhook_deliveries", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+
+    /// Queues the logged delivery again. The original entry is left as it
+    /// was so the log keeps every attempt.
+    pub async fn redeliver(&self, id: i32, delivery_id: i64) -> Result<WebhookDelivery, ServiceError> {
+        match self.repository.redeliver(id, delivery_id).await {
+            Ok(Some(delivery)) => {
+                self.status_reporter
+                    .report_success("redeliver_webhook", None)
+                    .await;
+                self.dispatcher_wakeup.notify_one();
+                Ok(delivery)
+            }
+            Ok(None) => {
+                let error = ServiceError::WebhookDeliveryNotFound { webhook_id: id, id: delivery_id };
+                self.status_reporter
+                    .report_failure("redeliver_webhook", &error.to_string(), None)
+                    .await;
+                Err(error)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to redeliver delivery {} of webhook {}: {}", delivery_id, id, e);
+                self.status_reporter
+                    .report_failure("redeliver_webhook", &error_msg, None)
+                    .await;
+                Err(ServiceError::Repository(e))
+            }
+        }
+    }
+}